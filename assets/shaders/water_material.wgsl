@@ -0,0 +1,20 @@
+#import bevy_entitiles::common::{VertexOutput, tilemap, tilemap_color}
+
+struct WaterMaterial {
+    tint: vec4<f32>,
+    speed: f32,
+}
+
+#ifdef PURE_COLOR
+@group(2) @binding(0)
+#else
+@group(4) @binding(0)
+#endif
+var<uniform> material: WaterMaterial;
+
+@fragment
+fn tilemap_fragment(input: VertexOutput) -> @location(0) vec4<f32> {
+    let color = tilemap_color(input);
+    let shimmer = sin(input.position.x * 0.05 + input.position.y * 0.03 + tilemap.time * material.speed);
+    return vec4<f32>(color.rgb * material.tint.rgb * (0.85 + 0.15 * shimmer), color.a);
+}
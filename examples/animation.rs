@@ -1,8 +1,9 @@
 use bevy::{
-    app::{App, Startup},
+    app::{App, Startup, Update},
     asset::AssetServer,
     core_pipeline::core_2d::Camera2dBundle,
-    ecs::system::{Commands, Res},
+    ecs::system::{Commands, Query, Res},
+    input::{keyboard::KeyCode, Input},
     math::{IVec2, UVec2, Vec2},
     render::render_resource::FilterMode,
     DefaultPlugins,
@@ -12,10 +13,10 @@ use bevy_entitiles::{
     tilemap::{
         bundles::TilemapBundle,
         map::{
-            TileRenderSize, TilemapRotation, TilemapSlotSize, TilemapStorage, TilemapTexture,
-            TilemapTextureDescriptor, TilemapType,
+            TileRenderSize, TilemapAnimationPlayback, TilemapLayerOpacities, TilemapRotation,
+            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor, TilemapType,
         },
-        tile::TileBuilder,
+        tile::{TileAnimationMode, TileBuilder, TileLayer},
     },
     EntiTilesPlugin,
 };
@@ -31,6 +32,7 @@ fn main() {
             EntiTilesHelpersPlugin::default(),
         ))
         .add_systems(Startup, setup)
+        .add_systems(Update, toggle_pause)
         .run();
 }
 
@@ -52,23 +54,55 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             ),
             TilemapRotation::None,
         ),
+        layer_opacities: TilemapLayerOpacities(vec![1., 0.5]),
         ..Default::default()
     };
 
     let anim_a = tilemap.animations.register_animation(2, vec![0, 1, 2, 3]);
     let anim_b = tilemap.animations.register_animation(3, vec![0, 1, 2]);
 
-    tilemap.storage.fill_rect(
+    // Offset the animations by the index to make a wave.
+    tilemap.storage.fill_rect_custom(
         &mut commands,
         TileArea::new(IVec2::ZERO, UVec2 { x: 20, y: 20 }),
-        TileBuilder::new().with_animation(anim_a),
+        |index| {
+            Some(
+                TileBuilder::new()
+                    .with_animation(anim_a.with_offset((index.x + index.y) as f32 * 0.1)),
+            )
+        },
+        false,
     );
 
     tilemap.storage.fill_rect(
         &mut commands,
         TileArea::new(IVec2::ZERO, UVec2 { x: 10, y: 10 }),
-        TileBuilder::new().with_animation(anim_b),
+        TileBuilder::new().with_animation(anim_b.with_mode(TileAnimationMode::PingPong)),
+    );
+
+    // Only the top layer is animated.
+    tilemap.storage.fill_rect(
+        &mut commands,
+        TileArea::new(IVec2 { x: 10, y: 10 }, UVec2 { x: 10, y: 10 }),
+        TileBuilder::new()
+            .with_layer(0, TileLayer::new().with_texture_index(0))
+            .with_layer(1, TileLayer::new().with_animation(anim_b)),
     );
 
     commands.entity(entity).insert(tilemap);
 }
+
+fn toggle_pause(
+    mut playbacks_query: Query<&mut TilemapAnimationPlayback>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        playbacks_query.for_each_mut(|mut playback| {
+            if playback.paused {
+                playback.resume();
+            } else {
+                playback.pause();
+            }
+        });
+    }
+}
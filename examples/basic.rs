@@ -90,7 +90,7 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
             1,
             TileLayer::new()
                 .with_texture_index(1)
-                .with_flip(TileFlip::Horizontal),
+                .with_flip(TileFlip::HORIZONTAL),
         ),
     );
 
@@ -101,7 +101,7 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
             0,
             TileLayer::new()
                 .with_texture_index(1)
-                .with_flip(TileFlip::Vertical),
+                .with_flip(TileFlip::VERTICAL),
         ),
     );
 
@@ -112,7 +112,7 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
             0,
             TileLayer::new()
                 .with_texture_index(1)
-                .with_flip(TileFlip::Both),
+                .with_flip(TileFlip::BOTH),
         ),
     );
 
@@ -120,10 +120,10 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
         &mut commands,
         TileArea::new(IVec2 { x: 1, y: 3 }, UVec2 { x: 3, y: 3 }),
         TileUpdater {
-            layer: Some(LayerUpdater {
+            layers: vec![LayerUpdater {
                 position: TileLayerPosition::Top,
                 layer: TileLayer::new().with_texture_index(3),
-            }),
+            }],
             ..Default::default()
         },
     );
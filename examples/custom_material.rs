@@ -0,0 +1,95 @@
+use bevy::{
+    app::{App, Startup},
+    asset::{Asset, AssetServer, Assets},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::system::{Commands, Res, ResMut},
+    math::{IVec2, UVec2, Vec2},
+    reflect::TypePath,
+    render::{
+        color::Color,
+        render_resource::{AsBindGroup, FilterMode, ShaderRef},
+    },
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    render::material::{TilemapMaterial, TilemapMaterialPlugin},
+    tilemap::{
+        bundles::TilemapBundle,
+        map::{
+            TileRenderSize, TilemapRotation, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+            TilemapMaterialPlugin::<WaterMaterial>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct WaterMaterial {
+    #[uniform(0)]
+    tint: Color,
+    #[uniform(0)]
+    speed: f32,
+}
+
+impl TilemapMaterial for WaterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/water_material.wgsl".into()
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<WaterMaterial>>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    let entity = commands.spawn_empty().id();
+    let mut tilemap = TilemapBundle {
+        tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+        slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+        ty: TilemapType::Square,
+        storage: TilemapStorage::new(16, entity),
+        texture: TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(
+                UVec2 { x: 32, y: 32 },
+                UVec2 { x: 16, y: 16 },
+                FilterMode::Nearest,
+            ),
+            TilemapRotation::None,
+        ),
+        ..Default::default()
+    };
+
+    tilemap.storage.fill_rect(
+        &mut commands,
+        TileArea::new(IVec2::ZERO, UVec2 { x: 20, y: 20 }),
+        TileBuilder::new().with_layer(0, TileLayer::new().with_texture_index(0)),
+    );
+
+    commands.entity(entity).insert((
+        tilemap,
+        materials.add(WaterMaterial {
+            tint: Color::rgb(0.4, 0.7, 1.),
+            speed: 2.,
+        }),
+    ));
+}
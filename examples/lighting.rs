@@ -0,0 +1,125 @@
+use bevy::{
+    app::{App, Startup, Update},
+    asset::AssetServer,
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::{
+        query::With,
+        system::{Commands, Query, Res},
+    },
+    math::{IVec2, Quat, UVec2, Vec2},
+    render::{color::Color, render_resource::FilterMode},
+    time::Time,
+    transform::{components::Transform, TransformBundle},
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    math::TileArea,
+    tilemap::{
+        bundles::TilemapBundle,
+        lighting::{TilemapLighting, TilemapPointLight, TilemapSpotLight},
+        map::{
+            TileRenderSize, TilemapRotation, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_lights)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2dBundle::default());
+
+    let entity = commands.spawn_empty().id();
+    let mut tilemap = TilemapBundle {
+        tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
+        slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
+        ty: TilemapType::Square,
+        storage: TilemapStorage::new(16, entity),
+        texture: TilemapTexture::new(
+            asset_server.load("test_square.png"),
+            TilemapTextureDescriptor::new(
+                UVec2 { x: 32, y: 32 },
+                UVec2 { x: 16, y: 16 },
+                FilterMode::Nearest,
+            ),
+            TilemapRotation::None,
+        ),
+        ..Default::default()
+    };
+
+    tilemap.storage.fill_rect(
+        &mut commands,
+        TileArea::new(IVec2::new(-20, -20), UVec2 { x: 40, y: 40 }),
+        TileBuilder::new().with_layer(0, TileLayer::new().with_texture_index(0)),
+    );
+
+    let mut lighting = TilemapLighting::new(16).with_ambient(Color::rgb(0.1, 0.1, 0.15));
+
+    // Some pillars that cast shadows.
+    for pillar in [
+        IVec2::new(-6, -6),
+        IVec2::new(5, -6),
+        IVec2::new(-6, 5),
+        IVec2::new(5, 5),
+    ] {
+        let area = TileArea::new(pillar, UVec2 { x: 2, y: 2 });
+        tilemap.storage.fill_rect(
+            &mut commands,
+            area,
+            TileBuilder::new().with_layer(0, TileLayer::new().with_texture_index(1)),
+        );
+        lighting.fill_occluder_rect(area, true);
+    }
+
+    commands.entity(entity).insert((tilemap, lighting));
+
+    commands.spawn((
+        TilemapPointLight {
+            color: Color::rgb(1., 0.8, 0.5),
+            intensity: 1.5,
+            range: 200.,
+        },
+        TransformBundle::default(),
+    ));
+
+    commands.spawn((
+        TilemapSpotLight {
+            color: Color::rgb(0.5, 0.7, 1.),
+            intensity: 2.,
+            range: 300.,
+            ..Default::default()
+        },
+        TransformBundle::default(),
+    ));
+}
+
+fn move_lights(
+    mut point_lights: Query<&mut Transform, With<TilemapPointLight>>,
+    mut spot_lights: Query<&mut Transform, With<TilemapSpotLight>>,
+    time: Res<Time>,
+) {
+    let t = time.elapsed_seconds();
+
+    point_lights.for_each_mut(|mut transform| {
+        transform.translation = (Vec2::new(t.cos(), t.sin()) * 120.).extend(0.);
+    });
+
+    spot_lights.for_each_mut(|mut transform| {
+        transform.rotation = Quat::from_rotation_z(-t * 0.5);
+    });
+}
@@ -15,7 +15,7 @@ use bevy::{
     DefaultPlugins,
 };
 use bevy_entitiles::{
-    algorithm::pathfinding::{PathFinder, PathFindingQueue},
+    algorithm::pathfinding::{PathAlgorithm, PathFinder, PathFindingQueue},
     math::TileArea,
     tilemap::{
         algorithm::path::{PathTile, PathTilemap},
@@ -92,6 +92,7 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
                 dest: IVec2::splat(499),
                 allow_diagonal: false,
                 max_steps: None,
+                algorithm: PathAlgorithm::AStar,
             },
         )
     });
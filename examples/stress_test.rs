@@ -10,7 +10,7 @@ use bevy::{
 };
 use bevy_entitiles::{
     math::TileArea,
-    render::culling::FrustumCulling,
+    render::{culling::FrustumCulling, lod::TilemapLod},
     tilemap::{
         bundles::TilemapBundle,
         map::{
@@ -66,5 +66,8 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         TileBuilder::new().with_layer(0, TileLayer::new().with_texture_index(0)),
     );
 
-    commands.entity(entity).insert(tilemap);
+    // Draw simplified chunks when zoomed out.
+    commands
+        .entity(entity)
+        .insert((tilemap, TilemapLod::default()));
 }
@@ -0,0 +1,85 @@
+use bevy::{
+    app::{App, PluginGroup, Startup, Update},
+    core_pipeline::core_2d::Camera2dBundle,
+    ecs::{
+        event::EventReader,
+        system::{Commands, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, Input},
+    render::{render_resource::FilterMode, texture::ImagePlugin, view::Msaa},
+    DefaultPlugins,
+};
+use bevy_entitiles::{
+    tiled::{
+        events::TiledEvent,
+        resources::{TiledAssets, TiledLoadConfig, TiledTilemapManager},
+    },
+    EntiTilesPlugin,
+};
+use helpers::EntiTilesHelpersPlugin;
+
+mod helpers;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins.set(ImagePlugin::default_nearest()),
+            EntiTilesPlugin,
+            EntiTilesHelpersPlugin::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (load, events, hot_reload))
+        // turn off msaa to avoid the white lines between tiles
+        .insert_resource(Msaa::Off)
+        .insert_resource(TiledLoadConfig {
+            map_path: vec!["assets/tiled/test_square.tmx".to_string()],
+            asset_path_prefix: "tiled/".to_string(),
+            filter_mode: FilterMode::Nearest,
+            ..Default::default()
+        })
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn load(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    mut manager: ResMut<TiledTilemapManager>,
+) {
+    if input.just_pressed(KeyCode::Key1) {
+        manager.switch_to(&mut commands, "test_square".to_string(), None);
+    }
+
+    if input.just_pressed(KeyCode::Space) {
+        manager.unload_all(&mut commands);
+    }
+}
+
+fn hot_reload(
+    input: Res<Input<KeyCode>>,
+    mut manager: ResMut<TiledTilemapManager>,
+    config: Res<TiledLoadConfig>,
+    mut assets: ResMut<TiledAssets>,
+) {
+    if input.just_pressed(KeyCode::Return) {
+        manager.reload_xml(&config);
+        *assets = TiledAssets::default();
+        println!("Hot reloaded!")
+    }
+}
+
+fn events(mut tiled_events: EventReader<TiledEvent>) {
+    for event in tiled_events.read() {
+        match event {
+            TiledEvent::MapLoaded(map) => {
+                println!("Map loaded: {}", map.name);
+            }
+            TiledEvent::MapUnloaded(map) => {
+                println!("Map unloaded: {}", map.name);
+            }
+        }
+    }
+}
@@ -1,14 +1,17 @@
 use bevy::{
+    app::Update,
     asset::AssetServer,
-    ecs::system::Res,
+    ecs::system::{Query, Res},
+    input::{keyboard::KeyCode, Input},
     math::IVec2,
     prelude::{App, Camera2dBundle, Commands, Startup, UVec2, Vec2},
-    render::render_resource::FilterMode,
+    render::{color::Color, render_resource::FilterMode},
     DefaultPlugins,
 };
 use bevy_entitiles::{
     algorithm::wfc::{WfcRules, WfcRunner, WfcSource},
     math::TileArea,
+    render::grid::TilemapGrid,
     tilemap::{
         bundles::TilemapBundle,
         map::{
@@ -30,6 +33,7 @@ fn main() {
             EntiTilesHelpersPlugin::default(),
         ))
         .add_systems(Startup, setup)
+        .add_systems(Update, toggle_grid)
         .run();
 }
 
@@ -67,5 +71,16 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             ),
             ..Default::default()
         },
+        // Press G to show or hide the grid and the indices of the tiles.
+        TilemapGrid {
+            coordinates: Some(Color::WHITE),
+            ..Default::default()
+        },
     ));
 }
+
+fn toggle_grid(mut grids_query: Query<&mut TilemapGrid>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::G) {
+        grids_query.for_each_mut(|mut grid| grid.visible = !grid.visible);
+    }
+}
@@ -0,0 +1,435 @@
+//! Cooperative pathfinding (WHCA*).
+//!
+//! The agents reserve the tiles they will be on at each step, and the later agents search in
+//! space and time to avoid the reserved tiles, by waiting or taking detours.
+//! Only the first `window` steps of a path are reserved, the rest ignores the other agents,
+//! so the agents should find their paths again before they walk out of the window.
+
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Mutex};
+
+use bevy::{
+    ecs::entity::Entity,
+    math::IVec2,
+    utils::{EntityHashMap, HashMap},
+};
+
+use crate::{
+    math::extension::TileIndex,
+    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+};
+
+use super::{
+    flow_field::{self, FlowFieldRequest},
+    pathfinding::{PathAlgorithm, PathFinder, PathStatus},
+};
+
+/// The tiles reserved by the agents at each step.
+///
+/// The agents are expected to move in lockstep. Call `advance` once all of them
+/// have taken a step, and call `Path::step` once per step, including the waits.
+#[derive(Debug, Default)]
+pub struct ReservationTable {
+    step: u32,
+    reserved: HashMap<(IVec2, u32), Entity>,
+    agents: EntityHashMap<Entity, Vec<(IVec2, u32)>>,
+}
+
+// `EntityHash` is not `Clone`.
+impl Clone for ReservationTable {
+    fn clone(&self) -> Self {
+        Self {
+            step: self.step,
+            reserved: self.reserved.clone(),
+            agents: self
+                .agents
+                .iter()
+                .map(|(agent, tiles)| (*agent, tiles.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl ReservationTable {
+    #[inline]
+    pub fn current_step(&self) -> u32 {
+        self.step
+    }
+
+    /// Move to the next step and forget the reservations in the past.
+    pub fn advance(&mut self) {
+        self.step += 1;
+        let step = self.step;
+        self.reserved.retain(|(_, s), _| *s >= step);
+        self.agents.retain(|_, tiles| {
+            tiles.retain(|(_, s)| *s >= step);
+            !tiles.is_empty()
+        });
+    }
+
+    /// Get the agent that reserved the tile at the step.
+    #[inline]
+    pub fn get(&self, index: IVec2, step: u32) -> Option<Entity> {
+        self.reserved.get(&(index, step)).copied()
+    }
+
+    /// Reserve the tile at the step for the agent.
+    /// Use this to keep the other agents away from the agents that are not moving.
+    ///
+    /// Returns `false` if it's already reserved by another agent.
+    pub fn reserve(&mut self, index: IVec2, step: u32, agent: Entity) -> bool {
+        match self.reserved.get(&(index, step)) {
+            Some(other) => *other == agent,
+            None => {
+                self.reserved.insert((index, step), agent);
+                self.agents.entry(agent).or_default().push((index, step));
+                true
+            }
+        }
+    }
+
+    /// Remove all the reservations of the agent.
+    pub fn release(&mut self, agent: Entity) {
+        if let Some(tiles) = self.agents.remove(&agent) {
+            tiles.into_iter().for_each(|tile| {
+                self.reserved.remove(&tile);
+            });
+        }
+    }
+
+    /// Replace the reservations of the agents with the ones planned on a copy of this table.
+    /// The reservations in the past or taken by others since the copy are skipped.
+    fn commit(&mut self, planned: &ReservationTable, agents: &[Entity]) {
+        for agent in agents {
+            self.release(*agent);
+            let Some(tiles) = planned.agents.get(agent) else {
+                continue;
+            };
+            for (index, step) in tiles {
+                if *step >= self.step {
+                    self.reserve(*index, *step, *agent);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn is_free(&self, index: IVec2, step: u32, agent: Entity) -> bool {
+        self.get(index, step).is_none_or(|other| other == agent)
+    }
+}
+
+/// Find the path for the agent, avoiding the tiles reserved by the other agents
+/// in the first `window` steps of `PathAlgorithm::Cooperative`, and reserve the path.
+/// Other algorithms have no window, so the path ignores the other agents.
+///
+/// Returns the tiles from `dest` back to the first step, like `PathGrid::collect_path`.
+/// The same tile appears multiple times if the agent needs to wait there.
+/// The path is `PathStatus::Unreachable` if the agent is completely blocked by the others.
+pub fn find_path(
+    path_tilemap: &PathTilemap,
+    ty: TilemapType,
+    reservations: &mut ReservationTable,
+    agent: Entity,
+    finder: &PathFinder,
+) -> (PathStatus, Vec<IVec2>) {
+    reservations.release(agent);
+    let window = match finder.algorithm {
+        PathAlgorithm::Cooperative { window } => window,
+        _ => 0,
+    };
+    let (origin, dest) = (finder.origin, finder.dest);
+
+    // The true distances to the destination, ignoring the other agents.
+    let distances = flow_field::integrate(
+        path_tilemap,
+        ty,
+        &FlowFieldRequest {
+            goals: vec![dest],
+            allow_diagonal: finder.allow_diagonal,
+            max_cost: None,
+        },
+    );
+    let Some(h_cost) = distances.get_elem(origin).map(|t| t.cost) else {
+        return (PathStatus::Unreachable, Vec::new());
+    };
+
+    let start = reservations.current_step();
+    // The cost and the parent of each tile at each step after the start.
+    let mut nodes = HashMap::<(IVec2, u32), (u32, Option<IVec2>)>::default();
+    let mut to_explore = BinaryHeap::new();
+    nodes.insert((origin, 0), (0, None));
+    to_explore.push(Reverse((h_cost, 0, 0, origin.to_array())));
+
+    let mut goal = None;
+    let mut steps = 0;
+    let mut limited = false;
+    while let Some(Reverse((_, g_cost, step, index))) = to_explore.pop() {
+        let index = IVec2::from_array(index);
+        if g_cost > nodes[&(index, step)].0 {
+            continue;
+        }
+        if let Some(max_steps) = finder.max_steps {
+            if steps > max_steps {
+                limited = true;
+                break;
+            }
+        }
+        steps += 1;
+
+        // Stay at the destination for the rest of the window, or keep going after the window.
+        if step == window
+            || (index == dest
+                && (step..=window).all(|s| reservations.is_free(dest, start + s, agent)))
+        {
+            goal = Some((index, step));
+            break;
+        }
+
+        // Waiting costs the same as entering the tile again.
+        let wait = Some(index);
+        for next in index
+            .neighbours(ty, finder.allow_diagonal)
+            .into_iter()
+            .chain([wait])
+            .flatten()
+        {
+            let (Some(tile), Some(distance)) = (path_tilemap.get(next), distances.get_elem(next))
+            else {
+                continue;
+            };
+            let now = start + step;
+            if !reservations.is_free(next, now + 1, agent) {
+                continue;
+            }
+            // Two agents can't swap their tiles.
+            if let Some(other) = reservations.get(next, now) {
+                if other != agent && reservations.get(index, now + 1) == Some(other) {
+                    continue;
+                }
+            }
+
+            let g_cost = g_cost + tile.cost;
+            let key = (next, step + 1);
+            if nodes.get(&key).is_some_and(|(old, _)| *old <= g_cost) {
+                continue;
+            }
+            nodes.insert(key, (g_cost, Some(index)));
+            to_explore.push(Reverse((
+                g_cost + distance.cost,
+                g_cost,
+                step + 1,
+                next.to_array(),
+            )));
+        }
+    }
+
+    let (status, (mut last, last_step)) = match goal {
+        Some(goal) => (PathStatus::Found, goal),
+        // Head to the tile closest to the destination instead.
+        None if limited => (
+            PathStatus::StepLimitReached,
+            nodes
+                .iter()
+                .min_by_key(|((index, _), (g_cost, _))| {
+                    (distances.get_elem(*index).map(|t| t.cost), *g_cost)
+                })
+                .map(|(key, _)| *key)
+                .unwrap(),
+        ),
+        None => return (PathStatus::Unreachable, Vec::new()),
+    };
+
+    let mut path = Vec::new();
+    let mut key = (last, last_step);
+    while let Some(parent) = nodes[&key].1 {
+        path.push(key.0);
+        key = (parent, key.1 - 1);
+    }
+    path.reverse();
+
+    reservations.reserve(origin, start, agent);
+    for (step, index) in path.iter().enumerate() {
+        reservations.reserve(*index, start + step as u32 + 1, agent);
+    }
+    if last == dest {
+        for step in last_step + 1..=window {
+            reservations.reserve(dest, start + step, agent);
+        }
+    } else if status == PathStatus::Found {
+        // Outside the window, follow the shortest path.
+        while last != dest {
+            last = distances.get_elem(last).unwrap().next;
+            path.push(last);
+        }
+    }
+
+    path.reverse();
+    (status, path)
+}
+
+/// Find the paths of the agents one after another in the order of the entities,
+/// so the later agents avoid the earlier ones and the result doesn't depend on the scheduling.
+///
+/// The table is only locked to copy it and to commit the new reservations,
+/// so it's not blocked during the search.
+pub fn find_paths(
+    path_tilemap: &PathTilemap,
+    ty: TilemapType,
+    reservations: &Mutex<ReservationTable>,
+    mut requests: Vec<(Entity, PathFinder)>,
+) -> Vec<(Entity, PathStatus, Vec<IVec2>)> {
+    requests.sort_by_key(|(agent, _)| *agent);
+    let mut planned = reservations.lock().unwrap().clone();
+    let paths = requests
+        .iter()
+        .map(|(agent, finder)| {
+            let (status, path) = find_path(path_tilemap, ty, &mut planned, *agent, finder);
+            (*agent, status, path)
+        })
+        .collect::<Vec<_>>();
+
+    let agents = requests.iter().map(|(agent, _)| *agent).collect::<Vec<_>>();
+    reservations.lock().unwrap().commit(&planned, &agents);
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tilemap::algorithm::path::PathTile;
+
+    #[test]
+    fn test_cooperative() {
+        // A hallway with a passing place in the middle.
+        let mut path_tilemap = PathTilemap::new();
+        for x in 0..9 {
+            path_tilemap.set(IVec2 { x, y: 0 }, PathTile { cost: 1 });
+        }
+        path_tilemap.set(IVec2::new(4, 1), PathTile { cost: 1 });
+
+        let finder = |origin: IVec2, dest: IVec2| PathFinder {
+            origin,
+            dest,
+            allow_diagonal: false,
+            max_steps: None,
+            algorithm: PathAlgorithm::Cooperative { window: 16 },
+        };
+        // The second agent has to step aside and wait for the first one.
+        let agents = [
+            (Entity::from_raw(0), IVec2::new(0, 0), IVec2::new(8, 0)),
+            (Entity::from_raw(1), IVec2::new(5, 0), IVec2::new(0, 0)),
+        ];
+
+        let mut reservations = ReservationTable::default();
+        let paths = agents.map(|(agent, origin, dest)| {
+            let (status, mut path) = find_path(
+                &path_tilemap,
+                TilemapType::Square,
+                &mut reservations,
+                agent,
+                &finder(origin, dest),
+            );
+            assert_eq!(status, PathStatus::Found);
+            path.reverse();
+            path.insert(0, origin);
+            path
+        });
+
+        for (path, (_, _, dest)) in paths.iter().zip(agents) {
+            assert_eq!(*path.last().unwrap(), dest);
+            assert!(path.len() > 1);
+            for step in path.windows(2) {
+                assert!(
+                    step[0] == step[1]
+                        || step[0]
+                            .neighbours(TilemapType::Square, false)
+                            .contains(&Some(step[1]))
+                );
+            }
+        }
+
+        // No two agents are on the same tile or swap their tiles at the same step.
+        let at = |path: &Vec<IVec2>, step: usize| path[step.min(path.len() - 1)];
+        let len = paths.iter().map(|p| p.len()).max().unwrap();
+        for step in 0..len {
+            for a in 0..paths.len() {
+                for b in a + 1..paths.len() {
+                    let (pa, pb) = (&paths[a], &paths[b]);
+                    assert_ne!(at(pa, step), at(pb, step));
+                    assert!(
+                        step == 0
+                            || at(pa, step) != at(pb, step - 1)
+                            || at(pb, step) != at(pa, step - 1)
+                    );
+                }
+            }
+        }
+
+        assert!(paths[1].contains(&IVec2::new(4, 1)));
+
+        // Replanning releases the old reservations.
+        let (agent, origin, dest) = agents[0];
+        find_path(
+            &path_tilemap,
+            TilemapType::Square,
+            &mut reservations,
+            agent,
+            &finder(origin, dest),
+        );
+        assert_eq!(reservations.agents[&agent].len(), 17);
+        reservations.advance();
+        assert_eq!(reservations.current_step(), 1);
+        assert!(reservations.reserved.keys().all(|(_, step)| *step >= 1));
+    }
+
+    #[test]
+    fn test_find_paths() {
+        let mut path_tilemap = PathTilemap::new();
+        for x in 0..9 {
+            path_tilemap.set(IVec2 { x, y: 0 }, PathTile { cost: 1 });
+        }
+        path_tilemap.set(IVec2::new(4, 1), PathTile { cost: 1 });
+
+        let finder = |origin: IVec2, dest: IVec2| PathFinder {
+            origin,
+            dest,
+            allow_diagonal: false,
+            max_steps: None,
+            algorithm: PathAlgorithm::Cooperative { window: 16 },
+        };
+        let agents = [
+            (Entity::from_raw(0), IVec2::new(0, 0), IVec2::new(8, 0)),
+            (Entity::from_raw(1), IVec2::new(5, 0), IVec2::new(0, 0)),
+        ];
+
+        // Planned one by one in the order of the entities.
+        let mut expected = ReservationTable::default();
+        let expected_paths = agents.map(|(agent, origin, dest)| {
+            let (status, path) = find_path(
+                &path_tilemap,
+                TilemapType::Square,
+                &mut expected,
+                agent,
+                &finder(origin, dest),
+            );
+            (agent, status, path)
+        });
+
+        // The requests come in an arbitrary order.
+        let reservations = Mutex::new(ReservationTable::default());
+        let paths = find_paths(
+            &path_tilemap,
+            TilemapType::Square,
+            &reservations,
+            agents
+                .iter()
+                .rev()
+                .map(|(agent, origin, dest)| (*agent, finder(*origin, *dest)))
+                .collect(),
+        );
+
+        assert_eq!(paths, expected_paths);
+        let reservations = reservations.into_inner().unwrap();
+        assert_eq!(reservations.reserved, expected.reserved);
+    }
+}
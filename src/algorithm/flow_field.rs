@@ -0,0 +1,302 @@
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc};
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Query},
+    },
+    math::IVec2,
+    reflect::Reflect,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::EntityHashMap,
+};
+
+use crate::{
+    math::extension::TileIndex,
+    tilemap::{algorithm::path::PathTilemap, chunking::storage::ChunkedStorage, map::TilemapType},
+};
+
+/// Request a flow field towards the goals.
+/// The `FlowField` will be inserted to the requester when it's generated.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct FlowFieldRequest {
+    pub goals: Vec<IVec2>,
+    pub allow_diagonal: bool,
+    /// Stop spreading once the cost exceeds this value.
+    /// The tiles further away are treated as unreachable.
+    pub max_cost: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Reflect)]
+pub struct FlowTile {
+    /// The total cost to reach the closest goal from this tile.
+    pub cost: u32,
+    /// The neighbour to step to. Same as the tile itself for goals.
+    pub next: IVec2,
+}
+
+/// The integration field and the directions towards the closest goal for every reachable tile.
+///
+/// All the agents heading to the same goals can share one flow field,
+/// instead of finding their own paths.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct FlowField {
+    pub(crate) storage: ChunkedStorage<FlowTile>,
+    goals: Vec<IVec2>,
+    tilemap: Entity,
+}
+
+impl FlowField {
+    /// Generate the flow field immediately.
+    pub fn generate(
+        path_tilemap: &PathTilemap,
+        ty: TilemapType,
+        request: &FlowFieldRequest,
+        tilemap: Entity,
+    ) -> Self {
+        Self {
+            storage: integrate(path_tilemap, ty, request),
+            goals: request.goals.clone(),
+            tilemap,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<&FlowTile> {
+        self.storage.get_elem(index)
+    }
+
+    /// The total cost to reach the closest goal. `None` if the tile can't reach any goal.
+    #[inline]
+    pub fn get_cost(&self, index: IVec2) -> Option<u32> {
+        self.get(index).map(|t| t.cost)
+    }
+
+    /// The next tile towards the closest goal. `None` if the tile can't reach any goal.
+    #[inline]
+    pub fn get_next(&self, index: IVec2) -> Option<IVec2> {
+        self.get(index).map(|t| t.next)
+    }
+
+    /// The offset to the next tile. Zero for the goals.
+    #[inline]
+    pub fn get_direction(&self, index: IVec2) -> Option<IVec2> {
+        self.get_next(index).map(|next| next - index)
+    }
+
+    #[inline]
+    pub fn goals(&self) -> &[IVec2] {
+        &self.goals
+    }
+
+    #[inline]
+    pub fn tilemap(&self) -> Entity {
+        self.tilemap
+    }
+}
+
+/// Dijkstra from the goals. The costs are the costs to reach the closest goal.
+pub(crate) fn integrate(
+    path_tilemap: &PathTilemap,
+    ty: TilemapType,
+    request: &FlowFieldRequest,
+) -> ChunkedStorage<FlowTile> {
+    let mut storage = ChunkedStorage::new(path_tilemap.chunk_size());
+    let mut to_explore = BinaryHeap::new();
+
+    for goal in request.goals.iter().copied() {
+        if path_tilemap.get(goal).is_some() {
+            storage.set_elem(
+                goal,
+                FlowTile {
+                    cost: 0,
+                    next: goal,
+                },
+            );
+            to_explore.push(Reverse((0, goal.to_array())));
+        }
+    }
+
+    while let Some(Reverse((cost, index))) = to_explore.pop() {
+        let index = IVec2::from_array(index);
+        if storage.get_elem(index).is_some_and(|t| cost > t.cost) {
+            continue;
+        }
+        // Stepping from the neighbour to this tile costs the cost of this tile.
+        let Some(step) = path_tilemap.get(index).map(|t| t.cost) else {
+            continue;
+        };
+        let cost = cost + step;
+        if request.max_cost.is_some_and(|max| cost > max) {
+            continue;
+        }
+
+        for neighbour in index
+            .neighbours(ty, request.allow_diagonal)
+            .into_iter()
+            .flatten()
+        {
+            if path_tilemap.get(neighbour).is_none()
+                || storage.get_elem(neighbour).is_some_and(|t| t.cost <= cost)
+            {
+                continue;
+            }
+            storage.set_elem(neighbour, FlowTile { cost, next: index });
+            to_explore.push(Reverse((cost, neighbour.to_array())));
+        }
+    }
+
+    storage
+}
+
+#[derive(Component)]
+pub struct FlowFieldQueue {
+    pub(crate) requests: EntityHashMap<Entity, FlowFieldRequest>,
+    pub(crate) tasks: EntityHashMap<Entity, Task<FlowField>>,
+    pub(crate) cache: Arc<PathTilemap>,
+}
+
+impl FlowFieldQueue {
+    pub fn new(cache: PathTilemap) -> Self {
+        FlowFieldQueue {
+            requests: EntityHashMap::default(),
+            tasks: EntityHashMap::default(),
+            cache: Arc::new(cache),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    #[inline]
+    pub fn schedule(&mut self, requester: Entity, request: FlowFieldRequest) {
+        self.requests.insert(requester, request);
+    }
+
+    #[inline]
+    pub fn get_cache(&self) -> Arc<PathTilemap> {
+        self.cache.clone()
+    }
+
+    /// Modify the cache. The tasks that are already running are not affected.
+    #[inline]
+    pub fn get_cache_mut(&mut self) -> &mut PathTilemap {
+        Arc::make_mut(&mut self.cache)
+    }
+}
+
+pub fn flow_field_scheduler(mut queues_query: Query<(Entity, &TilemapType, &mut FlowFieldQueue)>) {
+    let thread_pool = AsyncComputeTaskPool::get();
+    queues_query.for_each_mut(|(tilemap, ty, mut queue)| {
+        let mut tasks = Vec::new();
+        let path_tilemap = queue.cache.clone();
+        queue.requests.drain().for_each(|(requester, request)| {
+            let ty = *ty;
+            let path_tilemap = path_tilemap.clone();
+            let task = thread_pool
+                .spawn(async move { FlowField::generate(&path_tilemap, ty, &request, tilemap) });
+            tasks.push((requester, task));
+        });
+        queue.tasks.extend(tasks);
+    });
+}
+
+pub fn flow_field_assigner(mut commands: Commands, mut queues_query: Query<&mut FlowFieldQueue>) {
+    queues_query.for_each_mut(|mut queue| {
+        let mut completed = Vec::new();
+        queue.tasks.iter_mut().for_each(|(requester, task)| {
+            if let Some(flow_field) = bevy::tasks::block_on(futures_lite::future::poll_once(task)) {
+                commands.entity(*requester).insert(flow_field);
+                completed.push(*requester);
+            }
+        });
+        completed.iter().for_each(|requester| {
+            queue.tasks.remove(requester);
+        });
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tilemap::algorithm::path::PathTile;
+
+    #[test]
+    fn test_flow_field() {
+        let mut path_tilemap = PathTilemap::new_with_chunk_size(4);
+        for y in 0..12 {
+            for x in 0..12 {
+                // A wall with a gap, and an island that can't be reached.
+                if (x == 5 && y != 9) || (x >= 9 && y <= 2) {
+                    continue;
+                }
+                path_tilemap.set(
+                    IVec2 { x, y },
+                    PathTile {
+                        cost: 1 + x as u32 % 3,
+                    },
+                );
+            }
+        }
+        path_tilemap.set(IVec2::new(11, 0), PathTile { cost: 1 });
+        for x in 8..12 {
+            path_tilemap.remove(IVec2::new(x, 1));
+        }
+
+        let goals = vec![IVec2::new(0, 0), IVec2::new(10, 11)];
+        let request = FlowFieldRequest {
+            goals: goals.clone(),
+            allow_diagonal: false,
+            max_cost: None,
+        };
+        let flow_field = FlowField::generate(
+            &path_tilemap,
+            TilemapType::Square,
+            &request,
+            Entity::PLACEHOLDER,
+        );
+
+        for goal in goals {
+            assert_eq!(flow_field.get_cost(goal), Some(0));
+            assert_eq!(flow_field.get_direction(goal), Some(IVec2::ZERO));
+        }
+        assert!(flow_field.get(IVec2::new(11, 0)).is_none());
+        assert!(flow_field.get(IVec2::new(5, 0)).is_none());
+
+        // Every tile steps to a neighbour, and the cost decreases by the cost of the neighbour.
+        for y in 0..12 {
+            for x in 0..12 {
+                let index = IVec2 { x, y };
+                let Some(tile) = flow_field.get(index) else {
+                    continue;
+                };
+                if tile.cost == 0 {
+                    continue;
+                }
+                assert!(index
+                    .neighbours(TilemapType::Square, false)
+                    .contains(&Some(tile.next)));
+                assert_eq!(
+                    tile.cost,
+                    flow_field.get_cost(tile.next).unwrap()
+                        + path_tilemap.get(tile.next).unwrap().cost
+                );
+            }
+        }
+
+        let limited = FlowField::generate(
+            &path_tilemap,
+            TilemapType::Square,
+            &FlowFieldRequest {
+                max_cost: Some(5),
+                ..request
+            },
+            Entity::PLACEHOLDER,
+        );
+        assert!(limited.storage.iter_some().all(|tile| tile.cost <= 5));
+        assert!(limited.get(IVec2::new(4, 4)).is_none());
+    }
+}
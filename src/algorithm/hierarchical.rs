@@ -0,0 +1,523 @@
+//! Hierarchical pathfinding (HPA*).
+//!
+//! The tiles are grouped by the chunks of the `PathTilemap`. Where two chunks are connected,
+//! the tiles on the border become entrances, and the costs between the entrances in the same
+//! chunk are precomputed. Paths are searched between the entrances first, and then refined
+//! inside each chunk, so the path may be slightly longer than the optimal one.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    math::IVec2,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    math::extension::{ManhattanDistance, TileIndex},
+    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+};
+
+use super::pathfinding::PathStatus;
+
+/// The directions of the chunks that share a border or a corner with a chunk.
+/// Each border is stored in the chunk on the left, or below if they are in the same column.
+///
+/// Only the tiles at the corners connect to the diagonal chunks,
+/// through the diagonal neighbours of square tilemaps or the neighbours of hexagonal ones.
+const BORDERS: [IVec2; 4] = [IVec2::X, IVec2::Y, IVec2::ONE, IVec2::new(1, -1)];
+
+/// Entrances at least this wide have an entrance at both ends instead of one in the middle.
+const WIDE_ENTRANCE: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct PathHierarchy {
+    ty: TilemapType,
+    allow_diagonal: bool,
+    chunk_size: u32,
+    /// The versions of the chunks when they were built.
+    versions: HashMap<IVec2, u32>,
+    /// The connections between each chunk and the chunks in `BORDERS` from it.
+    /// Each connection is a pair of neighbouring tiles, the first one is in the chunk.
+    borders: HashMap<(IVec2, usize), Vec<(IVec2, IVec2)>>,
+    /// The costs from each entrance to the other entrances in the same chunk.
+    edges: HashMap<IVec2, HashMap<IVec2, Vec<(IVec2, u32)>>>,
+}
+
+impl PathHierarchy {
+    pub fn new(path_tilemap: &PathTilemap, ty: TilemapType, allow_diagonal: bool) -> Self {
+        let mut hierarchy = Self {
+            ty,
+            allow_diagonal,
+            chunk_size: path_tilemap.chunk_size(),
+            versions: HashMap::default(),
+            borders: HashMap::default(),
+            edges: HashMap::default(),
+        };
+        hierarchy.update(path_tilemap);
+        hierarchy
+    }
+
+    #[inline]
+    pub fn ty(&self) -> TilemapType {
+        self.ty
+    }
+
+    #[inline]
+    pub fn allow_diagonal(&self) -> bool {
+        self.allow_diagonal
+    }
+
+    /// Rebuild the chunks that are modified since the last update, and their borders.
+    pub fn update(&mut self, path_tilemap: &PathTilemap) {
+        if path_tilemap.chunk_size() != self.chunk_size {
+            *self = Self::new(path_tilemap, self.ty, self.allow_diagonal);
+            return;
+        }
+
+        let chunks = &path_tilemap.storage.chunks;
+        let dirty = chunks
+            .keys()
+            .filter(|c| self.versions.get(*c) != Some(&path_tilemap.get_chunk_version(**c)))
+            .chain(self.versions.keys().filter(|c| !chunks.contains_key(*c)))
+            .copied()
+            .collect::<HashSet<_>>();
+
+        let mut affected = HashSet::new();
+        for chunk in dirty {
+            if chunks.contains_key(&chunk) {
+                self.versions
+                    .insert(chunk, path_tilemap.get_chunk_version(chunk));
+            } else {
+                self.versions.remove(&chunk);
+            }
+
+            for (side, dir) in BORDERS.into_iter().enumerate() {
+                self.build_border(path_tilemap, chunk, side);
+                self.build_border(path_tilemap, chunk - dir, side);
+                affected.extend([chunk + dir, chunk - dir]);
+            }
+            affected.insert(chunk);
+        }
+
+        for chunk in affected {
+            self.build_edges(path_tilemap, chunk);
+        }
+    }
+
+    #[inline]
+    fn chunk_of(&self, index: IVec2) -> IVec2 {
+        index.div_euclid(IVec2::splat(self.chunk_size as i32))
+    }
+
+    fn build_border(&mut self, path_tilemap: &PathTilemap, chunk: IVec2, side: usize) {
+        self.borders.remove(&(chunk, side));
+        let other = chunk + BORDERS[side];
+        let chunks = &path_tilemap.storage.chunks;
+        if !chunks.contains_key(&chunk) || !chunks.contains_key(&other) {
+            return;
+        }
+
+        // The tiles along the border, and their first neighbour in the other chunk.
+        let size = self.chunk_size as i32;
+        let dir = BORDERS[side];
+        let along = |d: i32| match d {
+            1 => size - 1..=size - 1,
+            -1 => 0..=0,
+            _ => 0..=size - 1,
+        };
+        let connections = along(dir.y)
+            .flat_map(|y| along(dir.x).map(move |x| IVec2::new(x, y)))
+            .map(|in_chunk| {
+                let tile = chunk * size + in_chunk;
+                path_tilemap.get(tile)?;
+                tile.neighbours(self.ty, self.allow_diagonal)
+                    .into_iter()
+                    .flatten()
+                    .find(|n| self.chunk_of(*n) == other && path_tilemap.get(*n).is_some())
+                    .map(|n| (tile, n))
+            })
+            .collect::<Vec<_>>();
+
+        let mut entrances = Vec::new();
+        let mut start = None;
+        for i in 0..=connections.len() {
+            let connected = connections.get(i).is_some_and(|c| c.is_some());
+            match (connected, start) {
+                (true, None) => start = Some(i),
+                (false, Some(s)) => {
+                    let end = i - 1;
+                    if end - s + 1 >= WIDE_ENTRANCE {
+                        entrances.extend([connections[s].unwrap(), connections[end].unwrap()]);
+                    } else {
+                        entrances.push(connections[(s + end) / 2].unwrap());
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if !entrances.is_empty() {
+            self.borders.insert((chunk, side), entrances);
+        }
+    }
+
+    /// The tiles in the chunk that are connected to other chunks.
+    fn entrances(&self, chunk: IVec2) -> HashSet<IVec2> {
+        BORDERS
+            .into_iter()
+            .enumerate()
+            .flat_map(|(side, dir)| {
+                let outgoing = self.borders.get(&(chunk, side)).into_iter().flatten();
+                let incoming = self.borders.get(&(chunk - dir, side)).into_iter().flatten();
+                outgoing.map(|(a, _)| *a).chain(incoming.map(|(_, b)| *b))
+            })
+            .collect()
+    }
+
+    /// The tiles in other chunks that are connected to the entrance.
+    fn connections(&self, entrance: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        let chunk = self.chunk_of(entrance);
+        BORDERS
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(side, dir)| {
+                let outgoing = self.borders.get(&(chunk, side)).into_iter().flatten();
+                let incoming = self.borders.get(&(chunk - dir, side)).into_iter().flatten();
+                outgoing
+                    .filter(move |(a, _)| *a == entrance)
+                    .map(|(_, b)| *b)
+                    .chain(
+                        incoming
+                            .filter(move |(_, b)| *b == entrance)
+                            .map(|(a, _)| *a),
+                    )
+            })
+    }
+
+    fn build_edges(&mut self, path_tilemap: &PathTilemap, chunk: IVec2) {
+        self.edges.remove(&chunk);
+        if !path_tilemap.storage.chunks.contains_key(&chunk) {
+            return;
+        }
+
+        let entrances = self.entrances(chunk);
+        let edges = entrances
+            .iter()
+            .map(|from| {
+                let reached = self.search_in_chunk(path_tilemap, *from, false, None);
+                let costs = entrances
+                    .iter()
+                    .filter(|to| *to != from)
+                    .filter_map(|to| reached.get(to).map(|(cost, _)| (*to, *cost)))
+                    .collect();
+                (*from, costs)
+            })
+            .collect();
+        self.edges.insert(chunk, edges);
+    }
+
+    /// Dijkstra without leaving the chunk of `start`.
+    /// Returns the costs and the previous tiles of the reached tiles.
+    ///
+    /// If `reverse`, the costs are from the tiles to `start`,
+    /// and the previous tiles are the next steps towards `start`.
+    fn search_in_chunk(
+        &self,
+        path_tilemap: &PathTilemap,
+        start: IVec2,
+        reverse: bool,
+        target: Option<IVec2>,
+    ) -> HashMap<IVec2, (u32, IVec2)> {
+        let chunk = self.chunk_of(start);
+        let mut reached = HashMap::default();
+        let mut to_explore = BinaryHeap::new();
+        reached.insert(start, (0, start));
+        to_explore.push(Reverse((0, start.to_array())));
+
+        while let Some(Reverse((cost, index))) = to_explore.pop() {
+            let index = IVec2::from_array(index);
+            if Some(index) == target {
+                break;
+            }
+            if cost > reached[&index].0 {
+                continue;
+            }
+
+            for neighbour in index
+                .neighbours(self.ty, self.allow_diagonal)
+                .into_iter()
+                .flatten()
+            {
+                if self.chunk_of(neighbour) != chunk {
+                    continue;
+                }
+                let Some(tile) = path_tilemap.get(neighbour) else {
+                    continue;
+                };
+                // Entering a tile costs the cost of that tile.
+                let step = if reverse {
+                    path_tilemap.get(index).map(|t| t.cost).unwrap_or_default()
+                } else {
+                    tile.cost
+                };
+                let cost = cost + step;
+                if reached.get(&neighbour).is_some_and(|(old, _)| *old <= cost) {
+                    continue;
+                }
+                reached.insert(neighbour, (cost, index));
+                to_explore.push(Reverse((cost, neighbour.to_array())));
+            }
+        }
+
+        reached
+    }
+
+    /// Find the path from `origin` to `dest`.
+    ///
+    /// Returns the tiles from `dest` back to the first step, like `PathGrid::collect_path`.
+    pub fn find_path(
+        &self,
+        path_tilemap: &PathTilemap,
+        origin: IVec2,
+        dest: IVec2,
+        max_steps: Option<u32>,
+    ) -> (PathStatus, Vec<IVec2>) {
+        if path_tilemap.get(dest).is_none() {
+            return (PathStatus::Unreachable, Vec::new());
+        }
+        let origin_chunk = self.chunk_of(origin);
+        let dest_chunk = self.chunk_of(dest);
+
+        let from_origin = self.search_in_chunk(path_tilemap, origin, false, None);
+        if origin_chunk == dest_chunk && from_origin.contains_key(&dest) {
+            let mut path = trace(&from_origin, origin, dest);
+            path.reverse();
+            return (PathStatus::Found, path);
+        }
+        let to_dest = self.search_in_chunk(path_tilemap, dest, true, None);
+
+        // Dijkstra on the entrances. The parent is `None` if it's reached from the origin.
+        let mut nodes = HashMap::<IVec2, (u32, Option<IVec2>)>::default();
+        let mut to_explore = BinaryHeap::new();
+        for entrance in self.entrances(origin_chunk) {
+            if let Some((cost, _)) = from_origin.get(&entrance) {
+                nodes.insert(entrance, (*cost, None));
+                to_explore.push(Reverse((*cost, entrance.to_array())));
+            }
+        }
+
+        let mut best: Option<(u32, IVec2)> = None;
+        let mut steps = 0;
+        let mut limited = false;
+        while let Some(Reverse((cost, entrance))) = to_explore.pop() {
+            let entrance = IVec2::from_array(entrance);
+            if best.is_some_and(|(best, _)| cost >= best) {
+                break;
+            }
+            if cost > nodes[&entrance].0 {
+                continue;
+            }
+            if let Some(max_steps) = max_steps {
+                if steps > max_steps {
+                    limited = true;
+                    break;
+                }
+            }
+            steps += 1;
+
+            let chunk = self.chunk_of(entrance);
+            if chunk == dest_chunk {
+                if let Some((rest, _)) = to_dest.get(&entrance) {
+                    if best.is_none_or(|(best, _)| cost + rest < best) {
+                        best = Some((cost + rest, entrance));
+                    }
+                }
+            }
+
+            let inside = self
+                .edges
+                .get(&chunk)
+                .and_then(|edges| edges.get(&entrance))
+                .into_iter()
+                .flatten()
+                .copied();
+            let across = self
+                .connections(entrance)
+                .filter_map(|next| path_tilemap.get(next).map(|tile| (next, tile.cost)));
+            for (next, step) in inside.chain(across) {
+                let cost = cost + step;
+                if nodes.get(&next).is_some_and(|(old, _)| *old <= cost) {
+                    continue;
+                }
+                nodes.insert(next, (cost, Some(entrance)));
+                to_explore.push(Reverse((cost, next.to_array())));
+            }
+        }
+
+        let (status, last) = match best {
+            Some((_, last)) => (PathStatus::Found, last),
+            // Head to the closest entrance instead.
+            None if limited => {
+                let closest = nodes
+                    .iter()
+                    .min_by_key(|(entrance, (cost, _))| (entrance.manhattan_distance(dest), *cost))
+                    .map(|(entrance, _)| *entrance);
+                match closest {
+                    Some(closest) => (PathStatus::StepLimitReached, closest),
+                    None => return (PathStatus::StepLimitReached, Vec::new()),
+                }
+            }
+            None => return (PathStatus::Unreachable, Vec::new()),
+        };
+        let mut entrances = vec![last];
+        while let Some(parent) = nodes[entrances.last().unwrap()].1 {
+            entrances.push(parent);
+        }
+        entrances.reverse();
+
+        // Refine the path in each chunk.
+        let mut path = trace(&from_origin, origin, entrances[0]);
+        for pair in entrances.windows(2) {
+            if self.chunk_of(pair[0]) == self.chunk_of(pair[1]) {
+                let reached = self.search_in_chunk(path_tilemap, pair[0], false, Some(pair[1]));
+                path.extend(trace(&reached, pair[0], pair[1]));
+            } else {
+                path.push(pair[1]);
+            }
+        }
+        if status == PathStatus::Found {
+            let mut current = last;
+            while current != dest {
+                current = to_dest[&current].1;
+                path.push(current);
+            }
+        }
+
+        path.reverse();
+        (status, path)
+    }
+}
+
+/// The tiles from `from` to `to`, excluding `from`.
+fn trace(reached: &HashMap<IVec2, (u32, IVec2)>, from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let mut path = Vec::new();
+    let mut current = to;
+    while current != from {
+        path.push(current);
+        current = reached[&current].1;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bevy::ecs::entity::Entity;
+
+    use super::*;
+    use crate::{
+        algorithm::pathfinding::{PathFinder, PathGrid},
+        tilemap::algorithm::path::PathTile,
+    };
+
+    #[test]
+    fn test_hierarchical() {
+        let mut path_tilemap = PathTilemap::new_with_chunk_size(8);
+        for y in 0..40 {
+            for x in 0..40 {
+                // Walls with gaps at different places.
+                if x % 6 == 3 && (x + y) % 9 != 0 {
+                    continue;
+                }
+                path_tilemap.set(
+                    IVec2 { x, y },
+                    PathTile {
+                        cost: 1 + (x * y) as u32 % 3,
+                    },
+                );
+            }
+        }
+        let origin = IVec2::new(0, 2);
+        let dest = IVec2::new(38, 35);
+
+        for ty in [TilemapType::Square, TilemapType::Hexagonal(0)] {
+            let mut hierarchy = PathHierarchy::new(&path_tilemap, ty, false);
+            let assert_path = |path_tilemap: &PathTilemap, hierarchy: &PathHierarchy| {
+                let (status, path) = hierarchy.find_path(path_tilemap, origin, dest, None);
+                assert_eq!(status, PathStatus::Found);
+                let mut prev = origin;
+                for index in path.iter().rev() {
+                    assert!(path_tilemap.get(*index).is_some());
+                    assert!(prev.neighbours(ty, false).contains(&Some(*index)));
+                    prev = *index;
+                }
+                assert_eq!(prev, dest);
+                path
+            };
+
+            let path = assert_path(&path_tilemap, &hierarchy);
+            let (status, partial) = hierarchy.find_path(&path_tilemap, origin, dest, Some(3));
+            assert_eq!(status, PathStatus::StepLimitReached);
+            assert!(!partial.contains(&dest));
+            let mut grid = PathGrid::new(
+                PathFinder {
+                    origin,
+                    dest,
+                    allow_diagonal: false,
+                    max_steps: None,
+                    algorithm: Default::default(),
+                },
+                Entity::PLACEHOLDER,
+                Entity::PLACEHOLDER,
+                Arc::new(path_tilemap.clone()),
+            );
+            grid.find_path(ty);
+            // Never cheaper than the optimal path.
+            let cost = path
+                .iter()
+                .map(|index| path_tilemap.get(*index).unwrap().cost)
+                .sum::<u32>();
+            assert!(cost >= grid.all_nodes[&dest].g_cost);
+
+            // Block the path and only rebuild the changed chunk.
+            let mut blocked = path_tilemap.clone();
+            blocked.remove(path[path.len() / 2]);
+            hierarchy.update(&blocked);
+            let new_path = assert_path(&blocked, &hierarchy);
+            assert!(!new_path.contains(&path[path.len() / 2]));
+            assert_eq!(hierarchy.versions.len(), 25);
+        }
+
+        // Chunks that only touch at the corners.
+        for (ty, allow_diagonal, dir) in [
+            (TilemapType::Square, true, IVec2::ONE),
+            (TilemapType::Square, true, IVec2::new(1, -1)),
+            (TilemapType::Hexagonal(0), false, IVec2::ONE),
+        ] {
+            let mut path_tilemap = PathTilemap::new_with_chunk_size(4);
+            for chunk in [IVec2::ZERO, dir] {
+                for y in 0..4 {
+                    for x in 0..4 {
+                        path_tilemap.set(chunk * 4 + IVec2 { x, y }, PathTile { cost: 1 });
+                    }
+                }
+            }
+            // The opposite corners of the two chunks.
+            let origin = IVec2::new(0, if dir.y > 0 { 0 } else { 3 });
+            let dest = dir * 4 + IVec2::new(3, if dir.y > 0 { 3 } else { 0 });
+
+            let hierarchy = PathHierarchy::new(&path_tilemap, ty, allow_diagonal);
+            let (status, path) = hierarchy.find_path(&path_tilemap, origin, dest, None);
+            assert_eq!(status, PathStatus::Found);
+            let mut prev = origin;
+            for index in path.iter().rev() {
+                assert!(prev.neighbours(ty, allow_diagonal).contains(&Some(*index)));
+                prev = *index;
+            }
+            assert_eq!(prev, dest);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Jump Point Search for square tilemaps where all the tiles have the same cost.
+//!
+//! Instead of exploring every neighbour, the search jumps along straight and diagonal lines
+//! and only stops at the tiles where the obstacles force the path to turn.
+//! Tiles that are not in the `PathTilemap` are obstacles.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{math::IVec2, utils::HashMap};
+
+use crate::tilemap::algorithm::path::PathTilemap;
+
+use super::pathfinding::PathStatus;
+
+/// The cost of all the tiles. `None` if the costs are different or there are no tiles.
+pub fn uniform_cost(path_tilemap: &PathTilemap) -> Option<u32> {
+    let mut tiles = path_tilemap
+        .storage
+        .chunks
+        .values()
+        .flat_map(|chunk| chunk.iter().flatten());
+    let cost = tiles.next()?.cost;
+    tiles.all(|tile| tile.cost == cost).then_some(cost)
+}
+
+struct JumpPointGrid<'a> {
+    path_tilemap: &'a PathTilemap,
+    dest: IVec2,
+    allow_diagonal: bool,
+}
+
+impl<'a> JumpPointGrid<'a> {
+    #[inline]
+    fn is_walkable(&self, index: IVec2) -> bool {
+        self.path_tilemap.get(index).is_some()
+    }
+
+    /// The directions worth exploring from `index`, when it's reached from `parent`.
+    fn successors(&self, index: IVec2, parent: Option<IVec2>) -> Vec<IVec2> {
+        let Some(parent) = parent else {
+            let mut dirs = vec![IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+            if self.allow_diagonal {
+                dirs.extend([
+                    IVec2::ONE,
+                    IVec2::NEG_ONE,
+                    IVec2::new(1, -1),
+                    IVec2::new(-1, 1),
+                ]);
+            }
+            return dirs;
+        };
+
+        let d = (index - parent).signum();
+        let blocked = |offset: IVec2| !self.is_walkable(index + offset);
+        let mut dirs = vec![d];
+
+        if self.allow_diagonal {
+            if d.x != 0 && d.y != 0 {
+                dirs.extend([IVec2::new(d.x, 0), IVec2::new(0, d.y)]);
+                if blocked(IVec2::new(-d.x, 0)) {
+                    dirs.push(IVec2::new(-d.x, d.y));
+                }
+                if blocked(IVec2::new(0, -d.y)) {
+                    dirs.push(IVec2::new(d.x, -d.y));
+                }
+            } else {
+                let side = IVec2::new(d.y, d.x);
+                for side in [side, -side] {
+                    if blocked(side) {
+                        dirs.push(d + side);
+                    }
+                }
+            }
+        } else if d.x != 0 {
+            // Horizontal moves come first, so turning is always allowed.
+            dirs.extend([IVec2::Y, IVec2::NEG_Y]);
+        } else {
+            // Only turn if the horizontal move is blocked before reaching here.
+            for side in [IVec2::X, IVec2::NEG_X] {
+                if self.is_walkable(index + side) && blocked(side - d) {
+                    dirs.push(side);
+                }
+            }
+        }
+
+        dirs
+    }
+
+    /// Move from `index` towards `dir` until reaching a jump point.
+    fn jump(&self, mut index: IVec2, dir: IVec2) -> Option<IVec2> {
+        loop {
+            index += dir;
+            if !self.is_walkable(index) {
+                return None;
+            }
+            if index == self.dest || self.is_jump_point(index, dir) {
+                return Some(index);
+            }
+        }
+    }
+
+    fn is_jump_point(&self, index: IVec2, dir: IVec2) -> bool {
+        let walkable = |offset: IVec2| self.is_walkable(index + offset);
+
+        if self.allow_diagonal {
+            if dir.x != 0 && dir.y != 0 {
+                (walkable(IVec2::new(-dir.x, dir.y)) && !walkable(IVec2::new(-dir.x, 0)))
+                    || (walkable(IVec2::new(dir.x, -dir.y)) && !walkable(IVec2::new(0, -dir.y)))
+                    || self.jump(index, IVec2::new(dir.x, 0)).is_some()
+                    || self.jump(index, IVec2::new(0, dir.y)).is_some()
+            } else {
+                let side = IVec2::new(dir.y, dir.x);
+                [side, -side]
+                    .into_iter()
+                    .any(|side| walkable(dir + side) && !walkable(side))
+            }
+        } else if dir.x != 0 {
+            self.jump(index, IVec2::Y).is_some() || self.jump(index, IVec2::NEG_Y).is_some()
+        } else {
+            [IVec2::X, IVec2::NEG_X]
+                .into_iter()
+                .any(|side| walkable(side) && !walkable(side - dir))
+        }
+    }
+
+    #[inline]
+    fn distance(&self, a: IVec2, b: IVec2) -> u32 {
+        let d = (a - b).abs();
+        if self.allow_diagonal {
+            d.max_element() as u32
+        } else {
+            (d.x + d.y) as u32
+        }
+    }
+}
+
+/// Find the path from `origin` to `dest`. `cost` is the cost of every tile.
+///
+/// Returns the tiles from `dest` back to the first step, like `PathGrid::collect_path`.
+pub fn find_path(
+    path_tilemap: &PathTilemap,
+    origin: IVec2,
+    dest: IVec2,
+    allow_diagonal: bool,
+    cost: u32,
+    max_steps: Option<u32>,
+) -> (PathStatus, Vec<IVec2>) {
+    let grid = JumpPointGrid {
+        path_tilemap,
+        dest,
+        allow_diagonal,
+    };
+    if !grid.is_walkable(dest) {
+        return (PathStatus::Unreachable, Vec::new());
+    }
+
+    // The parent and the cost of the jump points.
+    let mut nodes = HashMap::<IVec2, (Option<IVec2>, u32)>::default();
+    let mut to_explore = BinaryHeap::new();
+    nodes.insert(origin, (None, 0));
+    to_explore.push(Reverse((
+        grid.distance(origin, dest) * cost,
+        0,
+        origin.to_array(),
+    )));
+    let mut steps = 0;
+
+    while let Some(Reverse((_, g_cost, index))) = to_explore.pop() {
+        let index = IVec2::from_array(index);
+        if index == dest {
+            return (PathStatus::Found, collect_path(&nodes, dest));
+        }
+        let (parent, best) = nodes[&index];
+        if g_cost > best {
+            continue;
+        }
+        if let Some(max_steps) = max_steps {
+            if steps > max_steps {
+                // Head to the closest jump point instead.
+                let closest = nodes
+                    .iter()
+                    .min_by_key(|(index, (_, g_cost))| (grid.distance(**index, dest), *g_cost))
+                    .map(|(index, _)| *index)
+                    .unwrap_or(origin);
+                return (PathStatus::StepLimitReached, collect_path(&nodes, closest));
+            }
+        }
+        steps += 1;
+
+        for dir in grid.successors(index, parent) {
+            let Some(jump_point) = grid.jump(index, dir) else {
+                continue;
+            };
+            let g_cost = g_cost + grid.distance(index, jump_point) * cost;
+            if nodes
+                .get(&jump_point)
+                .is_some_and(|(_, old)| *old <= g_cost)
+            {
+                continue;
+            }
+            nodes.insert(jump_point, (Some(index), g_cost));
+            to_explore.push(Reverse((
+                g_cost + grid.distance(jump_point, dest) * cost,
+                g_cost,
+                jump_point.to_array(),
+            )));
+        }
+    }
+
+    (PathStatus::Unreachable, Vec::new())
+}
+
+/// Fill the straight lines between the jump points.
+fn collect_path(nodes: &HashMap<IVec2, (Option<IVec2>, u32)>, dest: IVec2) -> Vec<IVec2> {
+    let mut path = Vec::new();
+    let mut current = dest;
+    while let Some(parent) = nodes[&current].0 {
+        let dir = (parent - current).signum();
+        while current != parent {
+            path.push(current);
+            current += dir;
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bevy::{ecs::entity::Entity, utils::HashSet};
+
+    use super::*;
+    use crate::{
+        algorithm::pathfinding::{PathFinder, PathGrid},
+        math::extension::TileIndex,
+        tilemap::{algorithm::path::PathTile, map::TilemapType},
+    };
+
+    #[test]
+    fn test_jump_point() {
+        let mut path_tilemap = PathTilemap::new();
+        for y in 0..20 {
+            for x in 0..20 {
+                // Walls with gaps at different places.
+                if x % 5 == 2 && (x + y) % 7 != 0 {
+                    continue;
+                }
+                path_tilemap.set(IVec2 { x, y }, PathTile { cost: 2 });
+            }
+        }
+        let path_tilemap = Arc::new(path_tilemap);
+        let origin = IVec2::new(0, 3);
+        let dest = IVec2::new(19, 17);
+        assert_eq!(uniform_cost(&path_tilemap), Some(2));
+
+        for allow_diagonal in [false, true] {
+            let (status, path) = find_path(&path_tilemap, origin, dest, allow_diagonal, 2, None);
+            assert_eq!(status, PathStatus::Found);
+
+            // Every step moves to a walkable neighbour.
+            let mut prev = origin;
+            for index in path.iter().rev() {
+                assert!(path_tilemap.get(*index).is_some());
+                assert!(prev
+                    .neighbours(TilemapType::Square, allow_diagonal)
+                    .contains(&Some(*index)));
+                prev = *index;
+            }
+            assert_eq!(prev, dest);
+            assert_eq!(path.iter().collect::<HashSet<_>>().len(), path.len());
+
+            // As short as the one found by A*.
+            let mut grid = PathGrid::new(
+                PathFinder {
+                    origin,
+                    dest,
+                    allow_diagonal,
+                    max_steps: None,
+                    algorithm: Default::default(),
+                },
+                Entity::PLACEHOLDER,
+                Entity::PLACEHOLDER,
+                path_tilemap.clone(),
+            );
+            grid.find_path(TilemapType::Square);
+            assert_eq!(path.len(), grid.collect_path().iter().count());
+
+            let (status, partial) =
+                find_path(&path_tilemap, origin, dest, allow_diagonal, 2, Some(2));
+            assert_eq!(status, PathStatus::StepLimitReached);
+            assert!(!partial.contains(&dest));
+        }
+        assert_eq!(
+            find_path(&path_tilemap, origin, IVec2::new(2, 3), false, 2, None).0,
+            PathStatus::Unreachable
+        );
+    }
+}
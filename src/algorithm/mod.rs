@@ -1,10 +1,15 @@
-use bevy::prelude::{Plugin, Update};
+use bevy::prelude::{IntoSystemConfigs, Plugin, Update};
 
 use self::{
-    pathfinding::Path,
+    flow_field::{FlowField, FlowFieldRequest},
+    pathfinding::{Path, PathInvalidated},
     wfc::{WfcData, WfcElement, WfcHistory, WfcSource},
 };
 
+pub mod cooperative;
+pub mod flow_field;
+pub mod hierarchical;
+pub mod jump_point;
 pub mod pathfinding;
 pub mod wfc;
 
@@ -12,7 +17,11 @@ pub struct EntiTilesAlgorithmPlugin;
 
 impl Plugin for EntiTilesAlgorithmPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.register_type::<Path>();
+        app.register_type::<Path>()
+            .register_type::<FlowField>()
+            .register_type::<FlowFieldRequest>();
+
+        app.add_event::<PathInvalidated>();
 
         app.register_type::<WfcElement>()
             .register_type::<WfcHistory>()
@@ -22,8 +31,11 @@ impl Plugin for EntiTilesAlgorithmPlugin {
         app.add_systems(
             Update,
             (
+                pathfinding::path_cache_synchronizer.before(pathfinding::pathfinding_scheduler),
                 pathfinding::pathfinding_scheduler,
                 pathfinding::path_assigner,
+                flow_field::flow_field_scheduler,
+                flow_field::flow_field_assigner,
                 wfc::wave_function_collapse,
                 wfc::wfc_data_assigner,
                 wfc::wfc_applier,
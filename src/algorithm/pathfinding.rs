@@ -1,7 +1,15 @@
-use std::{cmp::Ordering, collections::BinaryHeap, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
 
 use bevy::{
-    ecs::system::{Commands, Query},
+    ecs::{
+        event::{Event, EventWriter},
+        query::Changed,
+        system::{Commands, Query},
+    },
     math::IVec2,
     prelude::{Component, Entity},
     reflect::Reflect,
@@ -11,22 +19,64 @@ use bevy::{
 
 use crate::{
     math::extension::{ManhattanDistance, TileIndex},
-    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+    tilemap::{
+        algorithm::path::{PathTile, PathTilemap},
+        map::TilemapType,
+    },
+};
+
+use super::{
+    cooperative::{self, ReservationTable},
+    hierarchical::PathHierarchy,
+    jump_point,
 };
 
+/// The algorithm used to find the path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum PathAlgorithm {
+    #[default]
+    AStar,
+    /// Jump Point Search. Much faster on large open areas.
+    ///
+    /// Only works on square tilemaps where all the tiles have the same cost,
+    /// otherwise falls back to `AStar`.
+    JumpPoint,
+    /// Hierarchical pathfinding (HPA*). Searches between the chunks first,
+    /// so the path may be slightly longer than the optimal one.
+    ///
+    /// Requires `PathFindingQueue::with_hierarchy` with the same `allow_diagonal`,
+    /// otherwise falls back to `AStar`.
+    Hierarchical,
+    /// Cooperative pathfinding (WHCA*). The agents on the same queue reserve the tiles
+    /// in the first `window` steps of their paths, and avoid the tiles reserved by the others.
+    ///
+    /// See `ReservationTable` for how the agents should move.
+    Cooperative { window: u32 },
+}
+
 #[derive(Component, Reflect)]
 pub struct PathFinder {
     pub origin: IVec2,
     pub dest: IVec2,
     pub allow_diagonal: bool,
     pub max_steps: Option<u32>,
+    pub algorithm: PathAlgorithm,
 }
 
 #[derive(Component)]
 pub struct PathFindingQueue {
     pub(crate) finders: EntityHashMap<Entity, PathFinder>,
     pub(crate) tasks: EntityHashMap<Entity, Task<Path>>,
+    /// The requesters of `PathAlgorithm::Cooperative` in the order they are planned,
+    /// and the task that finds their paths one after another.
+    pub(crate) cooperative_task: Option<(Vec<Entity>, Task<Vec<Path>>)>,
     pub(crate) cache: Arc<PathTilemap>,
+    pub(crate) hierarchy: Option<Arc<PathHierarchy>>,
+    /// `None` if it's not computed since the cache is modified.
+    pub(crate) uniform_cost: Option<Option<u32>>,
+    /// The tiles modified since the running tasks are started.
+    pub(crate) outdated: EntityHashMap<Entity, HashSet<IVec2>>,
+    pub(crate) reservations: Arc<Mutex<ReservationTable>>,
 }
 
 impl PathFindingQueue {
@@ -34,7 +84,12 @@ impl PathFindingQueue {
         PathFindingQueue {
             finders: EntityHashMap::default(),
             tasks: EntityHashMap::default(),
+            cooperative_task: None,
             cache: Arc::new(cache),
+            hierarchy: None,
+            uniform_cost: None,
+            outdated: EntityHashMap::default(),
+            reservations: Default::default(),
         }
     }
 
@@ -45,13 +100,29 @@ impl PathFindingQueue {
         PathFindingQueue {
             finders: schedules.collect(),
             tasks: EntityHashMap::default(),
+            cooperative_task: None,
             cache: Arc::new(cache),
+            hierarchy: None,
+            uniform_cost: None,
+            outdated: EntityHashMap::default(),
+            reservations: Default::default(),
         }
     }
 
+    /// Build the hierarchy for `PathAlgorithm::Hierarchical`.
+    /// It's updated automatically when the cache is modified.
+    pub fn with_hierarchy(mut self, ty: TilemapType, allow_diagonal: bool) -> Self {
+        self.hierarchy = Some(Arc::new(PathHierarchy::new(
+            &self.cache,
+            ty,
+            allow_diagonal,
+        )));
+        self
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.tasks.is_empty()
+        self.tasks.is_empty() && self.cooperative_task.is_none()
     }
 
     #[inline]
@@ -63,6 +134,107 @@ impl PathFindingQueue {
     pub fn get_cache(&self) -> Arc<PathTilemap> {
         self.cache.clone()
     }
+
+    /// Modify the cache. The tasks that are already running are not affected.
+    #[inline]
+    pub fn get_cache_mut(&mut self) -> &mut PathTilemap {
+        self.uniform_cost = None;
+        Arc::make_mut(&mut self.cache)
+    }
+
+    /// The tiles reserved by `PathAlgorithm::Cooperative`.
+    /// Only locked briefly when the cooperative paths start and finish being found.
+    #[inline]
+    pub fn get_reservations(&self) -> Arc<Mutex<ReservationTable>> {
+        self.reservations.clone()
+    }
+
+    /// Copy the modified chunks of `path_tilemap` to the cache.
+    /// Returns the tiles that are different from the cache.
+    ///
+    /// This is done automatically if the `PathTilemap` is on the same entity as the queue.
+    pub fn sync_cache(&mut self, path_tilemap: &PathTilemap) -> HashSet<IVec2> {
+        let mut changed = HashSet::new();
+        let cache = &self.cache;
+
+        if cache.chunk_size() != path_tilemap.chunk_size() {
+            for storage in [&cache.storage, &path_tilemap.storage] {
+                changed.extend(storage.chunks.iter().flat_map(|(chunk_index, chunk)| {
+                    chunk.iter().enumerate().filter_map(|(i, tile)| {
+                        tile.map(|_| storage.inverse_transform_index(*chunk_index, i))
+                    })
+                }));
+            }
+            *self.get_cache_mut() = path_tilemap.clone();
+            return changed;
+        }
+
+        let chunks = path_tilemap
+            .storage
+            .chunks
+            .keys()
+            .chain(cache.storage.chunks.keys())
+            .filter(|c| {
+                path_tilemap.get_chunk_version(**c) != cache.get_chunk_version(**c)
+                    || path_tilemap.storage.chunks.contains_key(*c)
+                        != cache.storage.chunks.contains_key(*c)
+            })
+            .copied()
+            .collect::<HashSet<_>>();
+        if chunks.is_empty() {
+            return changed;
+        }
+
+        let chunk_area = (cache.chunk_size() * cache.chunk_size()) as usize;
+        for chunk_index in chunks.iter() {
+            let new = path_tilemap.storage.chunks.get(chunk_index);
+            let old = cache.storage.chunks.get(chunk_index);
+            let cost = |chunk: Option<&Vec<_>>, i: usize| {
+                chunk.and_then(|c| c[i]).map(|t: PathTile| t.cost)
+            };
+            changed.extend(
+                (0..chunk_area)
+                    .filter(|i| cost(new, *i) != cost(old, *i))
+                    .map(|i| cache.storage.inverse_transform_index(*chunk_index, i)),
+            );
+        }
+
+        let cache = self.get_cache_mut();
+        for chunk_index in chunks {
+            match path_tilemap.storage.chunks.get(&chunk_index) {
+                Some(chunk) => cache.storage.set_chunk(chunk_index, chunk.clone()),
+                None => {
+                    cache.storage.remove_chunk(chunk_index);
+                }
+            }
+            cache
+                .versions
+                .insert(chunk_index, path_tilemap.get_chunk_version(chunk_index));
+        }
+
+        changed
+    }
+}
+
+/// Sent when the tiles on the path are modified after the path is found.
+/// The path may pass through obstacles or miss shortcuts now.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PathInvalidated {
+    /// The entity with the `Path`.
+    pub requester: Entity,
+    pub tilemap: Entity,
+}
+
+/// The result of the pathfinding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum PathStatus {
+    /// The path leads to the destination.
+    Found,
+    /// The destination can't be reached from the origin. The path is empty.
+    Unreachable,
+    /// `max_steps` is reached before finding the destination.
+    /// The path leads to the explored tile that is closest to the destination.
+    StepLimitReached,
 }
 
 #[derive(Component, Clone, Reflect)]
@@ -70,6 +242,7 @@ pub struct Path {
     path: Vec<IVec2>,
     current_step: usize,
     tilemap: Entity,
+    status: PathStatus,
 }
 
 impl Path {
@@ -95,6 +268,12 @@ impl Path {
         self.tilemap
     }
 
+    /// Whether the path leads to the destination.
+    /// The path is always arrived if the destination is unreachable.
+    pub fn status(&self) -> PathStatus {
+        self.status
+    }
+
     pub fn iter(&self) -> std::slice::Iter<IVec2> {
         self.path.iter()
     }
@@ -198,6 +377,10 @@ impl PathGrid {
     }
 
     pub fn find_path(&mut self, ty: TilemapType) {
+        if self.path_tilemap.get(self.dest).is_none() {
+            return;
+        }
+
         let origin = PathNode::new(self.origin, 0, self.dest, 0);
         self.to_explore.push(origin.clone());
         self.all_nodes.insert(self.origin, origin);
@@ -241,15 +424,35 @@ impl PathGrid {
     }
 
     pub fn collect_path(&self) -> Path {
+        let reached = |node: &&PathNode| node.g_cost != u32::MAX;
+        let (status, target) = if self.all_nodes.get(&self.dest).filter(reached).is_some() {
+            (PathStatus::Found, Some(self.dest))
+        } else if self.to_explore.is_empty() {
+            (PathStatus::Unreachable, None)
+        } else {
+            // Stopped by `max_steps`, so head to the closest tile instead.
+            let closest = self
+                .all_nodes
+                .values()
+                .filter(reached)
+                .min_by_key(|node| (node.h_cost, node.g_cost))
+                .map(|node| node.index);
+            (PathStatus::StepLimitReached, closest)
+        };
+
         let mut path = Path {
             path: vec![],
             current_step: 0,
             tilemap: self.tilemap,
+            status,
+        };
+        let Some(target) = target else {
+            return path;
         };
-        let mut current = self.all_nodes.get(&self.dest).unwrap();
-        while current.index != self.origin {
+        let mut current = &self.all_nodes[&target];
+        while let Some(parent) = current.parent {
             path.path.push(current.index);
-            current = self.all_nodes.get(&current.parent.unwrap()).unwrap();
+            current = &self.all_nodes[&parent];
         }
         path
     }
@@ -260,41 +463,177 @@ pub fn pathfinding_scheduler(
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
     queues_query.for_each_mut(|(tilemap, ty, mut queue)| {
+        if queue.finders.is_empty() {
+            return;
+        }
+
+        let queue = &mut *queue;
+        let algorithms = queue
+            .finders
+            .values()
+            .map(|finder| finder.algorithm)
+            .collect::<HashSet<_>>();
+        if algorithms.contains(&PathAlgorithm::JumpPoint) && queue.uniform_cost.is_none() {
+            queue.uniform_cost = Some(jump_point::uniform_cost(&queue.cache));
+        }
+        if algorithms.contains(&PathAlgorithm::Hierarchical) {
+            if let Some(hierarchy) = &mut queue.hierarchy {
+                Arc::make_mut(hierarchy).update(&queue.cache);
+            }
+        }
+
         let mut tasks = Vec::new();
         let path_tilemap = queue.cache.clone();
-        queue.finders.drain().for_each(|(requester, finder)| {
+        let uniform_cost = queue.uniform_cost.flatten();
+        let hierarchy = queue.hierarchy.clone();
+        let mut cooperative = Vec::new();
+        for (requester, finder) in std::mem::take(&mut queue.finders) {
+            if matches!(finder.algorithm, PathAlgorithm::Cooperative { .. }) {
+                // Wait for the running ones, so the agents always see the others' reservations.
+                if queue.cooperative_task.is_some() {
+                    queue.finders.insert(requester, finder);
+                } else {
+                    cooperative.push((requester, finder));
+                }
+                continue;
+            }
+
             let ty = *ty;
             let path_tilemap = path_tilemap.clone();
+            let hierarchy = hierarchy.clone();
             let task = thread_pool.spawn(async move {
-                let mut grid = PathGrid::new(finder, requester, tilemap, path_tilemap.clone());
-                grid.find_path(ty);
-                grid.collect_path()
+                let (status, path) = match (finder.algorithm, ty, uniform_cost, hierarchy) {
+                    (PathAlgorithm::JumpPoint, TilemapType::Square, Some(cost), _) => {
+                        jump_point::find_path(
+                            &path_tilemap,
+                            finder.origin,
+                            finder.dest,
+                            finder.allow_diagonal,
+                            cost,
+                            finder.max_steps,
+                        )
+                    }
+                    (PathAlgorithm::Hierarchical, _, _, Some(hierarchy))
+                        if hierarchy.ty() == ty
+                            && hierarchy.allow_diagonal() == finder.allow_diagonal =>
+                    {
+                        hierarchy.find_path(
+                            &path_tilemap,
+                            finder.origin,
+                            finder.dest,
+                            finder.max_steps,
+                        )
+                    }
+                    _ => {
+                        let mut grid = PathGrid::new(finder, requester, tilemap, path_tilemap);
+                        grid.find_path(ty);
+                        return grid.collect_path();
+                    }
+                };
+                Path {
+                    path,
+                    current_step: 0,
+                    tilemap,
+                    status,
+                }
             });
             tasks.push((requester, task));
-        });
+        }
         queue.tasks.extend(tasks);
+
+        if !cooperative.is_empty() {
+            cooperative.sort_by_key(|(requester, _)| *requester);
+            let requesters = cooperative
+                .iter()
+                .map(|(requester, _)| *requester)
+                .collect();
+            let ty = *ty;
+            let reservations = queue.reservations.clone();
+            let task = thread_pool.spawn(async move {
+                cooperative::find_paths(&path_tilemap, ty, &reservations, cooperative)
+                    .into_iter()
+                    .map(|(_, status, path)| Path {
+                        path,
+                        current_step: 0,
+                        tilemap,
+                        status,
+                    })
+                    .collect()
+            });
+            queue.cooperative_task = Some((requesters, task));
+        }
     });
 }
 
-pub fn path_assigner(mut commands: Commands, mut queues_query: Query<&mut PathFindingQueue>) {
-    queues_query.for_each_mut(|mut queue| {
+pub fn path_assigner(
+    mut commands: Commands,
+    mut queues_query: Query<(Entity, &mut PathFindingQueue)>,
+    mut invalidated: EventWriter<PathInvalidated>,
+) {
+    queues_query.for_each_mut(|(tilemap, mut queue)| {
         let mut completed = Vec::new();
         queue.tasks.iter_mut().for_each(|(requester, task)| {
             if let Some(path) = bevy::tasks::block_on(futures_lite::future::poll_once(task)) {
-                commands.entity(*requester).insert(path);
-                completed.push(*requester);
+                completed.push((*requester, path));
             }
         });
-        completed.iter().for_each(|requester| {
-            queue.tasks.remove(requester);
+        if let Some((requesters, task)) = &mut queue.cooperative_task {
+            if let Some(paths) = bevy::tasks::block_on(futures_lite::future::poll_once(task)) {
+                completed.extend(requesters.iter().copied().zip(paths));
+                queue.cooperative_task = None;
+            }
+        }
+        completed.into_iter().for_each(|(requester, path)| {
+            queue.tasks.remove(&requester);
+            // The path is found with an outdated cache.
+            if queue
+                .outdated
+                .remove(&requester)
+                .is_some_and(|changed| path.iter().any(|index| changed.contains(index)))
+            {
+                invalidated.send(PathInvalidated { requester, tilemap });
+            }
+            commands.entity(requester).insert(path);
         });
     });
 }
 
+/// Keep the caches of the queues up to date with the `PathTilemap`s on the same entities.
+pub fn path_cache_synchronizer(
+    mut queues_query: Query<(Entity, &PathTilemap, &mut PathFindingQueue), Changed<PathTilemap>>,
+    paths_query: Query<(Entity, &Path)>,
+    mut invalidated: EventWriter<PathInvalidated>,
+) {
+    queues_query.for_each_mut(|(tilemap, path_tilemap, mut queue)| {
+        let changed = queue.sync_cache(path_tilemap);
+        if changed.is_empty() {
+            return;
+        }
+
+        paths_query
+            .iter()
+            .filter(|(_, path)| {
+                path.tilemap == tilemap && path.iter().any(|index| changed.contains(index))
+            })
+            .for_each(|(requester, _)| {
+                invalidated.send(PathInvalidated { requester, tilemap });
+            });
+
+        let queue = &mut *queue;
+        let cooperative = queue.cooperative_task.iter().flat_map(|(r, _)| r);
+        for requester in queue.tasks.keys().chain(cooperative) {
+            queue
+                .outdated
+                .entry(*requester)
+                .or_default()
+                .extend(changed.iter().copied());
+        }
+    });
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::tilemap::algorithm::path::PathTile;
 
     #[test]
     fn test_pathfinding() {
@@ -328,4 +667,89 @@ mod test {
         let path = grid.collect_path();
         dbg!(path.path);
     }
+
+    #[test]
+    fn test_sync_cache() {
+        let mut path_tilemap = PathTilemap::new_with_chunk_size(4);
+        for y in 0..8 {
+            for x in 0..8 {
+                path_tilemap.set(IVec2 { x, y }, PathTile { cost: 1 });
+            }
+        }
+        let mut queue = PathFindingQueue::new(path_tilemap.clone());
+        let snapshot = queue.get_cache();
+        assert!(queue.sync_cache(&path_tilemap).is_empty());
+
+        path_tilemap.remove(IVec2::new(1, 1));
+        path_tilemap.get_mut(IVec2::new(6, 5)).unwrap().cost = 3;
+        // Modified but the same as before.
+        path_tilemap.set(IVec2::new(2, 6), PathTile { cost: 1 });
+        path_tilemap.set(IVec2::new(9, 9), PathTile { cost: 2 });
+
+        let changed = queue.sync_cache(&path_tilemap);
+        assert_eq!(
+            changed,
+            HashSet::from_iter([IVec2::new(1, 1), IVec2::new(6, 5), IVec2::new(9, 9)])
+        );
+        let cache = queue.get_cache();
+        assert!(cache.get(IVec2::new(1, 1)).is_none());
+        assert_eq!(cache.get(IVec2::new(6, 5)).unwrap().cost, 3);
+        assert_eq!(cache.get(IVec2::new(9, 9)).unwrap().cost, 2);
+        // The running tasks keep the old snapshot.
+        assert!(snapshot.get(IVec2::new(1, 1)).is_some());
+
+        path_tilemap.remove_chunk(IVec2::new(2, 2));
+        assert_eq!(
+            queue.sync_cache(&path_tilemap),
+            HashSet::from_iter([IVec2::new(9, 9)])
+        );
+        assert!(queue.get_cache().get(IVec2::new(9, 9)).is_none());
+    }
+
+    #[test]
+    fn test_path_status() {
+        let mut path_tilemap = PathTilemap::new();
+        for y in 0..10 {
+            for x in 0..10 {
+                // The right column is walled off.
+                if x != 8 {
+                    path_tilemap.set(IVec2 { x, y }, PathTile { cost: 1 });
+                }
+            }
+        }
+        let path_tilemap = Arc::new(path_tilemap);
+        let find = |dest: IVec2, max_steps: Option<u32>| {
+            let mut grid = PathGrid::new(
+                PathFinder {
+                    origin: IVec2::ZERO,
+                    dest,
+                    allow_diagonal: false,
+                    max_steps,
+                    algorithm: PathAlgorithm::AStar,
+                },
+                Entity::PLACEHOLDER,
+                Entity::PLACEHOLDER,
+                path_tilemap.clone(),
+            );
+            grid.find_path(TilemapType::Square);
+            grid.collect_path()
+        };
+
+        let path = find(IVec2::new(7, 9), None);
+        assert_eq!(path.status(), PathStatus::Found);
+        assert_eq!(path.iter().count(), 16);
+
+        for dest in [IVec2::new(9, 9), IVec2::new(8, 0), IVec2::new(-1, 0)] {
+            let path = find(dest, None);
+            assert_eq!(path.status(), PathStatus::Unreachable);
+            assert!(path.is_arrived());
+        }
+
+        // Ends at the tile closest to the destination, and never passes the origin.
+        let path = find(IVec2::new(7, 9), Some(20));
+        assert_eq!(path.status(), PathStatus::StepLimitReached);
+        let end = *path.iter().next().unwrap();
+        assert!(end.manhattan_distance(IVec2::new(7, 9)) < 16);
+        assert!(!path.iter().any(|index| *index == IVec2::ZERO));
+    }
 }
@@ -55,7 +55,7 @@ impl WfcRules {
         let mut rule_set = Vec::with_capacity(rule_vec.len());
         for tex_idx in 0..rule_vec.len() {
             let mut tex_rule: Vec<Vec<u8>> = {
-                match ty {
+                match ty.unstaggered() {
                     TilemapType::Hexagonal(_) => vec![vec![]; 6],
                     _ => vec![vec![]; 4],
                 }
@@ -71,7 +71,7 @@ impl WfcRules {
         let mut rule = Vec::with_capacity(rule_set.len());
         for tex_idx in 0..rule_set.len() {
             let mut tex_rule = {
-                match ty {
+                match ty.unstaggered() {
                     TilemapType::Hexagonal(_) => vec![0; 6],
                     _ => vec![0; 4],
                 }
@@ -91,7 +91,7 @@ impl WfcRules {
 
     /// Check if there are conflicts in the rules.
     pub fn check_rules(&self, ty: TilemapType) {
-        let (total_dirs, dir_names) = match ty {
+        let (total_dirs, dir_names) = match ty.unstaggered() {
             TilemapType::Hexagonal(_) => (6, HEX_DIR.to_vec()),
             _ => (4, DIR.to_vec()),
         };
@@ -681,8 +681,8 @@ pub fn wfc_applier(
                                     *v = *v + origin.as_vec2() * tile_render_size.unwrap().0;
                                 });
                                 tilemap.data.set_elem(*index + origin, tile.clone());
-                                let entity = tile.spawn(&mut commands, *ty.unwrap());
-                                tilemap.storage.set_elem(*index + origin, entity);
+                                let collider = tile.spawn(&mut commands, *ty.unwrap(), entity);
+                                tilemap.storage.set_elem(*index + origin, collider);
                             });
                         }
                     });
@@ -1,4 +1,7 @@
-use bevy::{ecs::system::Query, gizmos::gizmos::Gizmos, math::Vec2, render::color::Color};
+use bevy::{
+    ecs::system::Query, gizmos::gizmos::Gizmos, math::Vec2, render::color::Color,
+    transform::components::GlobalTransform,
+};
 
 use crate::{
     math::{aabb::Aabb2d, CameraAabb2d},
@@ -16,11 +19,12 @@ pub fn draw_chunk_aabb(
         &TilemapType,
         &TilePivot,
         &TilemapSlotSize,
-        &TilemapTransform,
+        &GlobalTransform,
         &TilemapStorage,
     )>,
 ) {
-    for (ty, tile_pivot, slot_size, transform, storage) in tilemaps.iter() {
+    for (ty, tile_pivot, slot_size, global_transform, storage) in tilemaps.iter() {
+        let transform = TilemapTransform::from_global(global_transform);
         storage.storage.chunks.keys().for_each(|chunk| {
             let aabb = Aabb2d::from_tilemap(
                 *chunk,
@@ -28,7 +32,7 @@ pub fn draw_chunk_aabb(
                 *ty,
                 tile_pivot.0,
                 slot_size.0,
-                *transform,
+                transform,
             );
             gizmos.rect_2d(
                 aabb.center(),
@@ -55,22 +59,18 @@ pub fn draw_tilemap_aabb(mut gizmos: Gizmos, tilemaps: Query<&TilemapAabbs>) {
 pub fn draw_path(
     mut gizmos: Gizmos,
     path_query: Query<&Path>,
-    tilemaps: Query<(
-        &TilemapType,
-        &TilemapTransform,
-        &TilePivot,
-        &TilemapSlotSize,
-    )>,
+    tilemaps: Query<(&TilemapType, &GlobalTransform, &TilePivot, &TilemapSlotSize)>,
 ) {
     for path in path_query.iter() {
-        let (ty, transform, pivot, slot_size) = tilemaps.get(path.tilemap()).unwrap();
+        let (ty, global_transform, pivot, slot_size) = tilemaps.get(path.tilemap()).unwrap();
+        let transform = TilemapTransform::from_global(global_transform);
 
         for node in path.iter() {
             gizmos.circle_2d(
                 crate::tilemap::coordinates::index_to_world(
                     *node,
                     ty,
-                    transform,
+                    &transform,
                     pivot.0,
                     slot_size.0,
                 ),
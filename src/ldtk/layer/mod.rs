@@ -15,10 +15,10 @@ use crate::{
         buffers::TileBuffer,
         bundles::TilemapBundle,
         map::{
-            TileRenderSize, TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStorage,
-            TilemapTexture, TilemapTransform, TilemapType,
+            TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
+            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTransform, TilemapType,
         },
-        tile::{TileBuilder, TileLayer, TileTexture},
+        tile::{TileAnimation, TileBuilder, TileLayer, TileTexture},
     },
     DEFAULT_CHUNK_SIZE,
 };
@@ -104,6 +104,10 @@ pub struct LdtkLayers<'a> {
     pub translation: Vec2,
     pub base_z_index: i32,
     pub background: SpriteBundle,
+    /// The animations used by the tiles, shared by all the layers.
+    pub animations: TilemapAnimations,
+    /// Texture index -> registered animation.
+    pub registered_animations: HashMap<u32, TileAnimation>,
     #[cfg(feature = "algorithm")]
     pub path_layer: Option<(
         path::LdtkPathLayer,
@@ -131,6 +135,8 @@ impl<'a> LdtkLayers<'a> {
             translation,
             base_z_index,
             background,
+            animations: TilemapAnimations::default(),
+            registered_animations: HashMap::new(),
             ty,
             #[cfg(feature = "algorithm")]
             path_layer: None,
@@ -139,9 +145,24 @@ impl<'a> LdtkLayers<'a> {
         }
     }
 
-    pub fn set_tile(&mut self, layer_index: usize, layer: &LayerInstance, tile: &TileInstance) {
+    pub fn set_tile(
+        &mut self,
+        layer_index: usize,
+        layer: &LayerInstance,
+        tile: &TileInstance,
+        config: &LdtkLoadConfig,
+    ) {
         self.try_create_new_layer(layer_index, layer);
 
+        let mut tile_layer = TileLayer::new()
+            .with_texture_index(tile.tile_id as u32)
+            .with_flip_raw(tile.flip as u32);
+        if self.ty == LdtkLoaderMode::Tilemap {
+            if let Some(anim) = self.get_animation(tile.tile_id as u32, config) {
+                tile_layer = tile_layer.with_animation(anim);
+            }
+        }
+
         let (pattern, texture, _, _) = self.layers[layer_index].as_mut().unwrap();
         let tile_size = texture.desc.tile_size;
         let tile_index = IVec2 {
@@ -153,20 +174,32 @@ impl<'a> LdtkLayers<'a> {
             let TileTexture::Static(tile_layers) = &mut ser_tile.texture else {
                 unreachable!()
             };
-            tile_layers.push(TileLayer::new().with_texture_index(tile.tile_id as u32));
+            tile_layers.push(tile_layer);
         } else {
             let builder = TileBuilder::new()
-                .with_layer(
-                    0,
-                    TileLayer::new()
-                        .with_texture_index(tile.tile_id as u32)
-                        .with_flip_raw(tile.flip as u32),
-                )
+                .with_layer(0, tile_layer)
                 .with_color(Vec4::new(1., 1., 1., tile.alpha));
             pattern.tiles.tiles.insert(tile_index, builder);
         }
     }
 
+    fn get_animation(
+        &mut self,
+        texture_index: u32,
+        config: &LdtkLoadConfig,
+    ) -> Option<TileAnimation> {
+        if let Some(anim) = self.registered_animations.get(&texture_index) {
+            return Some(*anim);
+        }
+
+        let raw = config.animation_mapper.get(&texture_index)?;
+        let anim = self
+            .animations
+            .register_animation(raw.fps, raw.sequence.clone());
+        self.registered_animations.insert(texture_index, anim);
+        Some(anim)
+    }
+
     pub fn set_entity(&mut self, entity: PackedLdtkEntity) {
         self.entities.push(entity);
     }
@@ -257,6 +290,16 @@ impl<'a> LdtkLayers<'a> {
                 })
                     .for_each(|(index, (pattern, texture, iid, opacity))| {
                         let tilemap_entity = commands.spawn_empty().id();
+                        let layer_count = pattern
+                            .tiles
+                            .tiles
+                            .values()
+                            .map(|t| match &t.texture {
+                                TileTexture::Static(layers) => layers.len(),
+                                TileTexture::Animated(_) => 1,
+                            })
+                            .max()
+                            .unwrap_or_default();
                         let mut tilemap = TilemapBundle {
                             name: TilemapName(pattern.label.clone().unwrap()),
                             ty: TilemapType::Square,
@@ -269,7 +312,8 @@ impl<'a> LdtkLayers<'a> {
                                 z_index: self.base_z_index - index as i32 - 1,
                                 ..Default::default()
                             },
-                            layer_opacities: TilemapLayerOpacities([opacity; 4].into()),
+                            layer_opacities: TilemapLayerOpacities(vec![opacity; layer_count]),
+                            animations: self.animations.clone(),
                             ..Default::default()
                         };
 
@@ -287,6 +331,7 @@ impl<'a> LdtkLayers<'a> {
                                                 path_tilemap.clone(),
                                                 None,
                                             ),
+                                        ..Default::default()
                                     },
                                 );
                             }
@@ -19,7 +19,10 @@ use bevy::{
 
 use crate::{
     serializing::pattern::TilemapPattern,
-    tilemap::map::{TilemapRotation, TilemapTexture, TilemapTextureDescriptor},
+    tilemap::{
+        map::{TilemapRotation, TilemapTexture, TilemapTextureDescriptor},
+        tile::RawTileAnimation,
+    },
 };
 
 use super::{
@@ -308,6 +311,9 @@ pub struct LdtkLoadConfig {
     pub z_index: i32,
     pub ignore_unregistered_entities: bool,
     pub ignore_unregistered_entity_tags: bool,
+    /// Animate the tiles with these texture indices.
+    /// Only available in `LdtkLoaderMode::Tilemap` as patterns don't contain animations.
+    pub animation_mapper: HashMap<u32, RawTileAnimation>,
 }
 
 #[derive(Resource, Default, Reflect)]
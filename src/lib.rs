@@ -13,20 +13,23 @@ pub mod math;
 pub mod render;
 #[cfg(feature = "serializing")]
 pub mod serializing;
+#[cfg(feature = "tiled")]
+pub mod tiled;
 pub mod tilemap;
 
-pub const MAX_LAYER_COUNT: usize = 4;
 pub const DEFAULT_CHUNK_SIZE: u32 = 16;
 
 pub mod prelude {
     #[cfg(feature = "algorithm")]
     pub use crate::algorithm::{
-        pathfinding::{Path, PathFinder},
+        flow_field::{FlowField, FlowFieldRequest},
+        pathfinding::{Path, PathAlgorithm, PathFinder, PathStatus},
         wfc::WfcRunner,
     };
     #[cfg(feature = "ldtk")]
     pub use crate::ldtk::resources::{LdtkAssets, LdtkLevelManager};
     pub use crate::math::{aabb::Aabb2d, TileArea};
+    pub use crate::render::{bake::TilemapBaking, grid::TilemapGrid, lod::TilemapLod};
     #[cfg(feature = "serializing")]
     pub use crate::serializing::{
         chunk::{
@@ -35,17 +38,28 @@ pub mod prelude {
         },
         map::{load::TilemapLoader, save::TilemapSaver},
     };
+    #[cfg(feature = "tiled")]
+    pub use crate::tiled::resources::{TiledAssets, TiledTilemapManager};
     #[cfg(feature = "physics")]
     pub use crate::tilemap::physics::TileCollision;
     pub use crate::tilemap::{
+        autotile::{AutoTileRule, TilemapAutoTiles},
         bundles::{PureColorTilemapBundle, TilemapBundle},
         chunking::camera::{CameraChunkUpdater, CameraChunkUpdation},
+        coordinates::TilemapCoordinates,
+        fog::{FogColors, FogState, TilemapFog},
+        lighting::{TilemapLighting, TilemapPointLight, TilemapSpotLight},
         map::{
-            TilePivot, TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
-            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
-            TilemapTransform, TilemapType,
+            TilePivot, TileRenderSize, TilemapAnimationPlayback, TilemapAnimations,
+            TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStagger, TilemapStorage,
+            TilemapTexture, TilemapTextureDescriptor, TilemapTransform, TilemapType,
+        },
+        mask::{TilemapMask, TilemapMaskMode},
+        picking::{TileClicked, TileDragged, TileHovered, TilemapPicking, TilemapPickingPlugin},
+        tile::{
+            TileAnimation, TileAnimationFinished, TileAnimationMode, TileBuilder, TileFlip,
+            TileLayer, TileRotation, TileUpdater,
         },
-        tile::{TileAnimation, TileBuilder, TileLayer, TileUpdater},
     };
 }
 
@@ -67,6 +81,8 @@ impl Plugin for EntiTilesPlugin {
             serializing::EntiTilesSerializingPlugin,
             #[cfg(feature = "ldtk")]
             ldtk::EntiTilesLdtkPlugin,
+            #[cfg(feature = "tiled")]
+            tiled::EntiTilesTiledPlugin,
         ));
     }
 }
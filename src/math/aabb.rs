@@ -173,6 +173,25 @@ impl Aabb2d {
 
                 Aabb2d { min, max }
             }
+            TilemapType::StaggeredIsometric(_) | TilemapType::StaggeredHexagonal(..) => {
+                let row_height = match ty {
+                    TilemapType::StaggeredHexagonal(legs, _) => (slot_size.y + legs as f32) / 2.,
+                    _ => slot_size.y / 2.,
+                };
+                let n = chunk_size as f32;
+                let min = chunk_index.as_vec2() * n * Vec2::new(slot_size.x, row_height);
+
+                // The shifted rows stick out by half a tile on the right.
+                Aabb2d {
+                    min: min - pivot_offset,
+                    max: min
+                        + Vec2 {
+                            x: (n + 0.5) * slot_size.x,
+                            y: (n - 1.) * row_height + slot_size.y,
+                        }
+                        - pivot_offset,
+                }
+            }
         })
     }
 
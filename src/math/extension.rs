@@ -1,5 +1,5 @@
 use bevy::{
-    math::IVec2,
+    math::{IVec2, IVec3},
     prelude::{UVec2, Vec2},
 };
 
@@ -114,6 +114,126 @@ impl ManhattanDistance<u32> for IVec2 {
     }
 }
 
+/// The neighbours of a hexagonal tile, counter-clockwise starting from the right one.
+///
+/// The hexagonal tilemaps use axial coordinates, where the `y` axis points to the upper left,
+/// so `[1, 1]` is the upper right neighbour.
+pub const HEX_DIRECTIONS: [IVec2; 6] = [
+    IVec2::X,
+    IVec2::ONE,
+    IVec2::Y,
+    IVec2::NEG_X,
+    IVec2::NEG_ONE,
+    IVec2::NEG_Y,
+];
+
+pub trait HexIndex: Sized {
+    /// Convert the axial coordinate into the cube coordinate, where `x + y + z = 0`.
+    fn to_cube(self) -> IVec3;
+    fn from_cube(cube: IVec3) -> Self;
+    /// The distance between two tiles, in tiles.
+    fn hex_distance(self, other: Self) -> u32;
+    fn hex_neighbours(self) -> [Self; 6];
+    /// The tiles that are exactly `radius` away from this one.
+    fn hex_ring(self, radius: u32) -> Vec<Self>;
+    /// The tiles that are at most `radius` away from this one.
+    fn hex_range(self, radius: u32) -> Vec<Self>;
+    /// The same tiles as `hex_range`, but ordered ring by ring from the center.
+    fn hex_spiral(self, radius: u32) -> Vec<Self>;
+    /// The tiles on the line from this one to `other`, including both ends.
+    fn hex_line(self, other: Self) -> Vec<Self>;
+}
+
+impl HexIndex for IVec2 {
+    #[inline]
+    fn to_cube(self) -> IVec3 {
+        IVec3::new(self.x, -self.y, self.y - self.x)
+    }
+
+    #[inline]
+    fn from_cube(cube: IVec3) -> Self {
+        IVec2::new(cube.x, -cube.y)
+    }
+
+    fn hex_distance(self, other: Self) -> u32 {
+        let d = (other.to_cube() - self.to_cube()).abs();
+        d.max_element() as u32
+    }
+
+    fn hex_neighbours(self) -> [Self; 6] {
+        HEX_DIRECTIONS.map(|d| self + d)
+    }
+
+    fn hex_ring(self, radius: u32) -> Vec<Self> {
+        if radius == 0 {
+            return vec![self];
+        }
+
+        let mut ring = Vec::with_capacity(radius as usize * 6);
+        let mut cur = self + HEX_DIRECTIONS[4] * radius as i32;
+        for dir in HEX_DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(cur);
+                cur += dir;
+            }
+        }
+        ring
+    }
+
+    fn hex_range(self, radius: u32) -> Vec<Self> {
+        let r = radius as i32;
+        (-r..=r)
+            .flat_map(|x| ((x - r).max(-r)..=(x + r).min(r)).map(move |y| IVec2::new(x, y)))
+            .map(|d| self + d)
+            .collect()
+    }
+
+    fn hex_spiral(self, radius: u32) -> Vec<Self> {
+        (0..=radius).flat_map(|r| self.hex_ring(r)).collect()
+    }
+
+    fn hex_line(self, other: Self) -> Vec<Self> {
+        let n = self.hex_distance(other);
+        if n == 0 {
+            return vec![self];
+        }
+
+        // Nudge the line a bit so it won't go exactly along the edges.
+        let a = self.as_vec2() + 1e-4;
+        let b = other.as_vec2() + 1e-4;
+        (0..=n)
+            .map(|i| hex_round(a.lerp(b, i as f32 / n as f32)))
+            .collect()
+    }
+}
+
+/// Round a fractional axial coordinate to the hexagonal tile that contains it.
+pub fn hex_round(index: Vec2) -> IVec2 {
+    // Convert to cube coordinates, round and fix the component with the largest error.
+    let cube = [index.x, -index.y, index.y - index.x];
+    let mut rounded = cube.map(|c| c.round());
+    let diff = [0, 1, 2].map(|i| (rounded[i] - cube[i]).abs());
+    if diff[0] > diff[1] && diff[0] > diff[2] {
+        rounded[0] = -rounded[1] - rounded[2];
+    } else if diff[1] > diff[2] {
+        rounded[1] = -rounded[0] - rounded[2];
+    }
+    IVec2::new(rounded[0] as i32, -rounded[1] as i32)
+}
+
+/// The hexagonal neighbours returned by `TileIndex::neighbours`.
+///
+/// They are ordered like the square ones, the opposite of the `i`th one is the `5 - i`th one:
+/// up right, right, down right, up left, left, down left.
+const HEX_NEIGHBOURS: [IVec2; 6] = [
+    IVec2::ONE,
+    IVec2::X,
+    IVec2::NEG_Y,
+    IVec2::Y,
+    IVec2::NEG_X,
+    IVec2::NEG_ONE,
+];
+
 pub trait TileIndex<T> {
     fn neighbours(self, ty: TilemapType, allow_diagonal: bool) -> Vec<Option<T>>;
 }
@@ -121,17 +241,16 @@ pub trait TileIndex<T> {
 impl TileIndex<IVec2> for IVec2 {
     fn neighbours(self, ty: TilemapType, allow_diagonal: bool) -> Vec<Option<IVec2>> {
         match ty {
-            TilemapType::Hexagonal(_) => [
-                IVec2::ONE,
-                IVec2::ONE,
-                IVec2::NEG_ONE,
-                IVec2::NEG_ONE,
-                IVec2::X,
-                IVec2::Y,
-            ]
-            .into_iter()
-            .map(|p| Some(p + self))
-            .collect(),
+            TilemapType::Hexagonal(_) => {
+                HEX_NEIGHBOURS.into_iter().map(|p| Some(p + self)).collect()
+            }
+            // Staggered tilemaps have the same neighbours as the unstaggered ones.
+            TilemapType::StaggeredIsometric(_) | TilemapType::StaggeredHexagonal(..) => ty
+                .to_unstaggered_index(self)
+                .neighbours(ty.unstaggered(), allow_diagonal)
+                .into_iter()
+                .map(|n| n.map(|n| ty.from_unstaggered_index(n)))
+                .collect(),
             _ => {
                 let seq = [
                     IVec2::Y,
@@ -158,53 +277,11 @@ impl TileIndex<IVec2> for IVec2 {
 
 impl TileIndex<UVec2> for UVec2 {
     fn neighbours(self, ty: TilemapType, allow_diagonal: bool) -> Vec<Option<UVec2>> {
-        match ty {
-            TilemapType::Hexagonal(_) => [
-                IVec2::ONE,
-                IVec2::ONE,
-                IVec2::NEG_ONE,
-                IVec2::NEG_ONE,
-                IVec2::X,
-                IVec2::Y,
-            ]
+        self.as_ivec2()
+            .neighbours(ty, allow_diagonal)
             .into_iter()
-            .map(|p| {
-                let nei = p + self.as_ivec2();
-                if nei.x >= 0 && nei.y >= 0 {
-                    Some(nei.as_uvec2())
-                } else {
-                    None
-                }
-            })
-            .collect(),
-            _ => {
-                let seq = [
-                    IVec2::Y,
-                    IVec2::X,
-                    IVec2::NEG_X,
-                    IVec2::NEG_Y,
-                    IVec2::ONE,
-                    IVec2::NEG_ONE,
-                    IVec2::new(1, -1),
-                    IVec2::new(-1, 1),
-                ]
-                .into_iter()
-                .map(|p| {
-                    let nei = p + self.as_ivec2();
-                    if nei.x >= 0 && nei.y >= 0 {
-                        Some(nei.as_uvec2())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-                if allow_diagonal {
-                    seq
-                } else {
-                    seq[0..4].to_vec()
-                }
-            }
-        }
+            .map(|n| n.filter(|n| n.x >= 0 && n.y >= 0).map(|n| n.as_uvec2()))
+            .collect()
     }
 }
 
@@ -296,4 +373,33 @@ mod test {
 
         println!("passed");
     }
+
+    #[test]
+    fn test_hex() {
+        let center = IVec2::new(2, -1);
+
+        for (i, n) in center.hex_neighbours().into_iter().enumerate() {
+            assert_eq!(center.hex_distance(n), 1);
+            assert_eq!(n, center.hex_ring(1)[(i + 2) % 6]);
+        }
+        assert_eq!(IVec2::ZERO.hex_distance(IVec2::new(2, -1)), 3);
+        assert_eq!(IVec2::from_cube(center.to_cube()), center);
+        let cube = center.to_cube();
+        assert_eq!(cube.x + cube.y + cube.z, 0);
+
+        for r in 0..4 {
+            let ring = center.hex_ring(r);
+            assert_eq!(ring.len(), (r as usize * 6).max(1));
+            assert!(ring.iter().all(|i| center.hex_distance(*i) == r));
+            assert_eq!(center.hex_range(r).len(), (3 * r * (r + 1) + 1) as usize);
+            assert_eq!(center.hex_spiral(r).len(), center.hex_range(r).len());
+        }
+
+        let target = center + IVec2::new(3, -2);
+        let line = center.hex_line(target);
+        assert_eq!(line.len(), 6);
+        assert_eq!(line[0], center);
+        assert_eq!(*line.last().unwrap(), target);
+        assert!(line.windows(2).all(|w| w[0].hex_distance(w[1]) == 1));
+    }
 }
@@ -74,7 +74,7 @@ pub fn camera_aabb_updater(
     });
 }
 
-#[derive(Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
 pub struct TileArea {
     pub origin: IVec2,
     pub extent: UVec2,
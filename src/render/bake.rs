@@ -0,0 +1,533 @@
+use bevy::{
+    core::cast_slice,
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        component::Component,
+        query::Without,
+        system::{Query, Res, ResMut, Resource},
+        world::FromWorld,
+    },
+    math::{Mat4, Vec2},
+    prelude::Entity,
+    reflect::Reflect,
+    render::{
+        color::Color,
+        render_phase::{DrawFunctions, RenderPhase},
+        render_resource::{
+            BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+            BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer,
+            BufferInitDescriptor, BufferUsages, ColorTargetState, ColorWrites, Extent3d,
+            FilterMode, FragmentState, FrontFace, LoadOp, MultisampleState, Operations,
+            PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology,
+            RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, Sampler,
+            SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType,
+            SpecializedRenderPipeline, SpecializedRenderPipelines, Texture, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+            TextureViewDescriptor, TextureViewDimension, VertexBufferLayout, VertexFormat,
+            VertexState, VertexStepMode,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::{Msaa, ViewUniform},
+    },
+    utils::FloatOrd,
+};
+
+use crate::{
+    math::aabb::Aabb2d,
+    tilemap::map::{TilemapStagger, TilemapType},
+};
+
+use super::{
+    binding::{TilemapBindGroupLayouts, TilemapBindGroups},
+    buffer::{DynamicOffsetComponent, TilemapUniform},
+    chunk::{RenderChunkStorage, TilemapRenderChunk},
+    draw::DrawBakedChunks,
+    extract::ExtractedTilemap,
+    fog::TilemapFogBuffers,
+    lighting::TilemapLightBuffers,
+    lod::{ExtractedTilemapLod, LodLevel},
+    mask::TilemapMaskBuffers,
+    material::ExtractedMaterialTilemap,
+    pipeline::{EntiTilesPipeline, EntiTilesPipelineKey},
+    BAKED_CHUNK_SHADER,
+};
+
+/// Bakes the static chunks of the tilemap into textures. Insert this component to the tilemap entity.
+///
+/// A chunk is baked when it has no animated tiles and is unchanged for `frames` frames.
+/// Then it's drawn as a single quad, until its tiles, mask, fog or lighting changes.
+///
+/// Tilemaps with custom materials are never baked, as the materials may change over time.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapBaking {
+    pub frames: u32,
+}
+
+impl Default for TilemapBaking {
+    fn default() -> Self {
+        Self { frames: 60 }
+    }
+}
+
+#[derive(Clone)]
+pub struct BakedChunk {
+    pub texture: Texture,
+    pub bind_group: BindGroup,
+    pub vertex_buffer: Buffer,
+    /// The uniform when baking, without the time. The chunk is baked again if it changes.
+    pub uniform: TilemapUniform,
+}
+
+#[derive(Resource, Clone)]
+pub struct BakedChunkPipeline {
+    pub view_layout: BindGroupLayout,
+    pub texture_layout: BindGroupLayout,
+}
+
+impl FromWorld for BakedChunkPipeline {
+    fn from_world(world: &mut bevy::prelude::World) -> Self {
+        let view_layout = world
+            .resource::<TilemapBindGroupLayouts>()
+            .view_layout
+            .clone();
+        let texture_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("baked_chunk_texture_layout"),
+                    entries: &[
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Texture {
+                                sample_type: TextureSampleType::Float { filterable: true },
+                                view_dimension: TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+
+        Self {
+            view_layout,
+            texture_layout,
+        }
+    }
+}
+
+impl BakedChunkPipeline {
+    pub fn create_bind_group(
+        &self,
+        render_device: &RenderDevice,
+        texture_view: &TextureView,
+        sampler: &Sampler,
+    ) -> BindGroup {
+        render_device.create_bind_group(
+            Some("baked_chunk_bind_group"),
+            &self.texture_layout,
+            &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+        )
+    }
+}
+
+impl SpecializedRenderPipeline for BakedChunkPipeline {
+    /// The msaa samples.
+    type Key = u32;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("baked_chunk_pipeline".into()),
+            layout: vec![self.view_layout.clone(), self.texture_layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: VertexState {
+                shader: BAKED_CHUNK_SHADER,
+                shader_defs: vec![],
+                entry_point: "baked_chunk_vertex".into(),
+                buffers: vec![VertexBufferLayout::from_vertex_formats(
+                    VertexStepMode::Vertex,
+                    // position, uv
+                    vec![VertexFormat::Float32x2, VertexFormat::Float32x2],
+                )],
+            },
+            fragment: Some(FragmentState {
+                shader: BAKED_CHUNK_SHADER,
+                shader_defs: vec![],
+                entry_point: "baked_chunk_fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+/// Render the chunks that have been static for long enough into textures.
+pub fn bake_chunks(
+    tilemaps_query: Query<
+        (&ExtractedTilemap, &DynamicOffsetComponent<TilemapUniform>),
+        Without<ExtractedMaterialTilemap>,
+    >,
+    mut render_chunks: ResMut<RenderChunkStorage>,
+    bind_groups: Res<TilemapBindGroups>,
+    mask_buffers: Res<TilemapMaskBuffers>,
+    fog_buffers: Res<TilemapFogBuffers>,
+    light_buffers: Res<TilemapLightBuffers>,
+    pipeline_cache: Res<PipelineCache>,
+    mut sp_entitiles_pipeline: ResMut<SpecializedRenderPipelines<EntiTilesPipeline>>,
+    entitiles_pipeline: Res<EntiTilesPipeline>,
+    baked_chunk_pipeline: Res<BakedChunkPipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let mut command_encoder = render_device.create_command_encoder(&Default::default());
+    let mut baked_any = false;
+
+    tilemaps_query.for_each(|(tilemap, uniform_offset)| {
+        let Some(frames) = tilemap.bake_frames else {
+            return;
+        };
+        let Some(chunks) = render_chunks.get_chunks_mut(tilemap.id) else {
+            return;
+        };
+
+        // The chunks with animated tiles are never baked, so the times don't affect the result.
+        let uniform = TilemapUniform {
+            time: 0.,
+            anim_time: 0.,
+            ..tilemap.into()
+        };
+        let is_pure_color = tilemap.texture.is_none();
+        let pipeline = sp_entitiles_pipeline.specialize(
+            &pipeline_cache,
+            &entitiles_pipeline,
+            EntiTilesPipelineKey {
+                msaa: 1,
+                map_type: tilemap.ty,
+                is_pure_color,
+                has_mask: tilemap.mask_color.is_some(),
+                has_fog: tilemap.fog_colors.is_some(),
+                has_lighting: tilemap.ambient_light.is_some(),
+            },
+        );
+
+        for (chunk_index, chunk) in chunks.iter_mut() {
+            if chunk.baked.as_ref().is_some_and(|b| b.uniform != uniform) {
+                chunk.invalidate_bake();
+            }
+            chunk.static_frames = chunk.static_frames.saturating_add(1);
+
+            if chunk.baked.is_some()
+                || chunk.dirty_mesh
+                || chunk.has_animated
+                || chunk.lod_level != LodLevel::Full
+                || !chunk.visible
+                || chunk.static_frames < frames
+            {
+                continue;
+            }
+
+            let Some(render_pipeline) = pipeline_cache.get_render_pipeline(pipeline) else {
+                return;
+            };
+            let Some(gpu_mesh) = &chunk.gpu_mesh else {
+                continue;
+            };
+            let Some(aabb) = chunk_tiles_aabb(chunk, tilemap) else {
+                continue;
+            };
+            let Some(uniform_bind_group) = &bind_groups.tilemap_uniform_buffer else {
+                return;
+            };
+
+            // The bind groups after the view and the uniform.
+            let mut extra_bind_groups = Vec::with_capacity(6);
+            if !is_pure_color {
+                let (Some(texture), Some(storage)) = (
+                    tilemap
+                        .texture
+                        .as_ref()
+                        .and_then(|t| bind_groups.colored_textures.get(t.handle())),
+                    bind_groups.tilemap_storage_buffers.get(&tilemap.id),
+                ) else {
+                    return;
+                };
+                let Some(layers) = &chunk.gpu_layers else {
+                    continue;
+                };
+                extra_bind_groups.extend([texture, storage, layers]);
+            }
+            if tilemap.mask_color.is_some() {
+                let Some(mask) = mask_buffers.get_bind_group(tilemap.id, *chunk_index) else {
+                    continue;
+                };
+                extra_bind_groups.push(mask);
+            }
+            if tilemap.fog_colors.is_some() {
+                let Some(fog) = fog_buffers.get_bind_group(tilemap.id, *chunk_index) else {
+                    continue;
+                };
+                extra_bind_groups.push(fog);
+            }
+            if tilemap.ambient_light.is_some() {
+                let Some(light) = light_buffers.get_bind_group(tilemap.id, *chunk_index) else {
+                    continue;
+                };
+                extra_bind_groups.push(light);
+            }
+
+            // One texel per unit in world space.
+            let max_size = render_device.limits().max_texture_dimension_2d as f32;
+            let texture_size = aabb.size().ceil().clamp(Vec2::ONE, Vec2::splat(max_size));
+            let texture = render_device.create_texture(&TextureDescriptor {
+                label: Some("baked_chunk_texture"),
+                size: Extent3d {
+                    width: texture_size.x as u32,
+                    height: texture_size.y as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::bevy_default(),
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let texture_view = texture.create_view(&TextureViewDescriptor::default());
+
+            // The tilemap shader only reads the `view_proj`, so the rest can be left empty.
+            let mut view_data = vec![0u8; ViewUniform::min_size().get() as usize];
+            let view_proj =
+                Mat4::orthographic_rh(aabb.min.x, aabb.max.x, aabb.min.y, aabb.max.y, -1., 1.);
+            view_data[..64].copy_from_slice(cast_slice(&view_proj.to_cols_array()));
+            let view_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("baked_chunk_view_buffer"),
+                contents: &view_data,
+                usage: BufferUsages::UNIFORM,
+            });
+            let view_bind_group = render_device.create_bind_group(
+                Some("baked_chunk_view_bind_group"),
+                &entitiles_pipeline.view_layout,
+                &[BindGroupEntry {
+                    binding: 0,
+                    resource: view_buffer.as_entire_binding(),
+                }],
+            );
+
+            {
+                let mut pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("bake_chunk_pass"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &texture_view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color::NONE.into()),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+
+                pass.set_pipeline(render_pipeline);
+                pass.set_bind_group(0, &view_bind_group, &[0]);
+                pass.set_bind_group(1, uniform_bind_group, &[uniform_offset.index()]);
+                for (i, bind_group) in extra_bind_groups.into_iter().enumerate() {
+                    pass.set_bind_group(i as u32 + 2, bind_group, &[]);
+                }
+
+                pass.set_vertex_buffer(0, *gpu_mesh.vertex_buffer.slice(..));
+                match &gpu_mesh.buffer_info {
+                    bevy::render::mesh::GpuBufferInfo::Indexed {
+                        buffer,
+                        count,
+                        index_format,
+                    } => {
+                        pass.set_index_buffer(*buffer.slice(..), *index_format);
+                        pass.draw_indexed(0..*count, 0, 0..1);
+                    }
+                    bevy::render::mesh::GpuBufferInfo::NonIndexed => {
+                        pass.draw(0..gpu_mesh.vertex_count, 0..1);
+                    }
+                }
+            }
+
+            let filter_mode = tilemap
+                .texture
+                .as_ref()
+                .map(|t| t.desc().filter_mode)
+                .unwrap_or(FilterMode::Nearest);
+            let sampler = render_device.create_sampler(&SamplerDescriptor {
+                label: Some("baked_chunk_sampler"),
+                mag_filter: filter_mode,
+                min_filter: filter_mode,
+                ..Default::default()
+            });
+
+            let bind_group =
+                baked_chunk_pipeline.create_bind_group(&render_device, &texture_view, &sampler);
+            // The top left corner of the texture is the top left corner of the aabb.
+            let vertex_buffer = create_quad(
+                &render_device,
+                [
+                    Vec2::new(aabb.min.x, aabb.max.y),
+                    aabb.max,
+                    Vec2::new(aabb.max.x, aabb.min.y),
+                    aabb.min,
+                ],
+            );
+
+            chunk.baked = Some(BakedChunk {
+                texture,
+                bind_group,
+                vertex_buffer,
+                uniform,
+            });
+            baked_any = true;
+        }
+    });
+
+    if baked_any {
+        render_queue.submit([command_encoder.finish()]);
+    }
+}
+
+/// Create the vertex buffer of a quad. The `corners` are the world positions of
+/// uv `(0, 0)`, `(1, 0)`, `(1, 1)` and `(0, 1)`.
+pub(crate) fn create_quad(render_device: &RenderDevice, corners: [Vec2; 4]) -> Buffer {
+    let uvs = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+    let vertices = [0, 1, 2, 0, 2, 3]
+        .into_iter()
+        .flat_map(|i| [corners[i].x, corners[i].y, uvs[i].x, uvs[i].y])
+        .collect::<Vec<_>>();
+
+    render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("baked_chunk_vertex_buffer"),
+        contents: cast_slice(&vertices),
+        usage: BufferUsages::VERTEX,
+    })
+}
+
+/// The world space aabb that contains all the tiles in the chunk, including the parts
+/// that exceed the slots.
+fn chunk_tiles_aabb(chunk: &TilemapRenderChunk, tilemap: &ExtractedTilemap) -> Option<Aabb2d> {
+    let mut result: Option<Aabb2d> = None;
+
+    chunk.tiles.iter().flatten().for_each(|tile| {
+        let origin = mesh_origin(tile.index.as_vec2(), tilemap);
+        for corner in [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE] {
+            let point = tilemap
+                .transform
+                .transform_point((corner - tilemap.tile_pivot) * tilemap.tile_render_size + origin);
+            result = Some(match result {
+                Some(aabb) => Aabb2d {
+                    min: aabb.min.min(point),
+                    max: aabb.max.max(point),
+                },
+                None => Aabb2d {
+                    min: point,
+                    max: point,
+                },
+            });
+        }
+    });
+
+    result
+}
+
+/// The same as `get_mesh_origin` in the shaders.
+pub(crate) fn mesh_origin(index: Vec2, tilemap: &ExtractedTilemap) -> Vec2 {
+    match tilemap.ty {
+        TilemapType::Square => index * tilemap.slot_size,
+        TilemapType::Isometric => {
+            Vec2::new(index.x - index.y, index.x + index.y) / 2. * tilemap.slot_size
+        }
+        TilemapType::Hexagonal(legs) => Vec2::new(
+            tilemap.slot_size.x * (index.x - 0.5 * index.y),
+            (tilemap.slot_size.y + legs as f32) / 2. * index.y,
+        ),
+        TilemapType::StaggeredIsometric(stagger) => {
+            Vec2::new(index.x + stagger_shift(index.y, stagger), index.y / 2.) * tilemap.slot_size
+        }
+        TilemapType::StaggeredHexagonal(legs, stagger) => Vec2::new(
+            tilemap.slot_size.x * (index.x + stagger_shift(index.y, stagger)),
+            (tilemap.slot_size.y + legs as f32) / 2. * index.y,
+        ),
+    }
+}
+
+/// The shift of a row in tiles. The positions between rows, like the corners of
+/// lod summaries, use the average shift.
+fn stagger_shift(y: f32, stagger: TilemapStagger) -> f32 {
+    if y.fract() == 0. {
+        stagger.get_shift(y as i32) as f32 / 2.
+    } else {
+        0.25
+    }
+}
+
+/// Queue the baked chunks and the lod summaries.
+pub fn queue_baked_chunks(
+    mut views_query: Query<&mut RenderPhase<Transparent2d>>,
+    tilemaps_query: Query<
+        (Entity, &ExtractedTilemap, Option<&ExtractedTilemapLod>),
+        Without<ExtractedMaterialTilemap>,
+    >,
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    mut sp_baked_chunk_pipeline: ResMut<SpecializedRenderPipelines<BakedChunkPipeline>>,
+    baked_chunk_pipeline: Res<BakedChunkPipeline>,
+    msaa: Res<Msaa>,
+) {
+    let pipeline =
+        sp_baked_chunk_pipeline.specialize(&pipeline_cache, &baked_chunk_pipeline, msaa.samples());
+    let draw_function = draw_functions.read().get_id::<DrawBakedChunks>().unwrap();
+
+    views_query.for_each_mut(|mut transparent_phase| {
+        tilemaps_query
+            .iter()
+            .filter(|(_, tilemap, lod)| tilemap.bake_frames.is_some() || lod.is_some())
+            .for_each(|(entity, tilemap, _)| {
+                transparent_phase.add(Transparent2d {
+                    sort_key: FloatOrd(tilemap.transform.z_index as f32),
+                    entity,
+                    pipeline,
+                    draw_function,
+                    batch_range: 0..1,
+                    dynamic_offset: None,
+                });
+            });
+    });
+}
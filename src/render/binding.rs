@@ -1,6 +1,7 @@
 use bevy::{
     asset::Handle,
     ecs::{component::Component, entity::Entity, system::Resource, world::FromWorld},
+    math::Vec4,
     render::{
         render_resource::{
             BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
@@ -19,6 +20,7 @@ use super::{
         PerTilemapBuffersStorage, TilemapStorageBuffers, TilemapUniform, TilemapUniformBuffer,
         UniformBuffer,
     },
+    chunk::GpuTileLayer,
     extract::ExtractedTilemap,
     pipeline::EntiTilesPipeline,
     texture::TilemapTexturesStorage,
@@ -127,6 +129,10 @@ pub struct TilemapBindGroupLayouts {
     pub tilemap_uniforms_layout: BindGroupLayout,
     pub tilemap_storage_layout: BindGroupLayout,
     pub color_texture_layout: BindGroupLayout,
+    pub layers_layout: BindGroupLayout,
+    pub mask_layout: BindGroupLayout,
+    pub fog_layout: BindGroupLayout,
+    pub lighting_layout: BindGroupLayout,
 }
 
 impl FromWorld for TilemapBindGroupLayouts {
@@ -166,7 +172,7 @@ impl FromWorld for TilemapBindGroupLayouts {
                 label: Some("tilemap_storage_layout"),
                 entries: &[BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: ShaderStages::VERTEX,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Storage { read_only: true },
                         has_dynamic_offset: false,
@@ -176,6 +182,62 @@ impl FromWorld for TilemapBindGroupLayouts {
                 }],
             });
 
+        let layers_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tilemap_layers_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(GpuTileLayer::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let mask_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tilemap_mask_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(u32::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let fog_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tilemap_fog_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(u32::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let lighting_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tilemap_lighting_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(Vec4::min_size()),
+                },
+                count: None,
+            }],
+        });
+
         #[cfg(not(feature = "atlas"))]
         let color_texture_layout =
             render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -199,7 +261,7 @@ impl FromWorld for TilemapBindGroupLayouts {
                     },
                 ],
             });
-        
+
         #[cfg(feature = "atlas")]
         let color_texture_layout =
             render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -229,6 +291,10 @@ impl FromWorld for TilemapBindGroupLayouts {
             tilemap_uniforms_layout,
             tilemap_storage_layout,
             color_texture_layout,
+            layers_layout,
+            mask_layout,
+            fog_layout,
+            lighting_layout,
         }
     }
 }
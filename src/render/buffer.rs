@@ -1,22 +1,31 @@
-use std::marker::PhantomData;
+use std::{fmt::Debug, marker::PhantomData};
 
 use bevy::{
-    ecs::entity::Entity,
-    math::{Mat2, Vec4},
+    ecs::{
+        change_detection::DetectChanges,
+        entity::Entity,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Commands, Query, Res, ResMut},
+        world::Ref,
+    },
+    log::error,
+    math::{IVec2, Mat2, Vec4},
     prelude::{Component, Resource, Vec2},
     render::{
         render_resource::{
-            encase::internal::WriteInto, BindingResource, DynamicUniformBuffer, ShaderSize,
-            ShaderType, StorageBuffer,
+            encase::internal::WriteInto, BindGroup, BindGroupEntry, BindGroupLayout,
+            BindingResource, DynamicUniformBuffer, ShaderSize, ShaderType, StorageBuffer,
         },
         renderer::{RenderDevice, RenderQueue},
+        Extract,
     },
-    utils::EntityHashMap,
+    utils::{EntityHashMap, HashMap},
 };
 
 use crate::tilemap::map::TilemapType;
 
-use super::extract::ExtractedTilemap;
+use super::{chunk::RenderChunkStorage, extract::ExtractedTilemap, pipeline::EntiTilesPipeline};
 
 pub trait UniformBuffer<E, U: ShaderType + WriteInto + 'static> {
     fn insert(&mut self, extracted: &E) -> DynamicOffsetComponent<U>;
@@ -91,31 +100,272 @@ pub trait PerTilemapBuffersStorage<U: ShaderType + WriteInto + ShaderSize + 'sta
     fn get_mapper(&mut self) -> &mut EntityHashMap<Entity, (StorageBuffer<Vec<U>>, Vec<U>)>;
 }
 
-#[derive(ShaderType, Clone, Copy)]
+/// Storage buffers that are uploaded per chunk,
+/// and bound when drawing that chunk. Used by the fog and the lighting.
+#[derive(Resource)]
+pub struct PerChunkStorageBuffers<T: ShaderType + ShaderSize + WriteInto + Send + Sync + 'static> {
+    pub(crate) chunks: EntityHashMap<Entity, HashMap<IVec2, (StorageBuffer<Vec<T>>, BindGroup)>>,
+}
+
+impl<T: ShaderType + ShaderSize + WriteInto + Send + Sync + 'static> Default
+    for PerChunkStorageBuffers<T>
+{
+    fn default() -> Self {
+        Self {
+            chunks: Default::default(),
+        }
+    }
+}
+
+impl<T: ShaderType + ShaderSize + WriteInto + Send + Sync + 'static> PerChunkStorageBuffers<T> {
+    #[inline]
+    pub fn get_bind_group(&self, tilemap: Entity, chunk_index: IVec2) -> Option<&BindGroup> {
+        self.chunks
+            .get(&tilemap)
+            .and_then(|chunks| chunks.get(&chunk_index))
+            .map(|(_, bind_group)| bind_group)
+    }
+
+    /// The data of the chunk that was last written to the gpu.
+    #[inline]
+    pub fn get_data(&self, tilemap: Entity, chunk_index: IVec2) -> Option<&Vec<T>> {
+        self.chunks
+            .get(&tilemap)
+            .and_then(|chunks| chunks.get(&chunk_index))
+            .map(|(buffer, _)| buffer.get())
+    }
+
+    pub fn write_chunk(
+        &mut self,
+        tilemap: Entity,
+        chunk_index: IVec2,
+        data: Vec<T>,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        layout: &BindGroupLayout,
+    ) {
+        let mut buffer = self
+            .chunks
+            .entry(tilemap)
+            .or_default()
+            .remove(&chunk_index)
+            .map(|(buffer, _)| buffer)
+            .unwrap_or_default();
+        buffer.set(data);
+        buffer.write_buffer(render_device, render_queue);
+
+        let bind_group = render_device.create_bind_group(
+            Some("tilemap_per_chunk_bind_group"),
+            layout,
+            &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.binding().unwrap(),
+            }],
+        );
+        self.chunks
+            .get_mut(&tilemap)
+            .unwrap()
+            .insert(chunk_index, (buffer, bind_group));
+    }
+
+    /// Create buffers filled with `default` for the chunks that don't have one yet.
+    pub fn fill_missing(
+        &mut self,
+        tilemap: Entity,
+        chunk_indices: impl Iterator<Item = IVec2>,
+        default: impl Fn() -> Vec<T>,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        layout: &BindGroupLayout,
+    ) {
+        chunk_indices.for_each(|index| {
+            if self.get_bind_group(tilemap, index).is_none() {
+                self.write_chunk(
+                    tilemap,
+                    index,
+                    default(),
+                    render_device,
+                    render_queue,
+                    layout,
+                );
+            }
+        });
+    }
+
+    #[inline]
+    pub fn remove(&mut self, tilemap: Entity) {
+        self.chunks.remove(&tilemap);
+    }
+}
+
+/// A tilemap component whose data is uploaded to [`PerChunkStorageBuffers`].
+pub trait PerChunkData: Component + Debug {
+    type Item: ShaderType + ShaderSize + WriteInto + Clone + Debug + Send + Sync + 'static;
+
+    /// Used in the error messages.
+    const NAME: &'static str;
+    /// The data of the tiles in the chunks that are never uploaded.
+    const DEFAULT: Self::Item;
+
+    fn chunk_size(&self) -> u32;
+
+    /// All the chunks if the component is just added, otherwise the dirty ones.
+    fn changed_chunks(&self, added: bool) -> Vec<IVec2>;
+
+    fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<Self::Item>;
+
+    /// Whether the tilemap draws with the buffers.
+    fn is_enabled(tilemap: &ExtractedTilemap) -> bool;
+
+    fn layout(pipeline: &EntiTilesPipeline) -> &BindGroupLayout;
+
+    /// Discard what's drawn with the old data of the chunk.
+    /// `chunk_index` is `None` if the component is removed.
+    fn invalidate(
+        render_chunks: &mut RenderChunkStorage,
+        tilemap: Entity,
+        chunk_index: Option<IVec2>,
+    ) {
+        if let Some(index) = chunk_index {
+            render_chunks.invalidate_bake(tilemap, index);
+        }
+    }
+}
+
+/// Only exists when the component is changed or removed.
+#[derive(Component, Debug)]
+pub struct ExtractedPerChunkData<C: PerChunkData> {
+    pub chunk_size: u32,
+    /// The data of the changed chunks. `None` if the component is removed.
+    pub chunks: Option<Vec<(IVec2, Vec<C::Item>)>>,
+}
+
+pub fn extract_per_chunk_data<C: PerChunkData>(
+    mut commands: Commands,
+    data_query: Extract<Query<(Entity, Ref<C>), Changed<C>>>,
+    mut removed_data: Extract<RemovedComponents<C>>,
+) {
+    let mut extracted = removed_data
+        .read()
+        .map(|entity| {
+            (
+                entity,
+                ExtractedPerChunkData::<C> {
+                    chunk_size: 0,
+                    chunks: None,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    extracted.extend(data_query.iter().map(|(entity, data)| {
+        (
+            entity,
+            ExtractedPerChunkData::<C> {
+                chunk_size: data.chunk_size(),
+                chunks: Some(
+                    data.changed_chunks(data.is_added())
+                        .into_iter()
+                        .map(|index| (index, data.get_chunk_data(index)))
+                        .collect(),
+                ),
+            },
+        )
+    }));
+
+    commands.insert_or_spawn_batch(extracted);
+}
+
+pub fn prepare_per_chunk_buffers<C: PerChunkData>(
+    extracted_query: Query<(Entity, &ExtractedPerChunkData<C>)>,
+    tilemaps_query: Query<&ExtractedTilemap>,
+    mut render_chunks: ResMut<RenderChunkStorage>,
+    mut buffers: ResMut<PerChunkStorageBuffers<C::Item>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    entitiles_pipeline: Res<EntiTilesPipeline>,
+) {
+    let layout = C::layout(&entitiles_pipeline);
+
+    extracted_query.for_each(|(entity, extracted)| {
+        let Some(chunks) = &extracted.chunks else {
+            buffers.remove(entity);
+            C::invalidate(&mut render_chunks, entity, None);
+            return;
+        };
+
+        if let Ok(tilemap) = tilemaps_query.get(entity) {
+            if tilemap.chunk_size != extracted.chunk_size {
+                error!(
+                    "The chunk size of the {} ({}) doesn't match the tilemap {} ({})!",
+                    C::NAME,
+                    extracted.chunk_size,
+                    tilemap.name,
+                    tilemap.chunk_size
+                );
+                return;
+            }
+        }
+
+        chunks.iter().for_each(|(index, data)| {
+            buffers.write_chunk(
+                entity,
+                *index,
+                data.clone(),
+                &render_device,
+                &render_queue,
+                layout,
+            );
+            C::invalidate(&mut render_chunks, entity, Some(*index));
+        });
+    });
+
+    // The chunks that have no data yet.
+    tilemaps_query
+        .iter()
+        .filter(|tilemap| C::is_enabled(tilemap))
+        .for_each(|tilemap| {
+            let Some(chunks) = render_chunks.get_chunks(tilemap.id) else {
+                return;
+            };
+
+            buffers.fill_missing(
+                tilemap.id,
+                chunks.keys().copied(),
+                || vec![C::DEFAULT; (tilemap.chunk_size * tilemap.chunk_size) as usize],
+                &render_device,
+                &render_queue,
+                layout,
+            );
+        });
+}
+
+#[derive(ShaderType, Clone, Copy, PartialEq)]
 pub struct TilemapUniform {
     pub translation: Vec2,
-    pub rotation: Mat2,
+    /// Scale and rotation.
+    pub transform: Mat2,
     pub uv_rotation: u32,
     pub tile_render_size: Vec2,
     pub slot_size: Vec2,
     pub pivot: Vec2,
-    pub layer_opacities: Vec4,
+    pub mask_color: Vec4,
+    pub fog_unexplored_color: Vec4,
+    pub fog_explored_color: Vec4,
+    pub ambient_light: Vec4,
     pub hex_legs: f32,
     pub time: f32,
+    pub anim_time: f32,
+    pub chunk_size: u32,
+    pub mask_inverted: u32,
     #[cfg(feature = "atlas")]
     pub texture_tiled_size: bevy::math::IVec2,
     #[cfg(feature = "atlas")]
     pub tile_uv_size: Vec2,
 }
 
-#[derive(Resource, Default)]
-pub struct TilemapUniformBuffer(DynamicUniformBuffer<TilemapUniform>);
-
-impl UniformBuffer<ExtractedTilemap, TilemapUniform> for TilemapUniformBuffer {
-    /// Update the uniform buffer with the current tilemap uniforms.
-    /// Returns the `TilemapUniform` component to be used in the tilemap render pass.
-    #[inline]
-    fn insert(&mut self, extracted: &ExtractedTilemap) -> DynamicOffsetComponent<TilemapUniform> {
+impl From<&ExtractedTilemap> for TilemapUniform {
+    fn from(extracted: &ExtractedTilemap) -> Self {
         let uv_rotation = {
             if let Some(tex) = extracted.texture.as_ref() {
                 tex.rotation as u32 / 90
@@ -124,6 +374,8 @@ impl UniformBuffer<ExtractedTilemap, TilemapUniform> for TilemapUniformBuffer {
             }
         };
 
+        let fog_colors = extracted.fog_colors.unwrap_or_default();
+
         #[cfg(feature = "atlas")]
         let (texture_tiled_size, tile_uv_size) = {
             if let Some(tex) = extracted.texture.as_ref() {
@@ -136,24 +388,44 @@ impl UniformBuffer<ExtractedTilemap, TilemapUniform> for TilemapUniformBuffer {
             }
         };
 
-        DynamicOffsetComponent::new(self.buffer().push(TilemapUniform {
+        TilemapUniform {
             translation: extracted.transform.translation,
-            rotation: extracted.transform.get_rotation_matrix(),
+            transform: extracted.transform.get_matrix(),
             uv_rotation,
             tile_render_size: extracted.tile_render_size,
             slot_size: extracted.slot_size,
             pivot: extracted.tile_pivot,
-            layer_opacities: extracted.layer_opacities,
+            mask_color: extracted.mask_color.unwrap_or(Vec4::ONE),
+            fog_unexplored_color: fog_colors.unexplored,
+            fog_explored_color: fog_colors.explored,
+            ambient_light: extracted.ambient_light.unwrap_or(Vec4::ONE),
             hex_legs: match extracted.ty {
-                TilemapType::Hexagonal(legs) => legs as f32,
+                TilemapType::Hexagonal(legs) | TilemapType::StaggeredHexagonal(legs, _) => {
+                    legs as f32
+                }
                 _ => 0.,
             },
             time: extracted.time,
+            anim_time: extracted.anim_time,
+            chunk_size: extracted.chunk_size,
+            mask_inverted: extracted.mask_inverted as u32,
             #[cfg(feature = "atlas")]
             texture_tiled_size,
             #[cfg(feature = "atlas")]
             tile_uv_size,
-        }))
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TilemapUniformBuffer(DynamicUniformBuffer<TilemapUniform>);
+
+impl UniformBuffer<ExtractedTilemap, TilemapUniform> for TilemapUniformBuffer {
+    /// Update the uniform buffer with the current tilemap uniforms.
+    /// Returns the `TilemapUniform` component to be used in the tilemap render pass.
+    #[inline]
+    fn insert(&mut self, extracted: &ExtractedTilemap) -> DynamicOffsetComponent<TilemapUniform> {
+        DynamicOffsetComponent::new(self.buffer().push(extracted.into()))
     }
 
     #[inline]
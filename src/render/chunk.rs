@@ -1,11 +1,14 @@
 use bevy::{
     ecs::{component::Component, event::Event},
-    math::{IVec2, IVec4, UVec4},
+    math::{IVec2, UVec2},
     prelude::{Entity, Mesh, Resource, Vec3, Vec4},
     reflect::Reflect,
     render::{
         mesh::{GpuBufferInfo, GpuMesh, Indices},
-        render_resource::{BufferInitDescriptor, BufferUsages, IndexFormat, PrimitiveTopology},
+        render_resource::{
+            encase, BindGroup, BindGroupEntry, BindGroupLayout, BufferInitDescriptor, BufferUsages,
+            IndexFormat, PrimitiveTopology, ShaderType,
+        },
         renderer::RenderDevice,
     },
     utils::{EntityHashMap, HashMap},
@@ -14,16 +17,16 @@ use bevy::{
 use crate::{
     math::{aabb::Aabb2d, extension::DivToFloor},
     tilemap::{
-        map::{TilemapTexture, TilemapType},
-        tile::TileTexture,
+        map::{TilemapLayerOpacities, TilemapTexture, TilemapTransform, TilemapType},
+        tile::{TileLayer, TileTexture},
     },
-    MAX_LAYER_COUNT,
 };
 
 use super::{
+    bake::BakedChunk,
     extract::{ExtractedTile, ExtractedTilemap},
-    TILEMAP_MESH_ATTR_COLOR, TILEMAP_MESH_ATTR_FLIP, TILEMAP_MESH_ATTR_INDEX,
-    TILEMAP_MESH_ATTR_TEX_INDICES,
+    lod::LodLevel,
+    TILEMAP_MESH_ATTR_COLOR, TILEMAP_MESH_ATTR_INDEX, TILEMAP_MESH_ATTR_LAYERS,
 };
 
 #[derive(Component, Default, Debug, Clone, Reflect)]
@@ -37,14 +40,50 @@ pub struct ChunkUnload {
 
 #[derive(Clone)]
 pub struct MeshTileData {
-    // When the third and forth component of index are not -1,
-    // it means this tile is a animated tile
-    // So the zw components are the start index and the length of the animation sequence
-    pub index: IVec4,
-    // 4 layers
-    pub texture_indices: IVec4,
+    pub index: IVec2,
+    // From the bottom to the top.
+    pub layers: Vec<TileLayer>,
     pub color: Vec4,
-    pub flip: UVec4,
+}
+
+/// A tile layer in the per chunk layer buffer.
+#[derive(ShaderType, Clone, Copy)]
+pub struct GpuTileLayer {
+    pub texture_index: i32,
+    pub flip: u32,
+    pub rotation: u32,
+    pub opacity: f32,
+    /// The start of the animation sequence in `anim_seqs`, -1 if this layer is not animated.
+    pub anim_start: i32,
+    pub anim_length: u32,
+    pub anim_mode: u32,
+    pub anim_offset: f32,
+}
+
+impl GpuTileLayer {
+    pub fn new(layer: &TileLayer, opacity: f32) -> Self {
+        // The texture index of animated layers is determined in the shader.
+        let (anim_start, anim_length, anim_mode, anim_offset) =
+            layer.animation.map_or((-1, 0, 0, 0.), |anim| {
+                (
+                    anim.start as i32,
+                    anim.length,
+                    anim.mode as u32,
+                    anim.offset,
+                )
+            });
+
+        Self {
+            texture_index: layer.texture_index,
+            flip: layer.flip,
+            rotation: layer.rotation as u32,
+            opacity,
+            anim_start,
+            anim_length,
+            anim_mode,
+            anim_offset,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -58,7 +97,17 @@ pub struct TilemapRenderChunk {
     pub tiles: Vec<Option<MeshTileData>>,
     pub mesh: Mesh,
     pub gpu_mesh: Option<GpuMesh>,
+    /// The layers of all the tiles. Bound to `@group(#{LAYERS_BIND_GROUP})`.
+    pub gpu_layers: Option<BindGroup>,
     pub aabb: Aabb2d,
+    /// If there are any animated tiles in this chunk. These chunks are never baked.
+    pub has_animated: bool,
+    /// How many frames this chunk has been unchanged for.
+    pub static_frames: u32,
+    pub baked: Option<BakedChunk>,
+    pub lod_level: LodLevel,
+    /// The per tile and per chunk colour summaries.
+    pub lod_summaries: Option<[BakedChunk; 2]>,
 }
 
 impl TilemapRenderChunk {
@@ -72,20 +121,36 @@ impl TilemapRenderChunk {
             tiles: vec![None; (tilemap.chunk_size * tilemap.chunk_size) as usize],
             mesh: Mesh::new(PrimitiveTopology::TriangleList),
             gpu_mesh: None,
+            gpu_layers: None,
             dirty_mesh: true,
-            aabb: Aabb2d::from_tilemap(
-                index,
-                tilemap.chunk_size,
-                tilemap.ty,
-                tilemap.tile_pivot,
-                tilemap.slot_size,
-                tilemap.transform,
-            ),
+            has_animated: false,
+            static_frames: 0,
+            baked: None,
+            lod_level: LodLevel::Full,
+            lod_summaries: None,
+            aabb: Self::calculate_aabb(index, tilemap),
         }
     }
 
+    #[inline]
+    fn calculate_aabb(chunk_index: IVec2, tilemap: &ExtractedTilemap) -> Aabb2d {
+        Aabb2d::from_tilemap(
+            chunk_index,
+            tilemap.chunk_size,
+            tilemap.ty,
+            tilemap.tile_pivot,
+            tilemap.slot_size,
+            tilemap.transform,
+        )
+    }
+
     /// Update the raw mesh for GPU processing.
-    pub fn update_mesh(&mut self, render_device: &RenderDevice) {
+    pub fn update_mesh(
+        &mut self,
+        render_device: &RenderDevice,
+        layer_opacities: &TilemapLayerOpacities,
+        layers_layout: &BindGroupLayout,
+    ) {
         if !self.dirty_mesh {
             return;
         }
@@ -95,21 +160,24 @@ impl TilemapRenderChunk {
         let len = self.tiles.len();
 
         let mut positions = Vec::with_capacity(len * 4);
-        let mut texture_indices = Vec::with_capacity(len * 4);
+        let mut layer_ranges = Vec::with_capacity(len * 4);
+        let mut gpu_layers = Vec::with_capacity(len);
         let mut grid_indices = Vec::with_capacity(len * 4);
         let mut vertex_indices = Vec::with_capacity(len * 6);
         let mut color = Vec::with_capacity(len * 4);
-        let mut flip = Vec::with_capacity(len * 4);
 
         for tile_data in self.tiles.iter() {
             if let Some(tile) = tile_data {
                 if !is_pure_color {
-                    texture_indices.extend_from_slice(&[
-                        tile.texture_indices,
-                        tile.texture_indices,
-                        tile.texture_indices,
-                        tile.texture_indices,
-                    ]);
+                    let start = gpu_layers.len() as u32;
+                    gpu_layers.extend(
+                        tile.layers
+                            .iter()
+                            .enumerate()
+                            .map(|(i, layer)| GpuTileLayer::new(layer, layer_opacities.get(i))),
+                    );
+                    let range = UVec2::new(start, gpu_layers.len() as u32 - start);
+                    layer_ranges.extend_from_slice(&[range, range, range, range]);
                 }
 
                 let pos = Vec3::ZERO;
@@ -128,7 +196,6 @@ impl TilemapRenderChunk {
 
                 grid_indices.extend_from_slice(&[tile.index, tile.index, tile.index, tile.index]);
                 color.extend_from_slice(&[tile.color, tile.color, tile.color, tile.color]);
-                flip.extend_from_slice(&[tile.flip, tile.flip, tile.flip, tile.flip]);
             }
         }
 
@@ -139,8 +206,7 @@ impl TilemapRenderChunk {
         self.mesh.insert_attribute(TILEMAP_MESH_ATTR_COLOR, color);
         if !is_pure_color {
             self.mesh
-                .insert_attribute(TILEMAP_MESH_ATTR_TEX_INDICES, texture_indices);
-            self.mesh.insert_attribute(TILEMAP_MESH_ATTR_FLIP, flip)
+                .insert_attribute(TILEMAP_MESH_ATTR_LAYERS, layer_ranges);
         }
         self.mesh.set_indices(Some(Indices::U32(vertex_indices)));
 
@@ -175,9 +241,57 @@ impl TilemapRenderChunk {
             layout: self.mesh.get_mesh_vertex_buffer_layout(),
         });
 
+        self.gpu_layers = (!is_pure_color).then(|| {
+            // Storage buffers can't be empty.
+            if gpu_layers.is_empty() {
+                gpu_layers.push(GpuTileLayer::new(&TileLayer::new(), 0.));
+            }
+
+            let mut data = encase::StorageBuffer::new(Vec::new());
+            data.write(&gpu_layers).unwrap();
+            let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("tilemap_layers_buffer"),
+                contents: &data.into_inner(),
+                usage: BufferUsages::STORAGE,
+            });
+
+            render_device.create_bind_group(
+                Some("tilemap_layers_bind_group"),
+                layers_layout,
+                &[BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            )
+        });
+
+        self.has_animated = self.tiles.iter().any(|t| {
+            t.as_ref()
+                .is_some_and(|t| t.layers.iter().any(|l| l.animation.is_some()))
+        });
+        self.invalidate_bake();
+        self.lod_summaries = None;
         self.dirty_mesh = false;
     }
 
+    /// Discard the baked texture and restart counting the static frames.
+    #[inline]
+    pub fn invalidate_bake(&mut self) {
+        self.static_frames = 0;
+        self.baked = None;
+    }
+
+    /// The quad to draw instead of the tile mesh, if any.
+    #[inline]
+    pub fn quad(&self) -> Option<&BakedChunk> {
+        match self.lod_level {
+            LodLevel::Full => self.baked.as_ref(),
+            LodLevel::TileColors => self.lod_summaries.as_ref().map(|s| &s[0]),
+            LodLevel::ChunkColor => self.lod_summaries.as_ref().map(|s| &s[1]),
+        }
+        .or(self.baked.as_ref())
+    }
+
     /// Set a tile in the chunk. Overwrites the previous tile.
     pub fn set_tile(&mut self, index: usize, tile: Option<&ExtractedTile>) {
         // TODO fix this. This allows the tile sort by y axis. But this approach looks weird.
@@ -189,35 +303,16 @@ impl TilemapRenderChunk {
             return;
         };
 
-        let mut texture_indices = IVec4::NEG_ONE;
-        let mut flip = UVec4::ZERO;
-        let tile_index = {
-            match &tile.texture {
-                TileTexture::Static(tex) => {
-                    tex.iter()
-                        .enumerate()
-                        .rev()
-                        .take(MAX_LAYER_COUNT)
-                        .for_each(|(i, t)| {
-                            texture_indices[i] = t.texture_index;
-                            flip[i] = t.flip;
-                        });
-                    IVec4::new(tile.index.x, tile.index.y, -1, -1)
-                }
-                TileTexture::Animated(anim) => IVec4::new(
-                    tile.index.x,
-                    tile.index.y,
-                    anim.start as i32,
-                    anim.length as i32,
-                ),
-            }
+        let layers = match &tile.texture {
+            TileTexture::Static(layers) => layers.clone(),
+            // An animated tile is a tile with a single animated layer.
+            TileTexture::Animated(anim) => vec![TileLayer::new().with_animation(*anim)],
         };
 
         self.tiles[index] = Some(MeshTileData {
-            index: tile_index,
-            texture_indices,
+            index: tile.index,
+            layers,
             color: tile.color,
-            flip,
         });
         self.dirty_mesh = true;
     }
@@ -226,15 +321,60 @@ impl TilemapRenderChunk {
 #[derive(Resource, Default)]
 pub struct RenderChunkStorage {
     pub(crate) value: EntityHashMap<Entity, HashMap<IVec2, TilemapRenderChunk>>,
+    pub(crate) layer_opacities: EntityHashMap<Entity, TilemapLayerOpacities>,
+    pub(crate) transforms: EntityHashMap<Entity, TilemapTransform>,
 }
 
 impl RenderChunkStorage {
     /// Update the mesh for all chunks of a tilemap.
-    pub fn prepare_chunks(&mut self, tilemap: &ExtractedTilemap, render_device: &RenderDevice) {
+    pub fn prepare_chunks(
+        &mut self,
+        tilemap: &ExtractedTilemap,
+        render_device: &RenderDevice,
+        layers_layout: &BindGroupLayout,
+    ) {
         if let Some(chunks) = self.value.get_mut(&tilemap.id) {
-            chunks
-                .values_mut()
-                .for_each(|c| c.update_mesh(render_device));
+            // The opacities are stored in the layer buffers, so re-mesh if they are changed.
+            if self.layer_opacities.get(&tilemap.id) != Some(&tilemap.layer_opacities) {
+                self.layer_opacities
+                    .insert(tilemap.id, tilemap.layer_opacities.clone());
+                chunks.values_mut().for_each(|c| c.dirty_mesh = true);
+            }
+
+            // The tilemap or its parents are moved.
+            if self.transforms.get(&tilemap.id) != Some(&tilemap.transform) {
+                self.transforms.insert(tilemap.id, tilemap.transform);
+                chunks.iter_mut().for_each(|(index, c)| {
+                    c.aabb = TilemapRenderChunk::calculate_aabb(*index, tilemap)
+                });
+            }
+
+            chunks.values_mut().for_each(|c| {
+                c.update_mesh(render_device, &tilemap.layer_opacities, layers_layout)
+            });
+        }
+    }
+
+    #[inline]
+    pub fn invalidate_bake(&mut self, tilemap: Entity, index: IVec2) {
+        if let Some(chunk) = self.value.get_mut(&tilemap).and_then(|c| c.get_mut(&index)) {
+            chunk.invalidate_bake();
+        }
+    }
+
+    /// Discard the lod summaries of the chunk, or of all the chunks if `index` is `None`.
+    pub fn invalidate_lod_summaries(&mut self, tilemap: Entity, index: Option<IVec2>) {
+        let Some(chunks) = self.value.get_mut(&tilemap) else {
+            return;
+        };
+
+        match index {
+            Some(index) => {
+                if let Some(chunk) = chunks.get_mut(&index) {
+                    chunk.lod_summaries = None;
+                }
+            }
+            None => chunks.values_mut().for_each(|c| c.lod_summaries = None),
         }
     }
 
@@ -256,6 +396,8 @@ impl RenderChunkStorage {
         &mut self,
         tilemap: Entity,
     ) -> Option<HashMap<IVec2, TilemapRenderChunk>> {
+        self.layer_opacities.remove(&tilemap);
+        self.transforms.remove(&tilemap);
         self.value.remove(&tilemap)
     }
 
@@ -12,6 +12,7 @@ use crate::{math::CameraAabb2d, tilemap::map::TilemapAabbs};
 use super::{
     chunk::RenderChunkStorage,
     extract::{ExtractedTilemap, ExtractedView},
+    lod::ExtractedTilemapLod,
 };
 
 #[derive(Component)]
@@ -50,17 +51,40 @@ pub fn cull_tilemaps(
 }
 
 pub fn cull_chunks(
-    tilemaps: Query<&ExtractedTilemap>,
+    tilemaps: Query<(&ExtractedTilemap, Option<&ExtractedTilemapLod>)>,
     mut render_chunk_storage: ResMut<RenderChunkStorage>,
     cameras: Query<&ExtractedView>,
     culling: Res<FrustumCulling>,
 ) {
+    tilemaps.for_each(|(tilemap, lod)| {
+        let Some(chunks) = render_chunk_storage.get_chunks_mut(tilemap.id) else {
+            return;
+        };
+
+        // The thresholds are in tiles, so count them with the size they are rendered at.
+        let slot_size = tilemap.slot_size * tilemap.transform.scale.abs();
+        // Use the finest level any camera needs, so a zoomed out camera
+        // won't reduce the detail in the other views.
+        let lod_level = lod
+            .and_then(|lod| {
+                cameras
+                    .iter()
+                    .map(|cam_aabb| lod.0.get_level(cam_aabb.0, slot_size))
+                    .min()
+            })
+            .unwrap_or_default();
+
+        chunks.values_mut().for_each(|c| {
+            c.lod_level = lod_level;
+        });
+    });
+
     if !culling.0 {
         return;
     }
 
     cameras.for_each(|cam_aabb| {
-        tilemaps.for_each(|tilemap| {
+        tilemaps.for_each(|(tilemap, _)| {
             let Some(chunks) = render_chunk_storage.get_chunks_mut(tilemap.id) else {
                 return;
             };
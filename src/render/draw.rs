@@ -21,6 +21,10 @@ use super::{
     buffer::{DynamicOffsetComponent, TilemapUniform},
     chunk::RenderChunkStorage,
     extract::ExtractedTilemap,
+    fog::TilemapFogBuffers,
+    grid::{TilemapGridBuffers, TilemapGridUniform},
+    lighting::TilemapLightBuffers,
+    mask::TilemapMaskBuffers,
 };
 
 pub type DrawTilemap = (
@@ -29,14 +33,23 @@ pub type DrawTilemap = (
     SetTilemapUniformBufferBindGroup<1>,
     SetTilemapColorTextureBindGroup<2>,
     SetTilemapStorageBufferBindGroup<3>,
-    DrawTileMesh,
+    DrawTileMesh<4>,
 );
 
 pub type DrawTilemapPureColor = (
     SetPipeline,
     SetTilemapViewBindGroup<0>,
     SetTilemapUniformBufferBindGroup<1>,
-    DrawTileMesh,
+    DrawTileMesh<2>,
+);
+
+pub type DrawBakedChunks = (SetPipeline, SetTilemapViewBindGroup<0>, DrawBakedChunk<1>);
+
+pub type DrawTilemapGrid = (
+    SetPipeline,
+    SetTilemapViewBindGroup<0>,
+    SetTilemapUniformBufferBindGroup<1>,
+    DrawGrid<2>,
 );
 
 pub struct SetPipeline;
@@ -178,9 +191,17 @@ impl<const I: usize> RenderCommand<Transparent2d> for SetTilemapColorTextureBind
     }
 }
 
-pub struct DrawTileMesh;
-impl RenderCommand<Transparent2d> for DrawTileMesh {
-    type Param = SRes<RenderChunkStorage>;
+/// `I` is the index of the first per chunk bind group.
+/// The layers, the mask, the fog and the lighting bind groups are placed from it in order,
+/// if the tilemap has them.
+pub struct DrawTileMesh<const I: usize>;
+impl<const I: usize> RenderCommand<Transparent2d> for DrawTileMesh<I> {
+    type Param = (
+        SRes<RenderChunkStorage>,
+        SRes<TilemapMaskBuffers>,
+        SRes<TilemapFogBuffers>,
+        SRes<TilemapLightBuffers>,
+    );
 
     type ViewWorldQuery = ();
 
@@ -191,15 +212,57 @@ impl RenderCommand<Transparent2d> for DrawTileMesh {
         _item: &Transparent2d,
         _view: ROQueryItem<'w, Self::ViewWorldQuery>,
         tilemap: ROQueryItem<'w, Self::ItemWorldQuery>,
-        render_chunks: SystemParamItem<'w, '_, Self::Param>,
+        (render_chunks, mask_buffers, fog_buffers, light_buffers): SystemParamItem<
+            'w,
+            '_,
+            Self::Param,
+        >,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
+        let mask_buffers = mask_buffers.into_inner();
+        let fog_buffers = fog_buffers.into_inner();
+        let light_buffers = light_buffers.into_inner();
+        let mask_group = I + tilemap.texture.is_some() as usize;
+        let fog_group = mask_group + tilemap.mask_color.is_some() as usize;
+        let lighting_group = fog_group + tilemap.fog_colors.is_some() as usize;
+
         if let Some(chunks) = render_chunks.into_inner().get_chunks(tilemap.id) {
-            for chunk in chunks.values() {
-                if !chunk.visible {
+            for (chunk_index, chunk) in chunks.iter() {
+                if !chunk.visible || chunk.quad().is_some() {
                     continue;
                 }
 
+                if tilemap.texture.is_some() {
+                    let Some(bind_group) = &chunk.gpu_layers else {
+                        continue;
+                    };
+                    pass.set_bind_group(I, bind_group, &[]);
+                }
+
+                if tilemap.mask_color.is_some() {
+                    let Some(bind_group) = mask_buffers.get_bind_group(tilemap.id, *chunk_index)
+                    else {
+                        continue;
+                    };
+                    pass.set_bind_group(mask_group, bind_group, &[]);
+                }
+
+                if tilemap.fog_colors.is_some() {
+                    let Some(bind_group) = fog_buffers.get_bind_group(tilemap.id, *chunk_index)
+                    else {
+                        continue;
+                    };
+                    pass.set_bind_group(fog_group, bind_group, &[]);
+                }
+
+                if tilemap.ambient_light.is_some() {
+                    let Some(bind_group) = light_buffers.get_bind_group(tilemap.id, *chunk_index)
+                    else {
+                        continue;
+                    };
+                    pass.set_bind_group(lighting_group, bind_group, &[]);
+                }
+
                 if let Some(gpu_mesh) = &chunk.gpu_mesh {
                     pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
                     match &gpu_mesh.buffer_info {
@@ -222,3 +285,66 @@ impl RenderCommand<Transparent2d> for DrawTileMesh {
         RenderCommandResult::Success
     }
 }
+
+/// Draws the baked chunks and the lod summaries as quads. `I` is the index of the texture bind group.
+pub struct DrawBakedChunk<const I: usize>;
+impl<const I: usize> RenderCommand<Transparent2d> for DrawBakedChunk<I> {
+    type Param = SRes<RenderChunkStorage>;
+
+    type ViewWorldQuery = ();
+
+    type ItemWorldQuery = Read<ExtractedTilemap>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &Transparent2d,
+        _view: ROQueryItem<'w, Self::ViewWorldQuery>,
+        tilemap: ROQueryItem<'w, Self::ItemWorldQuery>,
+        render_chunks: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if let Some(chunks) = render_chunks.into_inner().get_chunks(tilemap.id) {
+            for chunk in chunks.values() {
+                if !chunk.visible {
+                    continue;
+                }
+
+                if let Some(baked) = chunk.quad() {
+                    pass.set_bind_group(I, &baked.bind_group, &[]);
+                    pass.set_vertex_buffer(0, baked.vertex_buffer.slice(..));
+                    pass.draw(0..6, 0..1);
+                }
+            }
+        }
+
+        RenderCommandResult::Success
+    }
+}
+
+/// Draws the grid as a single quad. `I` is the index of the grid bind group.
+pub struct DrawGrid<const I: usize>;
+impl<const I: usize> RenderCommand<Transparent2d> for DrawGrid<I> {
+    type Param = SRes<TilemapGridBuffers>;
+
+    type ViewWorldQuery = ();
+
+    type ItemWorldQuery = Read<DynamicOffsetComponent<TilemapGridUniform>>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &Transparent2d,
+        _view: ROQueryItem<'w, Self::ViewWorldQuery>,
+        uniform_data: ROQueryItem<'w, Self::ItemWorldQuery>,
+        grid_buffers: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if let Some(bind_group) = grid_buffers.into_inner().bind_group.as_ref() {
+            pass.set_bind_group(I, bind_group, &[uniform_data.index()]);
+            pass.draw(0..6, 0..1);
+            RenderCommandResult::Success
+        } else {
+            error!("Failed to get tilemap grid bind group!");
+            RenderCommandResult::Failure
+        }
+    }
+}
@@ -3,6 +3,7 @@ use bevy::{
     prelude::{Changed, Commands, Component, Entity, Query, Vec2, Vec4},
     render::Extract,
     time::Time,
+    transform::components::GlobalTransform,
     utils::EntityHashMap,
 };
 
@@ -10,15 +11,20 @@ use crate::{
     math::CameraAabb2d,
     tilemap::{
         despawn::{DespawnedTile, DespawnedTilemap},
+        fog::{FogColors, TilemapFog},
+        lighting::TilemapLighting,
         map::{
-            TilePivot, TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
-            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTransform, TilemapType,
+            TilePivot, TileRenderSize, TilemapAnimationPlayback, TilemapAnimations,
+            TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTransform, TilemapType,
         },
+        mask::TilemapMask,
         tile::Tile,
     },
 };
 
 use super::{
+    bake::TilemapBaking,
     chunk::{ChunkUnload, UnloadRenderChunk},
     culling::{FrustumCulling, InvisibleTilemap},
 };
@@ -31,12 +37,21 @@ pub struct ExtractedTilemap {
     pub slot_size: Vec2,
     pub ty: TilemapType,
     pub tile_pivot: Vec2,
-    pub layer_opacities: Vec4,
+    pub layer_opacities: TilemapLayerOpacities,
     pub transform: TilemapTransform,
     pub texture: Option<TilemapTexture>,
     pub animations: Option<TilemapAnimations>,
     pub chunk_size: u32,
     pub time: f32,
+    pub anim_time: f32,
+    /// The color multiplied with the masked tiles. `None` if the tilemap has no mask.
+    pub mask_color: Option<Vec4>,
+    pub mask_inverted: bool,
+    pub fog_colors: Option<FogColors>,
+    /// The linear ambient light. `None` if the tilemap has no lighting.
+    pub ambient_light: Option<Vec4>,
+    /// How many frames a chunk must be static before it's baked. `None` if baking is disabled.
+    pub bake_frames: Option<u32>,
 }
 
 pub type ExtractedTile = Tile;
@@ -55,10 +70,17 @@ pub fn extract_tilemaps(
                 &TilemapType,
                 &TilePivot,
                 &TilemapLayerOpacities,
-                &TilemapTransform,
+                &GlobalTransform,
                 &TilemapStorage,
                 Option<&TilemapTexture>,
-                Option<&TilemapAnimations>,
+                (
+                    Option<&TilemapAnimations>,
+                    Option<&TilemapAnimationPlayback>,
+                ),
+                Option<&TilemapMask>,
+                Option<&TilemapFog>,
+                Option<&TilemapLighting>,
+                Option<&TilemapBaking>,
             ),
             Without<InvisibleTilemap>,
         >,
@@ -80,7 +102,11 @@ pub fn extract_tilemaps(
                     transform,
                     storage,
                     texture,
-                    animations,
+                    (animations, playback),
+                    mask,
+                    fog,
+                    lighting,
+                    baking,
                 )| {
                     (
                         entity,
@@ -91,12 +117,19 @@ pub fn extract_tilemaps(
                             slot_size: slot_size.0,
                             ty: *ty,
                             tile_pivot: tile_pivot.0,
-                            layer_opacities: layer_opacities.0,
-                            transform: *transform,
+                            layer_opacities: layer_opacities.clone(),
+                            transform: TilemapTransform::from_global(transform),
                             texture: texture.cloned(),
                             animations: animations.cloned(),
                             chunk_size: storage.storage.chunk_size,
                             time: time.elapsed_seconds(),
+                            anim_time: playback.map_or(time.elapsed_seconds(), |p| p.time),
+                            mask_color: mask.map(|m| m.color()),
+                            mask_inverted: mask.is_some_and(|m| m.inverted),
+                            fog_colors: fog.map(|f| f.colors),
+                            ambient_light: lighting
+                                .map(|l| Vec4::from_array(l.ambient.as_linear_rgba_f32())),
+                            bake_frames: baking.map(|b| b.frames),
                         },
                     )
                 },
@@ -131,7 +164,8 @@ pub fn extract_tiles(
 
 pub fn extract_view(
     mut commands: Commands,
-    cameras: Extract<Query<(Entity, &CameraAabb2d), Changed<CameraAabb2d>>>,
+    // Extracted every frame, as the chunks need to be culled again if the tilemaps are moved.
+    cameras: Extract<Query<(Entity, &CameraAabb2d)>>,
 ) {
     commands.insert_or_spawn_batch(
         cameras
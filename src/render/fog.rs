@@ -0,0 +1,64 @@
+use bevy::{ecs::entity::Entity, math::IVec2, render::render_resource::BindGroupLayout};
+
+use crate::tilemap::fog::TilemapFog;
+
+use super::{
+    buffer::{ExtractedPerChunkData, PerChunkData, PerChunkStorageBuffers},
+    chunk::RenderChunkStorage,
+    extract::ExtractedTilemap,
+    pipeline::EntiTilesPipeline,
+};
+
+pub type ExtractedTilemapFog = ExtractedPerChunkData<TilemapFog>;
+
+/// The fog states are uploaded per chunk,
+/// and bound to `@group(#{FOG_BIND_GROUP})` when drawing that chunk.
+pub type TilemapFogBuffers = PerChunkStorageBuffers<u32>;
+
+impl PerChunkData for TilemapFog {
+    type Item = u32;
+
+    const NAME: &'static str = "fog";
+    // The chunks that have never been explored.
+    const DEFAULT: u32 = 0;
+
+    #[inline]
+    fn chunk_size(&self) -> u32 {
+        self.chunk_size()
+    }
+
+    fn changed_chunks(&self, added: bool) -> Vec<IVec2> {
+        if added {
+            self.states.chunks.keys().copied().collect()
+        } else {
+            self.dirty_chunks.iter().copied().collect()
+        }
+    }
+
+    #[inline]
+    fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<u32> {
+        self.get_chunk_data(chunk_index)
+    }
+
+    #[inline]
+    fn is_enabled(tilemap: &ExtractedTilemap) -> bool {
+        tilemap.fog_colors.is_some()
+    }
+
+    #[inline]
+    fn layout(pipeline: &EntiTilesPipeline) -> &BindGroupLayout {
+        &pipeline.fog_layout
+    }
+
+    /// The lod summaries are coloured by the fog too.
+    fn invalidate(
+        render_chunks: &mut RenderChunkStorage,
+        tilemap: Entity,
+        chunk_index: Option<IVec2>,
+    ) {
+        if let Some(index) = chunk_index {
+            render_chunks.invalidate_bake(tilemap, index);
+        }
+        render_chunks.invalidate_lod_summaries(tilemap, chunk_index);
+    }
+}
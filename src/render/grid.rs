@@ -0,0 +1,302 @@
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::FromWorld,
+    },
+    math::{Vec2, Vec4},
+    reflect::Reflect,
+    render::{
+        color::Color,
+        render_phase::{DrawFunctions, RenderPhase},
+        render_resource::{
+            BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+            BindGroupLayoutEntry, BindingType, BlendState, BufferBindingType, ColorTargetState,
+            ColorWrites, DynamicUniformBuffer, FragmentState, FrontFace, MultisampleState,
+            PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology,
+            RenderPipelineDescriptor, ShaderDefVal, ShaderStages, ShaderType,
+            SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat, VertexState,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::Msaa,
+        Extract,
+    },
+    utils::FloatOrd,
+};
+
+use crate::tilemap::map::{TilemapStagger, TilemapType};
+
+use super::{
+    binding::TilemapBindGroupLayouts, buffer::DynamicOffsetComponent, chunk::RenderChunkStorage,
+    draw::DrawTilemapGrid, extract::ExtractedTilemap, GRID_SHADER,
+};
+
+/// Draws the outlines of the slots over the tilemap. Insert this component to the tilemap entity.
+///
+/// The grid covers the chunks of the tilemap that are on the screen, and is drawn above the tiles.
+/// Set `visible` to `false` to hide it without removing the component.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapGrid {
+    pub visible: bool,
+    pub color: Color,
+    /// The width of the lines in pixels on the screen.
+    pub thickness: f32,
+    /// The color of the indices drawn at the center of the slots. `None` to hide them.
+    ///
+    /// The indices are hidden when the slots are too small on the screen to read them.
+    pub coordinates: Option<Color>,
+}
+
+impl Default for TilemapGrid {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            color: Color::rgba(1., 1., 1., 0.5),
+            thickness: 1.,
+            coordinates: None,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ExtractedTilemapGrid(pub TilemapGrid);
+
+#[derive(ShaderType, Clone, Copy)]
+pub struct TilemapGridUniform {
+    pub color: Vec4,
+    pub coordinates_color: Vec4,
+    /// The area covered by the chunks in world space.
+    pub area_min: Vec2,
+    pub area_max: Vec2,
+    pub thickness: f32,
+    pub show_coordinates: u32,
+}
+
+#[derive(Resource, Default)]
+pub struct TilemapGridBuffers {
+    pub uniforms: DynamicUniformBuffer<TilemapGridUniform>,
+    pub bind_group: Option<BindGroup>,
+}
+
+#[derive(Resource, Clone)]
+pub struct TilemapGridPipeline {
+    pub view_layout: BindGroupLayout,
+    pub uniform_buffers_layout: BindGroupLayout,
+    pub grid_layout: BindGroupLayout,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub struct TilemapGridPipelineKey {
+    pub msaa: u32,
+    pub map_type: TilemapType,
+}
+
+impl FromWorld for TilemapGridPipeline {
+    fn from_world(world: &mut bevy::prelude::World) -> Self {
+        let layouts = world.resource::<TilemapBindGroupLayouts>();
+        let view_layout = layouts.view_layout.clone();
+        let uniform_buffers_layout = layouts.tilemap_uniforms_layout.clone();
+        let grid_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("tilemap_grid_layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: Some(TilemapGridUniform::min_size()),
+                        },
+                        count: None,
+                    }],
+                });
+
+        Self {
+            view_layout,
+            uniform_buffers_layout,
+            grid_layout,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for TilemapGridPipeline {
+    type Key = TilemapGridPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        // The grid only reads the tilemap uniform from the common bindings.
+        let mut shader_defs: Vec<ShaderDefVal> = vec!["PURE_COLOR".into()];
+        shader_defs.push(
+            {
+                match key.map_type.unstaggered() {
+                    TilemapType::Square => "SQUARE",
+                    TilemapType::Isometric => "ISOMETRIC",
+                    _ => "HEXAGONAL",
+                }
+            }
+            .into(),
+        );
+        match key.map_type {
+            TilemapType::StaggeredIsometric(stagger)
+            | TilemapType::StaggeredHexagonal(_, stagger) => {
+                shader_defs.push("STAGGERED".into());
+                if stagger == TilemapStagger::Even {
+                    shader_defs.push("STAGGER_EVEN".into());
+                }
+            }
+            _ => {}
+        }
+        #[cfg(feature = "atlas")]
+        shader_defs.push("ATLAS".into());
+
+        RenderPipelineDescriptor {
+            label: Some("tilemap_grid_pipeline".into()),
+            layout: vec![
+                self.view_layout.clone(),
+                self.uniform_buffers_layout.clone(),
+                self.grid_layout.clone(),
+            ],
+            push_constant_ranges: vec![],
+            vertex: VertexState {
+                shader: GRID_SHADER,
+                shader_defs: shader_defs.clone(),
+                entry_point: "grid_vertex".into(),
+                // The quad is generated from the vertex index.
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: GRID_SHADER,
+                shader_defs,
+                entry_point: "grid_fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.msaa,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+pub fn extract_grids(mut commands: Commands, grids_query: Extract<Query<(Entity, &TilemapGrid)>>) {
+    commands.insert_or_spawn_batch(
+        grids_query
+            .iter()
+            .filter(|(_, grid)| grid.visible)
+            .map(|(entity, grid)| (entity, ExtractedTilemapGrid(*grid)))
+            .collect::<Vec<_>>(),
+    );
+}
+
+pub fn prepare_grids(
+    mut commands: Commands,
+    grids_query: Query<(Entity, &ExtractedTilemapGrid), With<ExtractedTilemap>>,
+    render_chunks: Res<RenderChunkStorage>,
+    mut grid_buffers: ResMut<TilemapGridBuffers>,
+    grid_pipeline: Res<TilemapGridPipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    grid_buffers.uniforms.clear();
+
+    grids_query.for_each(|(entity, grid)| {
+        // Empty if the tilemap has no chunks, and nothing will be drawn.
+        let area = render_chunks
+            .get_chunks(entity)
+            .and_then(|chunks| {
+                chunks
+                    .values()
+                    .map(|chunk| chunk.aabb)
+                    .reduce(|mut area, aabb| {
+                        area.expand(aabb);
+                        area
+                    })
+            })
+            .unwrap_or_default();
+
+        let grid = grid.0;
+        let offset = grid_buffers.uniforms.push(TilemapGridUniform {
+            color: Vec4::from_array(grid.color.as_linear_rgba_f32()),
+            coordinates_color: Vec4::from_array(
+                grid.coordinates.unwrap_or_default().as_linear_rgba_f32(),
+            ),
+            area_min: area.min,
+            area_max: area.max,
+            thickness: grid.thickness,
+            show_coordinates: grid.coordinates.is_some() as u32,
+        });
+        commands
+            .entity(entity)
+            .insert(DynamicOffsetComponent::<TilemapGridUniform>::new(offset));
+    });
+
+    grid_buffers
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+
+    grid_buffers.bind_group = grid_buffers.uniforms.binding().map(|resource| {
+        render_device.create_bind_group(
+            Some("tilemap_grid_bind_group"),
+            &grid_pipeline.grid_layout,
+            &[BindGroupEntry {
+                binding: 0,
+                resource,
+            }],
+        )
+    });
+}
+
+pub fn queue_grids(
+    mut views_query: Query<&mut RenderPhase<Transparent2d>>,
+    grids_query: Query<(Entity, &ExtractedTilemap), With<ExtractedTilemapGrid>>,
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    mut sp_grid_pipeline: ResMut<SpecializedRenderPipelines<TilemapGridPipeline>>,
+    grid_pipeline: Res<TilemapGridPipeline>,
+    msaa: Res<Msaa>,
+) {
+    let draw_function = draw_functions.read().get_id::<DrawTilemapGrid>().unwrap();
+
+    views_query.for_each_mut(|mut transparent_phase| {
+        grids_query.iter().for_each(|(entity, tilemap)| {
+            let pipeline = sp_grid_pipeline.specialize(
+                &pipeline_cache,
+                &grid_pipeline,
+                TilemapGridPipelineKey {
+                    msaa: msaa.samples(),
+                    map_type: tilemap.ty,
+                },
+            );
+
+            transparent_phase.add(Transparent2d {
+                // Above the tiles of this tilemap, but below the tilemaps on the higher layers.
+                sort_key: FloatOrd(tilemap.transform.z_index as f32 + 0.5),
+                entity,
+                pipeline,
+                draw_function,
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        });
+    });
+}
@@ -0,0 +1,54 @@
+use bevy::{
+    math::{IVec2, Vec4},
+    render::render_resource::BindGroupLayout,
+};
+
+use crate::tilemap::lighting::TilemapLighting;
+
+use super::{
+    buffer::{ExtractedPerChunkData, PerChunkData, PerChunkStorageBuffers},
+    extract::ExtractedTilemap,
+    pipeline::EntiTilesPipeline,
+};
+
+pub type ExtractedTilemapLighting = ExtractedPerChunkData<TilemapLighting>;
+
+/// The lightmap is uploaded per chunk,
+/// and bound to `@group(#{LIGHTING_BIND_GROUP})` when drawing that chunk.
+pub type TilemapLightBuffers = PerChunkStorageBuffers<Vec4>;
+
+impl PerChunkData for TilemapLighting {
+    type Item = Vec4;
+
+    const NAME: &'static str = "lighting";
+    // The chunks that no light reaches.
+    const DEFAULT: Vec4 = Vec4::ZERO;
+
+    #[inline]
+    fn chunk_size(&self) -> u32 {
+        self.chunk_size()
+    }
+
+    fn changed_chunks(&self, added: bool) -> Vec<IVec2> {
+        if added {
+            self.lightmap.keys().copied().collect()
+        } else {
+            self.dirty_chunks.iter().copied().collect()
+        }
+    }
+
+    #[inline]
+    fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<Vec4> {
+        self.get_chunk_data(chunk_index)
+    }
+
+    #[inline]
+    fn is_enabled(tilemap: &ExtractedTilemap) -> bool {
+        tilemap.ambient_light.is_some()
+    }
+
+    #[inline]
+    fn layout(pipeline: &EntiTilesPipeline) -> &BindGroupLayout {
+        &pipeline.lighting_layout
+    }
+}
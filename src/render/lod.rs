@@ -0,0 +1,353 @@
+use bevy::{
+    asset::Assets,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Without,
+        system::{Commands, Query, Res, ResMut},
+    },
+    log::error,
+    math::{IVec2, UVec2, Vec2, Vec4, Vec4Swizzles},
+    reflect::Reflect,
+    render::{
+        color::Color,
+        render_resource::{
+            Extent3d, FilterMode, SamplerDescriptor, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsages, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{BevyDefault, Image},
+        Extract,
+    },
+};
+
+use crate::{math::aabb::Aabb2d, tilemap::map::TilemapTexture};
+
+use super::{
+    bake::{create_quad, mesh_origin, BakedChunk, BakedChunkPipeline},
+    buffer::TilemapUniform,
+    chunk::{MeshTileData, RenderChunkStorage},
+    extract::ExtractedTilemap,
+    fog::TilemapFogBuffers,
+    mask::TilemapMaskBuffers,
+    material::ExtractedMaterialTilemap,
+};
+
+/// Renders the chunks with simplified colour summaries when the camera is zoomed out.
+/// Insert this component to the tilemap entity.
+///
+/// The thresholds are the number of tile slots that fit along the longer side of the camera.
+/// Once exceeded, each chunk is drawn as a single quad, coloured by one texel per tile or by one
+/// colour for the whole chunk. The summaries only take the textures, tile colours, layer opacities,
+/// masks and fog into account.
+///
+/// Tilemaps with custom materials are never simplified.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapLod {
+    pub tile_colors_threshold: f32,
+    pub chunk_color_threshold: f32,
+    /// The average colour of each tile in the texture.
+    /// Calculated once the texture is loaded.
+    pub(crate) texture_colors: Vec<Vec4>,
+}
+
+impl Default for TilemapLod {
+    fn default() -> Self {
+        Self::new(256., 2048.)
+    }
+}
+
+impl TilemapLod {
+    pub fn new(tile_colors_threshold: f32, chunk_color_threshold: f32) -> Self {
+        Self {
+            tile_colors_threshold,
+            chunk_color_threshold,
+            texture_colors: Vec::new(),
+        }
+    }
+
+    /// Get the level of detail to use when the camera sees `view`.
+    pub fn get_level(&self, view: Aabb2d, slot_size: Vec2) -> LodLevel {
+        let tiles = (view.size() / slot_size).max_element();
+
+        if tiles > self.chunk_color_threshold {
+            LodLevel::ChunkColor
+        } else if tiles > self.tile_colors_threshold {
+            LodLevel::TileColors
+        } else {
+            LodLevel::Full
+        }
+    }
+}
+
+/// Ordered from the finest to the coarsest.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Reflect)]
+pub enum LodLevel {
+    /// Draw the tile meshes.
+    #[default]
+    Full,
+    /// Draw one texel per tile.
+    TileColors,
+    /// Draw one colour for the whole chunk.
+    ChunkColor,
+}
+
+pub fn lod_texture_colors_updater(
+    mut lods_query: Query<(&mut TilemapLod, &TilemapTexture)>,
+    images: Res<Assets<Image>>,
+) {
+    lods_query.for_each_mut(|(mut lod, texture)| {
+        if !lod.texture_colors.is_empty() {
+            return;
+        }
+        let Some(image) = images.get(texture.handle()) else {
+            return;
+        };
+
+        lod.texture_colors = average_tile_colors(image, texture);
+    });
+}
+
+fn average_tile_colors(image: &Image, texture: &TilemapTexture) -> Vec<Vec4> {
+    let desc = texture.desc();
+    let tiles = desc.size / desc.tile_size;
+    let tile_count = (tiles.x * tiles.y) as usize;
+
+    let is_srgb = match image.texture_descriptor.format {
+        TextureFormat::Rgba8UnormSrgb => true,
+        TextureFormat::Rgba8Unorm => false,
+        format => {
+            error!(
+                "Unsupported texture format {:?} for tilemap lod! Using white instead.",
+                format
+            );
+            return vec![Vec4::ONE; tile_count];
+        }
+    };
+    let width = image.texture_descriptor.size.width as usize;
+
+    (0..tile_count)
+        .map(|index| {
+            let index = index as u32;
+            let tile = UVec2::new(index % tiles.x, index / tiles.x) * desc.tile_size;
+            let mut sum = Vec4::ZERO;
+
+            for y in tile.y..tile.y + desc.tile_size.y {
+                for x in tile.x..tile.x + desc.tile_size.x {
+                    let i = (y as usize * width + x as usize) * 4;
+                    let Some(&[r, g, b, a]) = image.data.get(i..i + 4) else {
+                        continue;
+                    };
+                    let color = if is_srgb {
+                        Vec4::from_array(Color::rgba_u8(r, g, b, a).as_linear_rgba_f32())
+                    } else {
+                        Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.
+                    };
+                    // Weight by the alpha so transparent pixels don't darken the colour.
+                    sum += (color.xyz() * color.w).extend(color.w);
+                }
+            }
+
+            if sum.w == 0. {
+                Vec4::ZERO
+            } else {
+                let pixels = (desc.tile_size.x * desc.tile_size.y) as f32;
+                (sum.xyz() / sum.w).extend(sum.w / pixels)
+            }
+        })
+        .collect()
+}
+
+#[derive(Component, Debug)]
+pub struct ExtractedTilemapLod(pub TilemapLod);
+
+pub fn extract_lod(mut commands: Commands, lods_query: Extract<Query<(Entity, &TilemapLod)>>) {
+    commands.insert_or_spawn_batch(
+        lods_query
+            .iter()
+            .map(|(entity, lod)| (entity, ExtractedTilemapLod(lod.clone())))
+            .collect::<Vec<_>>(),
+    );
+}
+
+/// Create the colour summaries for the chunks that are not drawn in full detail.
+pub fn prepare_lod_summaries(
+    tilemaps_query: Query<
+        (&ExtractedTilemap, &ExtractedTilemapLod),
+        Without<ExtractedMaterialTilemap>,
+    >,
+    mut render_chunks: ResMut<RenderChunkStorage>,
+    mask_buffers: Res<TilemapMaskBuffers>,
+    fog_buffers: Res<TilemapFogBuffers>,
+    baked_chunk_pipeline: Res<BakedChunkPipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let sampler = render_device.create_sampler(&SamplerDescriptor {
+        label: Some("lod_summary_sampler"),
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    tilemaps_query.for_each(|(tilemap, lod)| {
+        if tilemap.texture.is_some() && lod.0.texture_colors.is_empty() {
+            return;
+        }
+
+        let uniform = TilemapUniform {
+            time: 0.,
+            anim_time: 0.,
+            ..tilemap.into()
+        };
+        let Some(chunks) = render_chunks.get_chunks_mut(tilemap.id) else {
+            return;
+        };
+
+        for (chunk_index, chunk) in chunks.iter_mut() {
+            if chunk
+                .lod_summaries
+                .as_ref()
+                .is_some_and(|s| s[0].uniform != uniform)
+            {
+                chunk.lod_summaries = None;
+            }
+
+            if chunk.lod_summaries.is_some()
+                || chunk.lod_level == LodLevel::Full
+                || chunk.dirty_mesh
+                || !chunk.visible
+            {
+                continue;
+            }
+
+            let size = chunk.size as i32;
+            let chunk_origin = *chunk_index * size;
+            let mask_states = tilemap
+                .mask_color
+                .and_then(|_| mask_buffers.get_data(tilemap.id, *chunk_index));
+            let fog_states = tilemap
+                .fog_colors
+                .and_then(|_| fog_buffers.get_data(tilemap.id, *chunk_index));
+            let mut texels = vec![Vec4::ZERO; (size * size) as usize];
+            chunk.tiles.iter().flatten().for_each(|tile| {
+                let in_chunk = tile.index - chunk_origin;
+                let i = (in_chunk.y * size + in_chunk.x) as usize;
+                let mask_state = mask_states.and_then(|m| m.get(i).copied());
+                let fog_state = fog_states.and_then(|f| f.get(i).copied());
+                texels[i] = premultiplied_tile_color(tile, tilemap, &lod.0, mask_state, fog_state);
+            });
+            let average = texels.iter().sum::<Vec4>() / texels.len() as f32;
+
+            // Map the chunk local coordinates into world space, texel `(x, y)` covers tile `(x, y)`.
+            let corners = [IVec2::ZERO, IVec2::X, IVec2::ONE, IVec2::Y].map(|corner| {
+                let index = (chunk_origin + corner * size).as_vec2() - Vec2::splat(0.5);
+                tilemap.transform.transform_point(
+                    mesh_origin(index, tilemap)
+                        + (Vec2::splat(0.5) - tilemap.tile_pivot) * tilemap.tile_render_size,
+                )
+            });
+            let vertex_buffer = create_quad(&render_device, corners);
+
+            let create_summary = |texels: &[Vec4], size: u32| {
+                let data = texels
+                    .iter()
+                    .flat_map(|c| Color::rgba_linear(c.x, c.y, c.z, c.w).as_rgba_u8())
+                    .collect::<Vec<_>>();
+                let texture = render_device.create_texture_with_data(
+                    &render_queue,
+                    &TextureDescriptor {
+                        label: Some("lod_summary_texture"),
+                        size: Extent3d {
+                            width: size,
+                            height: size,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::bevy_default(),
+                        usage: TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    },
+                    &data,
+                );
+                let bind_group = baked_chunk_pipeline.create_bind_group(
+                    &render_device,
+                    &texture.create_view(&TextureViewDescriptor::default()),
+                    &sampler,
+                );
+
+                BakedChunk {
+                    texture,
+                    bind_group,
+                    vertex_buffer: vertex_buffer.clone(),
+                    uniform,
+                }
+            };
+
+            chunk.lod_summaries = Some([
+                create_summary(&texels, chunk.size),
+                create_summary(&[average], 1),
+            ]);
+        }
+    });
+}
+
+/// The same as `tilemap_color` in the shaders, but uses the average colours of the textures.
+fn premultiplied_tile_color(
+    tile: &MeshTileData,
+    tilemap: &ExtractedTilemap,
+    lod: &TilemapLod,
+    mask_state: Option<u32>,
+    fog_state: Option<u32>,
+) -> Vec4 {
+    let tint = tile.color.xyz().powf(2.2).extend(tile.color.w);
+
+    let mut color = if tilemap.texture.is_none() {
+        tint
+    } else {
+        let mut color = Vec4::ZERO;
+
+        for (i, layer) in tile.layers.iter().enumerate() {
+            let texture_index = match layer.animation {
+                // Use the first frame of the animation.
+                Some(anim) => tilemap
+                    .animations
+                    .as_ref()
+                    .and_then(|a| a.0.get(anim.start as usize).copied())
+                    .unwrap_or(-1),
+                None => layer.texture_index,
+            };
+            if let Some(tex_color) = texture_color(lod, texture_index) {
+                color = color.lerp(tex_color, tex_color.w * tilemap.layer_opacities.get(i));
+            }
+        }
+
+        color * tint
+    };
+
+    if let Some(mask_color) = tilemap.mask_color {
+        // The tiles in the chunks that are not uploaded yet are not marked.
+        if (mask_state.unwrap_or_default() != 0) != tilemap.mask_inverted {
+            color *= mask_color;
+        }
+    }
+
+    if let Some(colors) = tilemap.fog_colors {
+        match fog_state {
+            Some(0) => color *= colors.unexplored,
+            Some(1) => color *= colors.explored,
+            _ => {}
+        }
+    }
+
+    (color.xyz() * color.w).extend(color.w)
+}
+
+#[inline]
+fn texture_color(lod: &TilemapLod, texture_index: i32) -> Option<Vec4> {
+    usize::try_from(texture_index)
+        .ok()
+        .and_then(|i| lod.texture_colors.get(i).copied())
+}
@@ -0,0 +1,64 @@
+use bevy::{ecs::entity::Entity, math::IVec2, render::render_resource::BindGroupLayout};
+
+use crate::tilemap::mask::TilemapMask;
+
+use super::{
+    buffer::{ExtractedPerChunkData, PerChunkData, PerChunkStorageBuffers},
+    chunk::RenderChunkStorage,
+    extract::ExtractedTilemap,
+    pipeline::EntiTilesPipeline,
+};
+
+pub type ExtractedTilemapMask = ExtractedPerChunkData<TilemapMask>;
+
+/// The marked tiles are uploaded per chunk,
+/// and bound to `@group(#{MASK_BIND_GROUP})` when drawing that chunk.
+pub type TilemapMaskBuffers = PerChunkStorageBuffers<u32>;
+
+impl PerChunkData for TilemapMask {
+    type Item = u32;
+
+    const NAME: &'static str = "mask";
+    // The chunks that have no marked tiles.
+    const DEFAULT: u32 = 0;
+
+    #[inline]
+    fn chunk_size(&self) -> u32 {
+        self.chunk_size()
+    }
+
+    fn changed_chunks(&self, added: bool) -> Vec<IVec2> {
+        if added {
+            self.chunks().into_iter().collect()
+        } else {
+            self.dirty_chunks.iter().copied().collect()
+        }
+    }
+
+    #[inline]
+    fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<u32> {
+        self.get_chunk_data(chunk_index)
+    }
+
+    #[inline]
+    fn is_enabled(tilemap: &ExtractedTilemap) -> bool {
+        tilemap.mask_color.is_some()
+    }
+
+    #[inline]
+    fn layout(pipeline: &EntiTilesPipeline) -> &BindGroupLayout {
+        &pipeline.mask_layout
+    }
+
+    /// The lod summaries are masked too.
+    fn invalidate(
+        render_chunks: &mut RenderChunkStorage,
+        tilemap: Entity,
+        chunk_index: Option<IVec2>,
+    ) {
+        if let Some(index) = chunk_index {
+            render_chunks.invalidate_bake(tilemap, index);
+        }
+        render_chunks.invalidate_lod_summaries(tilemap, chunk_index);
+    }
+}
@@ -0,0 +1,392 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin},
+    asset::{Asset, AssetApp, AssetEvent, AssetId, AssetServer, Assets, Handle},
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::{ROQueryItem, With, Without},
+        schedule::IntoSystemConfigs,
+        system::{
+            lifetimeless::{Read, SRes},
+            Commands, Local, Query, Res, ResMut, Resource, SystemParamItem,
+        },
+        world::{FromWorld, World},
+    },
+    log::error,
+    render::{
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, RenderCommand, RenderCommandResult, RenderPhase,
+            TrackedRenderPass,
+        },
+        render_resource::{
+            AsBindGroup, AsBindGroupError, BindGroup, BindGroupLayout, PipelineCache,
+            RenderPipelineDescriptor, Shader, ShaderRef, SpecializedRenderPipeline,
+            SpecializedRenderPipelines,
+        },
+        renderer::RenderDevice,
+        texture::{FallbackImage, Image},
+        view::Msaa,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    utils::{FloatOrd, HashMap, HashSet},
+};
+
+use crate::tilemap::map::TilemapStorage;
+
+use super::{
+    binding::{TilemapBindGroupLayouts, TilemapBindGroups},
+    culling::InvisibleTilemap,
+    draw::{
+        DrawTileMesh, SetPipeline, SetTilemapColorTextureBindGroup,
+        SetTilemapStorageBufferBindGroup, SetTilemapUniformBufferBindGroup,
+        SetTilemapViewBindGroup,
+    },
+    extract::ExtractedTilemap,
+    pipeline::{EntiTilesPipeline, EntiTilesPipelineKey},
+    queue,
+    texture::TilemapTexturesStorage,
+};
+
+/// Materials allow you to render tilemaps with your own shaders.
+///
+/// Add `Handle<M>` to the tilemap entity to use the material, and don't forget to add
+/// `TilemapMaterialPlugin::<M>` to your app.
+///
+/// The material bind group is bound to `@group(4)`, or `@group(2)` if the tilemap
+/// has no texture. Use `#ifdef PURE_COLOR` if your material is used in both cases.
+/// The fragment shader receives `bevy_entitiles::common::VertexOutput`,
+/// and you can call `bevy_entitiles::common::tilemap_color` to get the color
+/// that the tilemap would have been rendered with.
+pub trait TilemapMaterial: Asset + AsBindGroup + Clone + Sized {
+    /// Returns the fragment shader of this material.
+    /// The entry point should be `tilemap_fragment`.
+    ///
+    /// `ShaderRef::Default` means the built-in fragment shader will be used.
+    fn fragment_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    /// Customize the pipeline descriptor of this material.
+    #[allow(unused_variables)]
+    #[inline]
+    fn specialize(descriptor: &mut RenderPipelineDescriptor, key: &EntiTilesPipelineKey) {}
+}
+
+pub struct TilemapMaterialPlugin<M: TilemapMaterial>(PhantomData<M>);
+
+impl<M: TilemapMaterial> Default for TilemapMaterialPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: TilemapMaterial> Plugin for TilemapMaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<M>();
+
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+
+        render_app
+            .add_systems(
+                ExtractSchedule,
+                (extract_material_tilemaps::<M>, extract_materials::<M>),
+            )
+            .add_systems(
+                Render,
+                prepare_materials::<M>.in_set(RenderSet::PrepareBindGroups),
+            )
+            .add_systems(
+                Render,
+                queue_material_tilemaps::<M>
+                    .in_set(RenderSet::Queue)
+                    .after(queue::queue),
+            );
+
+        render_app
+            .init_resource::<ExtractedTilemapMaterials<M>>()
+            .init_resource::<TilemapMaterialBindGroups<M>>();
+
+        render_app
+            .add_render_command::<Transparent2d, DrawTilemapWithMaterial<M>>()
+            .add_render_command::<Transparent2d, DrawTilemapPureColorWithMaterial<M>>();
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+
+        render_app
+            .init_resource::<EntiTilesMaterialPipeline<M>>()
+            .init_resource::<SpecializedRenderPipelines<EntiTilesMaterialPipeline<M>>>();
+    }
+}
+
+/// Tilemaps with this component are rendered by `TilemapMaterialPlugin`
+/// instead of the default queue.
+#[derive(Component)]
+pub struct ExtractedMaterialTilemap;
+
+#[derive(Resource)]
+pub struct ExtractedTilemapMaterials<M: TilemapMaterial> {
+    pub extracted: Vec<(AssetId<M>, M)>,
+    pub removed: Vec<AssetId<M>>,
+}
+
+impl<M: TilemapMaterial> Default for ExtractedTilemapMaterials<M> {
+    fn default() -> Self {
+        Self {
+            extracted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct TilemapMaterialBindGroups<M: TilemapMaterial>(pub HashMap<AssetId<M>, BindGroup>);
+
+impl<M: TilemapMaterial> Default for TilemapMaterialBindGroups<M> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+#[derive(Resource)]
+pub struct EntiTilesMaterialPipeline<M: TilemapMaterial> {
+    pub entitiles_pipeline: EntiTilesPipeline,
+    pub material_layout: BindGroupLayout,
+    pub fragment_shader: Option<Handle<Shader>>,
+    marker: PhantomData<M>,
+}
+
+impl<M: TilemapMaterial> FromWorld for EntiTilesMaterialPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        world.init_resource::<TilemapBindGroupLayouts>();
+        let entitiles_pipeline = EntiTilesPipeline::from_world(world);
+        let asset_server = world.resource::<AssetServer>();
+        let render_device = world.resource::<RenderDevice>();
+
+        Self {
+            entitiles_pipeline,
+            material_layout: M::bind_group_layout(render_device),
+            fragment_shader: match M::fragment_shader() {
+                ShaderRef::Default => None,
+                ShaderRef::Handle(handle) => Some(handle),
+                ShaderRef::Path(path) => Some(asset_server.load(path)),
+            },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesMaterialPipeline<M> {
+    type Key = EntiTilesPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = self
+            .entitiles_pipeline
+            .specialize_with_material(key.clone(), Some(&self.material_layout));
+
+        descriptor.label = Some("tilemap_material_pipeline".into());
+        if let (Some(shader), Some(fragment)) =
+            (self.fragment_shader.as_ref(), descriptor.fragment.as_mut())
+        {
+            fragment.shader = shader.clone();
+        }
+
+        M::specialize(&mut descriptor, &key);
+        descriptor
+    }
+}
+
+pub fn extract_material_tilemaps<M: TilemapMaterial>(
+    mut commands: Commands,
+    tilemaps_query: Extract<
+        Query<(Entity, &Handle<M>), (With<TilemapStorage>, Without<InvisibleTilemap>)>,
+    >,
+) {
+    commands.insert_or_spawn_batch(
+        tilemaps_query
+            .iter()
+            .map(|(entity, material)| (entity, (material.clone_weak(), ExtractedMaterialTilemap)))
+            .collect::<Vec<_>>(),
+    );
+}
+
+pub fn extract_materials<M: TilemapMaterial>(
+    mut commands: Commands,
+    mut events: Extract<EventReader<AssetEvent<M>>>,
+    assets: Extract<Res<Assets<M>>>,
+) {
+    let mut changed = HashSet::new();
+    let mut removed = Vec::new();
+
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                changed.insert(*id);
+            }
+            AssetEvent::Removed { id } => {
+                changed.remove(id);
+                removed.push(*id);
+            }
+            AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+
+    commands.insert_resource(ExtractedTilemapMaterials {
+        extracted: changed
+            .into_iter()
+            .filter_map(|id| assets.get(id).map(|material| (id, material.clone())))
+            .collect(),
+        removed,
+    });
+}
+
+pub fn prepare_materials<M: TilemapMaterial>(
+    mut retry_next_frame: Local<Vec<(AssetId<M>, M)>>,
+    mut extracted_materials: ResMut<ExtractedTilemapMaterials<M>>,
+    mut bind_groups: ResMut<TilemapMaterialBindGroups<M>>,
+    render_device: Res<RenderDevice>,
+    images: Res<RenderAssets<Image>>,
+    fallback_image: Res<FallbackImage>,
+    pipeline: Res<EntiTilesMaterialPipeline<M>>,
+) {
+    extracted_materials.removed.drain(..).for_each(|id| {
+        bind_groups.0.remove(&id);
+    });
+
+    let queued = std::mem::take(&mut *retry_next_frame);
+    queued
+        .into_iter()
+        .chain(extracted_materials.extracted.drain(..))
+        .for_each(|(id, material)| {
+            match material.as_bind_group(
+                &pipeline.material_layout,
+                &render_device,
+                &images,
+                &fallback_image,
+            ) {
+                Ok(prepared) => {
+                    bind_groups.0.insert(id, prepared.bind_group);
+                }
+                Err(AsBindGroupError::RetryNextUpdate) => {
+                    retry_next_frame.push((id, material));
+                }
+            }
+        });
+}
+
+pub fn queue_material_tilemaps<M: TilemapMaterial>(
+    mut views_query: Query<&mut RenderPhase<Transparent2d>>,
+    tilemaps_query: Query<(&ExtractedTilemap, &Handle<M>)>,
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    mut sp_material_pipeline: ResMut<SpecializedRenderPipelines<EntiTilesMaterialPipeline<M>>>,
+    material_pipeline: Res<EntiTilesMaterialPipeline<M>>,
+    material_bind_groups: Res<TilemapMaterialBindGroups<M>>,
+    render_device: Res<RenderDevice>,
+    mut bind_groups: ResMut<TilemapBindGroups>,
+    textures_storage: Res<TilemapTexturesStorage>,
+    msaa: Res<Msaa>,
+) {
+    for mut transparent_phase in views_query.iter_mut() {
+        for (tilemap, material) in tilemaps_query.iter() {
+            if !material_bind_groups.0.contains_key(&material.id()) {
+                continue;
+            }
+
+            let is_pure_color = bind_groups.queue_textures(
+                tilemap,
+                &render_device,
+                &textures_storage,
+                &material_pipeline.entitiles_pipeline,
+            );
+
+            let pipeline = sp_material_pipeline.specialize(
+                &pipeline_cache,
+                &material_pipeline,
+                EntiTilesPipelineKey {
+                    msaa: msaa.samples(),
+                    map_type: tilemap.ty,
+                    is_pure_color,
+                    has_mask: tilemap.mask_color.is_some(),
+                    has_fog: tilemap.fog_colors.is_some(),
+                    has_lighting: tilemap.ambient_light.is_some(),
+                },
+            );
+
+            let draw_function = {
+                if is_pure_color {
+                    draw_functions
+                        .read()
+                        .get_id::<DrawTilemapPureColorWithMaterial<M>>()
+                        .unwrap()
+                } else {
+                    draw_functions
+                        .read()
+                        .get_id::<DrawTilemapWithMaterial<M>>()
+                        .unwrap()
+                }
+            };
+
+            transparent_phase.add(Transparent2d {
+                sort_key: FloatOrd(tilemap.transform.z_index as f32),
+                entity: tilemap.id,
+                pipeline,
+                draw_function,
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        }
+    }
+}
+
+pub type DrawTilemapWithMaterial<M> = (
+    SetPipeline,
+    SetTilemapViewBindGroup<0>,
+    SetTilemapUniformBufferBindGroup<1>,
+    SetTilemapColorTextureBindGroup<2>,
+    SetTilemapStorageBufferBindGroup<3>,
+    SetTilemapMaterialBindGroup<M, 4>,
+    DrawTileMesh<5>,
+);
+
+pub type DrawTilemapPureColorWithMaterial<M> = (
+    SetPipeline,
+    SetTilemapViewBindGroup<0>,
+    SetTilemapUniformBufferBindGroup<1>,
+    SetTilemapMaterialBindGroup<M, 2>,
+    DrawTileMesh<3>,
+);
+
+pub struct SetTilemapMaterialBindGroup<M: TilemapMaterial, const I: usize>(PhantomData<M>);
+impl<M: TilemapMaterial, const I: usize> RenderCommand<Transparent2d>
+    for SetTilemapMaterialBindGroup<M, I>
+{
+    type Param = SRes<TilemapMaterialBindGroups<M>>;
+
+    type ViewWorldQuery = ();
+
+    type ItemWorldQuery = Read<Handle<M>>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &Transparent2d,
+        _view: ROQueryItem<'w, Self::ViewWorldQuery>,
+        material: ROQueryItem<'w, Self::ItemWorldQuery>,
+        bind_groups: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if let Some(bind_group) = bind_groups.into_inner().0.get(&material.id()) {
+            pass.set_bind_group(I, bind_group, &[]);
+            RenderCommandResult::Success
+        } else {
+            error!("Failed to get tilemap material bind group!");
+            RenderCommandResult::Failure
+        }
+    }
+}
@@ -12,21 +12,35 @@ use bevy::{
 };
 
 use crate::render::{
+    bake::{BakedChunkPipeline, TilemapBaking},
     binding::{TilemapBindGroupLayouts, TilemapBindGroups},
     buffer::{TilemapStorageBuffers, TilemapUniformBuffer},
     chunk::{ChunkUnload, RenderChunkStorage, UnloadRenderChunk},
     culling::FrustumCulling,
-    draw::{DrawTilemap, DrawTilemapPureColor},
+    draw::{DrawBakedChunks, DrawTilemap, DrawTilemapGrid, DrawTilemapPureColor},
+    fog::TilemapFogBuffers,
+    grid::{TilemapGrid, TilemapGridBuffers, TilemapGridPipeline},
+    lighting::TilemapLightBuffers,
+    lod::TilemapLod,
+    mask::TilemapMaskBuffers,
     pipeline::EntiTilesPipeline,
     texture::TilemapTexturesStorage,
 };
+use crate::tilemap::{fog::TilemapFog, lighting::TilemapLighting, mask::TilemapMask};
 
+pub mod bake;
 pub mod binding;
 pub mod buffer;
 pub mod chunk;
 pub mod culling;
 pub mod draw;
 pub mod extract;
+pub mod fog;
+pub mod grid;
+pub mod lighting;
+pub mod lod;
+pub mod mask;
+pub mod material;
 pub mod pipeline;
 pub mod prepare;
 pub mod queue;
@@ -37,15 +51,16 @@ const ISOMETRIC: Handle<Shader> = Handle::weak_from_u128(45522415151365135);
 const HEXAGONAL: Handle<Shader> = Handle::weak_from_u128(341658413214563135);
 const COMMON: Handle<Shader> = Handle::weak_from_u128(1321023135616351);
 const TILEMAP_SHADER: Handle<Shader> = Handle::weak_from_u128(89646584153215);
+const BAKED_CHUNK_SHADER: Handle<Shader> = Handle::weak_from_u128(6513213546513584);
+const GRID_SHADER: Handle<Shader> = Handle::weak_from_u128(7916535486132158);
 
 pub const TILEMAP_MESH_ATTR_INDEX: MeshVertexAttribute =
-    MeshVertexAttribute::new("GridIndex", 14513156146, VertexFormat::Sint32x4);
+    MeshVertexAttribute::new("GridIndex", 14513156146, VertexFormat::Sint32x2);
 pub const TILEMAP_MESH_ATTR_COLOR: MeshVertexAttribute =
     MeshVertexAttribute::new("Color", 85415341854, VertexFormat::Float32x4);
-pub const TILEMAP_MESH_ATTR_TEX_INDICES: MeshVertexAttribute =
-    MeshVertexAttribute::new("TextureIndex", 186541653135, VertexFormat::Sint32x4);
-pub const TILEMAP_MESH_ATTR_FLIP: MeshVertexAttribute =
-    MeshVertexAttribute::new("Flip", 7365156123161, VertexFormat::Uint32x4);
+/// The start and the count of the tile layers in the per chunk layer buffer.
+pub const TILEMAP_MESH_ATTR_LAYERS: MeshVertexAttribute =
+    MeshVertexAttribute::new("Layers", 186541653135, VertexFormat::Uint32x2);
 
 pub struct EntiTilesRendererPlugin;
 
@@ -56,7 +71,10 @@ impl Plugin for EntiTilesRendererPlugin {
         load_internal_asset!(app, HEXAGONAL, "shaders/hexagonal.wgsl", Shader::from_wgsl);
         load_internal_asset!(app, COMMON, "shaders/common.wgsl", Shader::from_wgsl);
 
-        app.add_systems(Update, culling::cull_tilemaps);
+        app.add_systems(
+            Update,
+            (culling::cull_tilemaps, lod::lod_texture_colors_updater),
+        );
 
         load_internal_asset!(
             app,
@@ -64,10 +82,20 @@ impl Plugin for EntiTilesRendererPlugin {
             "shaders/tilemap.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            BAKED_CHUNK_SHADER,
+            "shaders/baked_chunk.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(app, GRID_SHADER, "shaders/grid.wgsl", Shader::from_wgsl);
 
         app.init_resource::<FrustumCulling>();
 
-        app.register_type::<UnloadRenderChunk>();
+        app.register_type::<UnloadRenderChunk>()
+            .register_type::<TilemapBaking>()
+            .register_type::<TilemapLod>()
+            .register_type::<TilemapGrid>();
         app.add_event::<ChunkUnload>();
 
         let render_app = app.get_sub_app_mut(RenderApp).unwrap();
@@ -78,6 +106,11 @@ impl Plugin for EntiTilesRendererPlugin {
                 (
                     extract::extract_tilemaps,
                     extract::extract_tiles,
+                    buffer::extract_per_chunk_data::<TilemapMask>,
+                    buffer::extract_per_chunk_data::<TilemapFog>,
+                    buffer::extract_per_chunk_data::<TilemapLighting>,
+                    lod::extract_lod,
+                    grid::extract_grids,
                     extract::extract_view,
                     extract::extract_unloaded_chunks,
                     extract::extract_resources,
@@ -90,6 +123,11 @@ impl Plugin for EntiTilesRendererPlugin {
                 (
                     prepare::prepare_tilemaps,
                     prepare::prepare_tiles,
+                    buffer::prepare_per_chunk_buffers::<TilemapMask>.after(prepare::prepare_tiles),
+                    buffer::prepare_per_chunk_buffers::<TilemapFog>.after(prepare::prepare_tiles),
+                    buffer::prepare_per_chunk_buffers::<TilemapLighting>
+                        .after(prepare::prepare_tiles),
+                    grid::prepare_grids.after(prepare::prepare_tilemaps),
                     prepare::prepare_unloaded_chunks,
                     prepare::prepare_despawned_tilemaps,
                     prepare::prepare_despawned_tiles,
@@ -97,18 +135,36 @@ impl Plugin for EntiTilesRendererPlugin {
                 )
                     .in_set(RenderSet::Prepare),
             )
-            .add_systems(Render, queue::queue.in_set(RenderSet::Queue));
+            .add_systems(
+                Render,
+                (
+                    queue::queue,
+                    bake::queue_baked_chunks.after(queue::queue),
+                    grid::queue_grids,
+                )
+                    .in_set(RenderSet::Queue),
+            )
+            .add_systems(
+                Render,
+                (bake::bake_chunks, lod::prepare_lod_summaries).in_set(RenderSet::PrepareBindGroups),
+            );
 
         render_app
             .init_resource::<RenderChunkStorage>()
             .init_resource::<TilemapTexturesStorage>()
             .init_resource::<TilemapUniformBuffer>()
             .init_resource::<TilemapStorageBuffers>()
+            .init_resource::<TilemapMaskBuffers>()
+            .init_resource::<TilemapFogBuffers>()
+            .init_resource::<TilemapLightBuffers>()
+            .init_resource::<TilemapGridBuffers>()
             .init_resource::<TilemapBindGroups>();
 
         render_app
             .add_render_command::<Transparent2d, DrawTilemap>()
-            .add_render_command::<Transparent2d, DrawTilemapPureColor>();
+            .add_render_command::<Transparent2d, DrawTilemapPureColor>()
+            .add_render_command::<Transparent2d, DrawBakedChunks>()
+            .add_render_command::<Transparent2d, DrawTilemapGrid>();
     }
 
     fn finish(&self, app: &mut bevy::prelude::App) {
@@ -117,6 +173,10 @@ impl Plugin for EntiTilesRendererPlugin {
         render_app
             .init_resource::<TilemapBindGroupLayouts>()
             .init_resource::<EntiTilesPipeline>()
-            .init_resource::<SpecializedRenderPipelines<EntiTilesPipeline>>();
+            .init_resource::<SpecializedRenderPipelines<EntiTilesPipeline>>()
+            .init_resource::<BakedChunkPipeline>()
+            .init_resource::<SpecializedRenderPipelines<BakedChunkPipeline>>()
+            .init_resource::<TilemapGridPipeline>()
+            .init_resource::<SpecializedRenderPipelines<TilemapGridPipeline>>();
     }
 }
@@ -11,16 +11,20 @@ use bevy::{
     },
 };
 
-use crate::tilemap::map::TilemapType;
+use crate::tilemap::map::{TilemapStagger, TilemapType};
 
 use super::{binding::TilemapBindGroupLayouts, TILEMAP_SHADER};
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct EntiTilesPipeline {
     pub view_layout: BindGroupLayout,
     pub uniform_buffers_layout: BindGroupLayout,
     pub storage_buffers_layout: BindGroupLayout,
     pub color_texture_layout: BindGroupLayout,
+    pub layers_layout: BindGroupLayout,
+    pub mask_layout: BindGroupLayout,
+    pub fog_layout: BindGroupLayout,
+    pub lighting_layout: BindGroupLayout,
 }
 
 #[derive(PartialEq, Eq, Hash, Clone)]
@@ -28,6 +32,9 @@ pub struct EntiTilesPipelineKey {
     pub msaa: u32,
     pub map_type: TilemapType,
     pub is_pure_color: bool,
+    pub has_mask: bool,
+    pub has_fog: bool,
+    pub has_lighting: bool,
 }
 
 impl FromWorld for EntiTilesPipeline {
@@ -38,6 +45,10 @@ impl FromWorld for EntiTilesPipeline {
             uniform_buffers_layout: layouts.tilemap_uniforms_layout.clone(),
             storage_buffers_layout: layouts.tilemap_storage_layout.clone(),
             color_texture_layout: layouts.color_texture_layout.clone(),
+            layers_layout: layouts.layers_layout.clone(),
+            mask_layout: layouts.mask_layout.clone(),
+            fog_layout: layouts.fog_layout.clone(),
+            lighting_layout: layouts.lighting_layout.clone(),
         }
     }
 }
@@ -46,25 +57,48 @@ impl SpecializedRenderPipeline for EntiTilesPipeline {
     type Key = EntiTilesPipelineKey;
 
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        self.specialize_with_material(key, None)
+    }
+}
+
+impl EntiTilesPipeline {
+    /// The material bind group is placed right after the built-in ones,
+    /// and the per chunk bind groups like the layers, the mask, the fog and the lighting
+    /// are placed after it.
+    pub fn specialize_with_material(
+        &self,
+        key: EntiTilesPipelineKey,
+        material_layout: Option<&BindGroupLayout>,
+    ) -> RenderPipelineDescriptor {
         let mut shader_defs: Vec<ShaderDefVal> = vec![];
         shader_defs.push(
             {
-                match key.map_type {
+                match key.map_type.unstaggered() {
                     TilemapType::Square => "SQUARE",
                     TilemapType::Isometric => "ISOMETRIC",
-                    TilemapType::Hexagonal(_) => "HEXAGONAL",
+                    _ => "HEXAGONAL",
                 }
             }
             .into(),
         );
+        match key.map_type {
+            TilemapType::StaggeredIsometric(stagger)
+            | TilemapType::StaggeredHexagonal(_, stagger) => {
+                shader_defs.push("STAGGERED".into());
+                if stagger == TilemapStagger::Even {
+                    shader_defs.push("STAGGER_EVEN".into());
+                }
+            }
+            _ => {}
+        }
         #[cfg(feature = "atlas")]
         shader_defs.push("ATLAS".into());
 
         let mut vtx_fmt = vec![
             // position
             VertexFormat::Float32x3,
-            // index + anim_start + anim_len
-            VertexFormat::Sint32x4,
+            // index
+            VertexFormat::Sint32x2,
             // color
             VertexFormat::Float32x4,
         ];
@@ -72,10 +106,8 @@ impl SpecializedRenderPipeline for EntiTilesPipeline {
         if key.is_pure_color {
             shader_defs.push("PURE_COLOR".into());
         } else {
-            // texture_indices
-            vtx_fmt.push(VertexFormat::Sint32x4);
-            // flip
-            vtx_fmt.push(VertexFormat::Uint32x4);
+            // layer start + layer count
+            vtx_fmt.push(VertexFormat::Uint32x2);
         }
 
         let vertex_layout =
@@ -95,6 +127,46 @@ impl SpecializedRenderPipeline for EntiTilesPipeline {
             layout.push(self.storage_buffers_layout.clone());
         }
 
+        if let Some(material_layout) = material_layout {
+            // group(4) or group(2) if pure color
+            layout.push(material_layout.clone());
+        }
+
+        if !key.is_pure_color {
+            shader_defs.push(ShaderDefVal::UInt(
+                "LAYERS_BIND_GROUP".into(),
+                layout.len() as u32,
+            ));
+            layout.push(self.layers_layout.clone());
+        }
+
+        if key.has_mask {
+            shader_defs.push("MASK".into());
+            shader_defs.push(ShaderDefVal::UInt(
+                "MASK_BIND_GROUP".into(),
+                layout.len() as u32,
+            ));
+            layout.push(self.mask_layout.clone());
+        }
+
+        if key.has_fog {
+            shader_defs.push("FOG".into());
+            shader_defs.push(ShaderDefVal::UInt(
+                "FOG_BIND_GROUP".into(),
+                layout.len() as u32,
+            ));
+            layout.push(self.fog_layout.clone());
+        }
+
+        if key.has_lighting {
+            shader_defs.push("LIGHTING".into());
+            shader_defs.push(ShaderDefVal::UInt(
+                "LIGHTING_BIND_GROUP".into(),
+                layout.len() as u32,
+            ));
+            layout.push(self.lighting_layout.clone());
+        }
+
         RenderPipelineDescriptor {
             label: Some("tilemap_pipeline".into()),
             layout,
@@ -13,6 +13,8 @@ use super::{
     },
     chunk::{TilemapRenderChunk, UnloadRenderChunk},
     extract::{ExtractedTile, ExtractedTilemap},
+    fog::TilemapFogBuffers,
+    lighting::TilemapLightBuffers,
     pipeline::EntiTilesPipeline,
     texture::TilemapTexturesStorage,
     RenderChunkStorage,
@@ -38,7 +40,7 @@ pub fn prepare_tilemaps(
             .entity(tilemap.id)
             .insert(uniform_buffers.insert(tilemap));
 
-        render_chunks.prepare_chunks(tilemap, &render_device);
+        render_chunks.prepare_chunks(tilemap, &render_device, &entitiles_pipeline.layers_layout);
 
         if let Some(texture) = tilemap.texture.as_ref() {
             storage_buffers
@@ -94,11 +96,15 @@ pub fn prepare_unloaded_chunks(
 pub fn prepare_despawned_tilemaps(
     mut render_chunks: ResMut<RenderChunkStorage>,
     mut storage_buffers: ResMut<TilemapStorageBuffers>,
+    mut fog_buffers: ResMut<TilemapFogBuffers>,
+    mut light_buffers: ResMut<TilemapLightBuffers>,
     tilemaps_query: Query<&DespawnedTilemap>,
 ) {
     tilemaps_query.for_each(|map| {
         render_chunks.remove_tilemap(map.0);
         storage_buffers.remove(map.0);
+        fog_buffers.remove(map.0);
+        light_buffers.remove(map.0);
     });
 }
 
@@ -1,5 +1,6 @@
 use bevy::{
     core_pipeline::core_2d::Transparent2d,
+    ecs::query::Without,
     prelude::{Commands, Entity, Msaa, Query, Res, ResMut},
     render::{
         render_asset::RenderAssets,
@@ -16,6 +17,7 @@ use super::{
     binding::{TilemapBindGroups, TilemapViewBindGroup},
     draw::{DrawTilemap, DrawTilemapPureColor},
     extract::ExtractedTilemap,
+    material::ExtractedMaterialTilemap,
     pipeline::{EntiTilesPipeline, EntiTilesPipelineKey},
     texture::TilemapTexturesStorage,
 };
@@ -26,7 +28,7 @@ use bevy::render::renderer::RenderQueue;
 pub fn queue(
     mut commands: Commands,
     mut views_query: Query<(Entity, &mut RenderPhase<Transparent2d>)>,
-    tilemaps_query: Query<&ExtractedTilemap>,
+    tilemaps_query: Query<&ExtractedTilemap, Without<ExtractedMaterialTilemap>>,
     pipeline_cache: Res<PipelineCache>,
     draw_functions: Res<DrawFunctions<Transparent2d>>,
     mut sp_entitiles_pipeline: ResMut<SpecializedRenderPipelines<EntiTilesPipeline>>,
@@ -79,6 +81,9 @@ pub fn queue(
                     msaa: msaa.samples(),
                     map_type: tilemap.ty,
                     is_pure_color,
+                    has_mask: tilemap.mask_color.is_some(),
+                    has_fog: tilemap.fog_colors.is_some(),
+                    has_lighting: tilemap.ambient_light.is_some(),
                 },
             );
 
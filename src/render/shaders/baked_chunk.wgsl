@@ -0,0 +1,30 @@
+#import bevy_sprite::mesh2d_view_bindings::view
+
+struct BakedChunkVertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct BakedChunkVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var baked_texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var baked_sampler: sampler;
+
+@vertex
+fn baked_chunk_vertex(input: BakedChunkVertexInput) -> BakedChunkVertexOutput {
+    var output: BakedChunkVertexOutput;
+    output.position = view.view_proj * vec4<f32>(input.position, 0., 1.);
+    output.uv = input.uv;
+    return output;
+}
+
+@fragment
+fn baked_chunk_fragment(input: BakedChunkVertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(baked_texture, baked_sampler, input.uv);
+}
@@ -3,39 +3,50 @@
 struct VertexInput {
     @builtin(vertex_index) v_index: u32,
     @location(0) position: vec3<f32>,
-    // When the third and forth component of index are not -1,
-    // it means this tile is a animated tile
-    // So the zw components are the start index and the length of the animation sequence
-    @location(1) index: vec4<i32>,
+    @location(1) index: vec2<i32>,
     @location(2) color: vec4<f32>,
 #ifndef PURE_COLOR
-    @location(3) texture_indices: vec4<i32>,
-    @location(4) flip: vec4<u32>,
+    // The start and the count of the layers in `tile_layers`
+    @location(3) layers: vec2<u32>,
 #endif
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
+#ifdef MASK
+    @location(5) masked: u32,
+#endif
+#ifdef FOG
+    @location(6) fog_state: u32,
+#endif
+#ifdef LIGHTING
+    @location(7) light: vec3<f32>,
+#endif
 #ifndef PURE_COLOR
     @location(1) uv: vec2<f32>,
-    @location(2) flip: vec4<u32>,
-    @location(3) texture_indices: vec4<i32>,
-    @location(4) anim_flag: i32,
+    @location(2) layers: vec2<u32>,
 #endif
 }
 
 struct Tilemap {
     translation: vec2<f32>,
-    rot_mat: mat2x2<f32>,
+    // scale and rotation
+    transform: mat2x2<f32>,
     uv_rot: u32,
     tile_render_size: vec2<f32>,
     slot_size: vec2<f32>,
     pivot: vec2<f32>,
-    layer_opacities: vec4<f32>,
+    mask_color: vec4<f32>,
+    fog_unexplored_color: vec4<f32>,
+    fog_explored_color: vec4<f32>,
+    ambient_light: vec4<f32>,
     // this value will only be meaningful when the tilemap is hexagonal!
     hex_legs: f32,
     time: f32,
+    anim_time: f32,
+    chunk_size: u32,
+    mask_inverted: u32,
 #ifdef ATLAS
     // texture size in tiles
     texture_tiled_size: vec2<i32>,
@@ -61,3 +72,160 @@ var color_texture_sampler: sampler;
 @group(3) @binding(0)
 var<storage> anim_seqs: array<i32>;
 #endif
+
+#ifndef PURE_COLOR
+struct TileLayer {
+    texture_index: i32,
+    flip: u32,
+    // clockwise, in 90 degrees
+    rotation: u32,
+    opacity: f32,
+    // -1 if this layer is not animated
+    anim_start: i32,
+    anim_length: u32,
+    // 0: loop, 1: ping-pong, 2: once
+    anim_mode: u32,
+    anim_offset: f32,
+}
+
+// The layers of the tiles in the chunk being drawn.
+@group(#{LAYERS_BIND_GROUP}) @binding(0)
+var<storage> tile_layers: array<TileLayer>;
+#endif
+
+#ifdef MASK
+// The mask states of the chunk being drawn, 1 if the tile is marked.
+@group(#{MASK_BIND_GROUP}) @binding(0)
+var<storage> mask_states: array<u32>;
+#endif
+
+#ifdef FOG
+// The fog states of the chunk being drawn.
+@group(#{FOG_BIND_GROUP}) @binding(0)
+var<storage> fog_states: array<u32>;
+#endif
+
+#ifdef LIGHTING
+// The lightmap of the chunk being drawn.
+@group(#{LIGHTING_BIND_GROUP}) @binding(0)
+var<storage> lightmap: array<vec4<f32>>;
+#endif
+
+#ifdef STAGGERED
+// The shift of the row in tiles, every other row is shifted by half a tile.
+fn stagger_shift(y: i32) -> f32 {
+    let odd = f32(((y % 2) + 2) % 2);
+#ifdef STAGGER_EVEN
+    return (1. - odd) * 0.5;
+#else
+    return odd * 0.5;
+#endif
+}
+#endif
+
+// The index of the tile in the per chunk buffers.
+fn in_chunk_index(index: vec2<i32>) -> i32 {
+    let chunk_size = i32(tilemap.chunk_size);
+    // `%` keeps the sign of the dividend, so make it positive.
+    let in_chunk = ((index % chunk_size) + chunk_size) % chunk_size;
+    return in_chunk.y * chunk_size + in_chunk.x;
+}
+
+#ifndef PURE_COLOR
+// The texture index of the current frame of an animated layer.
+fn anim_frame(layer: TileLayer) -> i32 {
+    // The number before the start index is the fps.
+    // See register_animation function in TilemapAnimations.
+    let fps = f32(anim_seqs[layer.anim_start - 1]);
+    let length = i32(layer.anim_length);
+    // Hold the first frame until the animation starts.
+    let frame = max(i32((tilemap.anim_time - layer.anim_offset) * fps), 0);
+
+    var index = frame % length;
+    switch layer.anim_mode {
+        // Ping-pong
+        case 1u: {
+            let period = max(length * 2 - 2, 1);
+            let i = frame % period;
+            index = select(i, period - i, i >= length);
+        }
+        // Once
+        case 2u: {
+            index = min(frame, length - 1);
+        }
+        default: {}
+    }
+    return anim_seqs[layer.anim_start + index];
+}
+#endif
+
+#ifndef PURE_COLOR
+// Undo the rotation and then the flipping to find the uv on the texture.
+fn layer_uv(tile_uv: vec2<f32>, layer: TileLayer) -> vec2<f32> {
+    var uv = tile_uv;
+    for (var i = 0u; i < layer.rotation; i++) {
+        uv = vec2<f32>(uv.y, 1. - uv.x);
+    }
+    if (layer.flip & 2u) != 0u {
+        uv.y = 1. - uv.y;
+    }
+    if (layer.flip & 1u) != 0u {
+        uv.x = 1. - uv.x;
+    }
+    if (layer.flip & 4u) != 0u {
+        uv = uv.yx;
+    }
+    return uv;
+}
+#endif
+
+// The color of the tile before applying any custom materials.
+fn tilemap_color(input: VertexOutput) -> vec4<f32> {
+#ifdef PURE_COLOR
+    var color = input.color;
+#else
+    var color = vec4<f32>(0., 0., 0., 0.);
+
+    for (var i = input.layers.x; i < input.layers.x + input.layers.y; i++) {
+        let layer = tile_layers[i];
+        var texture_index = layer.texture_index;
+        if layer.anim_start != -1 {
+            texture_index = anim_frame(layer);
+        }
+        if texture_index < 0 {
+            continue;
+        }
+
+        let uv = layer_uv(input.uv, layer);
+#ifdef ATLAS
+        let tile_index = vec2<f32>(f32(texture_index % tilemap.texture_tiled_size.x),
+                                   f32(texture_index / tilemap.texture_tiled_size.x));
+        let atlas_uv = (tile_index + uv) * tilemap.tile_uv_size;
+        let tex_color = textureSample(color_texture, color_texture_sampler, atlas_uv);
+#else
+        let tex_color = textureSample(color_texture, color_texture_sampler, uv, texture_index);
+#endif
+        color = mix(color, tex_color, tex_color.a * layer.opacity);
+    }
+    color *= input.color;
+#endif
+
+#ifdef MASK
+    if (input.masked != 0u) != (tilemap.mask_inverted != 0u) {
+        color *= tilemap.mask_color;
+    }
+#endif
+
+#ifdef FOG
+    if input.fog_state == 0u {
+        color *= tilemap.fog_unexplored_color;
+    } else if input.fog_state == 1u {
+        color *= tilemap.fog_explored_color;
+    }
+#endif
+
+#ifdef LIGHTING
+    color = vec4<f32>(color.rgb * (tilemap.ambient_light.rgb + input.light), color.a);
+#endif
+    return color;
+}
@@ -0,0 +1,242 @@
+#import bevy_entitiles::common::tilemap
+#import bevy_sprite::mesh2d_view_bindings::view
+
+#ifdef STAGGERED
+#import bevy_entitiles::common::stagger_shift
+#endif
+
+struct TilemapGrid {
+    color: vec4<f32>,
+    coordinates_color: vec4<f32>,
+    // The area covered by the chunks in world space.
+    area_min: vec2<f32>,
+    area_max: vec2<f32>,
+    // The width of the lines in pixels.
+    thickness: f32,
+    show_coordinates: u32,
+}
+
+@group(2) @binding(0)
+var<uniform> grid: TilemapGrid;
+
+struct GridVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Relative to the bottom left of the slot at [0, 0], without the tilemap transform.
+    @location(0) local: vec2<f32>,
+}
+
+struct Slot {
+    index: vec2<i32>,
+    center: vec2<f32>,
+    // The distance to the closest edge of the slot.
+    edge: f32,
+}
+
+// 3x5 bitmaps of 0-9 and the minus sign, from the top left to the bottom right.
+const GLYPHS = array<u32, 11>(
+    31599u, 11415u, 29671u, 29647u, 23497u, 31183u, 31215u, 29257u, 31727u, 31695u, 448u
+);
+
+// The bottom left of the slot at [0, 0]. Same as `index_to_world` in `coordinates.rs`.
+fn slot_origin() -> vec2<f32> {
+    var shift = 0.;
+#ifdef STAGGERED
+    shift = stagger_shift(0);
+#endif
+#ifdef HEXAGONAL
+    return vec2<f32>(
+        tilemap.slot_size.x * (shift - tilemap.pivot.x),
+        (tilemap.slot_size.y + tilemap.hex_legs) / 2. * -tilemap.pivot.y,
+    );
+#else
+    return (vec2<f32>(shift, 0.) - tilemap.pivot) * tilemap.slot_size;
+#endif
+}
+
+// Find the slot like `world_to_index`, but without the staggering.
+fn locate_slot(local: vec2<f32>) -> Slot {
+    var slot: Slot;
+    let half = tilemap.slot_size / 2.;
+
+#ifdef SQUARE
+    let index = floor(local / tilemap.slot_size);
+    slot.index = vec2<i32>(index);
+    slot.center = index * tilemap.slot_size + half;
+    let d = half - abs(local - slot.center);
+    slot.edge = min(d.x, d.y);
+#endif
+
+#ifdef ISOMETRIC
+    let rel = (local - half) / tilemap.slot_size;
+    let index = round(vec2<f32>(rel.x + rel.y, rel.y - rel.x));
+    slot.index = vec2<i32>(index);
+    slot.center = vec2<f32>(index.x - index.y, index.x + index.y) / 2. * tilemap.slot_size + half;
+    let d = abs(local - slot.center);
+    slot.edge = (1. - d.x / half.x - d.y / half.y) * half.x * half.y / length(half);
+#endif
+
+#ifdef HEXAGONAL
+    let rel = local - half;
+    let row_height = (tilemap.slot_size.y + tilemap.hex_legs) / 2.;
+    let half_leg = tilemap.hex_legs / 2.;
+
+    // The point is between the centers of these two rows,
+    // so it must be inside the closest hexagon of one of them.
+    let lower = floor(rel.y / row_height);
+    var best = 3.4e38;
+    for (var y = lower; y <= lower + 1.; y += 1.) {
+        let x = round(rel.x / tilemap.slot_size.x + 0.5 * y);
+        let center = vec2<f32>(tilemap.slot_size.x * (x - 0.5 * y), row_height * y);
+        let d = abs(rel - center);
+        let edge = half_leg + (half.y - half_leg) * (1. - d.x / half.x);
+        if d.y - edge < best {
+            best = d.y - edge;
+            slot.index = vec2<i32>(i32(x), i32(y));
+            slot.center = center + half;
+            // The distances to the vertical and the slanted edges.
+            slot.edge = min(
+                half.x - d.x,
+                (edge - d.y) * half.x / length(vec2<f32>(half.x, half.y - half_leg))
+            );
+        }
+    }
+#endif
+
+    return slot;
+}
+
+#ifdef STAGGERED
+// Same as `TilemapType::from_unstaggered_index`.
+fn from_unstaggered_index(index: vec2<i32>) -> vec2<i32> {
+    let shift_0 = i32(stagger_shift(0) * 2.);
+#ifdef ISOMETRIC
+    let y = index.x + index.y;
+    let d = index.x - index.y;
+    return vec2<i32>((d + shift_0 - i32(stagger_shift(y) * 2.)) / 2, y);
+#else
+    let shift_y = i32(stagger_shift(index.y) * 2.);
+    return vec2<i32>(index.x - (index.y + shift_y - shift_0) / 2, index.y);
+#endif
+}
+#endif
+
+// Whether the pixel at `col` and `row` of the label `value` is filled.
+// The label is centered horizontally at `col` 0.
+fn is_label_pixel(value: i32, col: i32, row: i32) -> bool {
+    let negative = value < 0;
+    var magnitude = u32(abs(value));
+    var digits = 1;
+    var power = 1u;
+    while magnitude / power >= 10u {
+        power *= 10u;
+        digits += 1;
+    }
+
+    let chars = digits + i32(negative);
+    // Each char is 3 pixels wide with a pixel of spacing.
+    let x = col + (chars * 4 - 1) / 2;
+    if x < 0 || x >= chars * 4 - 1 || x % 4 == 3 {
+        return false;
+    }
+
+    let char_index = x / 4;
+    var glyph = 10;
+    if !negative || char_index > 0 {
+        let digit_index = char_index - i32(negative);
+        for (var i = 0; i < digit_index; i++) {
+            power /= 10u;
+        }
+        glyph = i32(magnitude / power % 10u);
+    }
+
+    var glyphs = GLYPHS;
+    let bit = u32((4 - row) * 3 + (2 - x % 4));
+    return (glyphs[glyph] >> bit & 1u) != 0u;
+}
+
+// Draw the x index above the y index at the center of the slot.
+fn is_coordinates_pixel(index: vec2<i32>, p: vec2<f32>) -> bool {
+    let row = i32(floor(5.5 - p.y));
+    let col = i32(floor(p.x));
+    if row >= 0 && row < 5 {
+        return is_label_pixel(index.x, col, row);
+    }
+    if row >= 6 && row < 11 {
+        return is_label_pixel(index.y, col, row - 6);
+    }
+    return false;
+}
+
+@vertex
+fn grid_vertex(@builtin(vertex_index) v_index: u32) -> GridVertexOutput {
+    var output: GridVertexOutput;
+
+    // Clip the area to the camera.
+    var camera_min = vec2<f32>(3.4e38);
+    var camera_max = vec2<f32>(-3.4e38);
+    for (var i = 0u; i < 4u; i++) {
+        let ndc = vec2<f32>(f32(i & 1u), f32(i >> 1u)) * 2. - 1.;
+        let world = view.inverse_view_proj * vec4<f32>(ndc, 0., 1.);
+        camera_min = min(camera_min, world.xy / world.w);
+        camera_max = max(camera_max, world.xy / world.w);
+    }
+    let area_min = max(grid.area_min, camera_min);
+    let area_max = max(min(grid.area_max, camera_max), area_min);
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0., 0.),
+        vec2<f32>(0., 1.),
+        vec2<f32>(1., 1.),
+        vec2<f32>(0., 0.),
+        vec2<f32>(1., 1.),
+        vec2<f32>(1., 0.),
+    );
+    let world = mix(area_min, area_max, corners[v_index % 6u]);
+    output.position = view.view_proj * vec4<f32>(world, 0., 1.);
+
+    let m = tilemap.transform;
+    let inverse_transform = mat2x2<f32>(m[1][1], -m[0][1], -m[1][0], m[0][0])
+                            * (1. / determinant(m));
+    output.local = inverse_transform * (world - tilemap.translation) - slot_origin();
+    return output;
+}
+
+@fragment
+fn grid_fragment(input: GridVertexOutput) -> @location(0) vec4<f32> {
+    // The size of a pixel on the screen in local space.
+    let pixel = max(length(dpdx(input.local)), length(dpdy(input.local)));
+    let slot = locate_slot(input.local);
+    // Premultiplied.
+    var color = vec4<f32>(0., 0., 0., 0.);
+
+    if grid.show_coordinates != 0u {
+        // The size of a pixel of the labels, so that two lines of 5 chars fit in the slot.
+#ifdef ISOMETRIC
+        let label_pixel = min(tilemap.slot_size.x / 48., tilemap.slot_size.y / 28.);
+#else
+        let label_pixel = min(tilemap.slot_size.x / 24., tilemap.slot_size.y / 14.);
+#endif
+        // Too small to read.
+        if label_pixel >= pixel {
+            var index = slot.index;
+#ifdef STAGGERED
+            index = from_unstaggered_index(index);
+#endif
+            if is_coordinates_pixel(index, (input.local - slot.center) / label_pixel) {
+                let c = grid.coordinates_color;
+                color = vec4<f32>(c.rgb * c.a, c.a);
+            }
+        }
+    }
+
+    // Both slots next to an edge draw half of the line.
+    let half_thickness = grid.thickness / 2.;
+    let line = 1. - smoothstep(half_thickness - 0.5, half_thickness + 0.5, slot.edge / pixel);
+    let line_alpha = line * grid.color.a;
+    color = vec4<f32>(grid.color.rgb * line_alpha, line_alpha) + color * (1. - line_alpha);
+
+    if color.a <= 0. {
+        discard;
+    }
+    return color;
+}
@@ -2,14 +2,25 @@
 
 #import bevy_entitiles::common::{VertexInput, tilemap}
 
+#ifdef STAGGERED
+#import bevy_entitiles::common::stagger_shift
+#endif
+
 fn get_mesh_origin(input: VertexInput) -> vec2<f32> {
     /*
      * ANOTHER MATHEMATICAL MAGIC!!!!!!!
      */
-    let index = vec2<f32>(input.index.xy);
+    let index = vec2<f32>(input.index);
 
+#ifdef STAGGERED
+    return vec2<f32>(
+        tilemap.slot_size.x * (index.x + stagger_shift(input.index.y)),
+        (tilemap.slot_size.y + tilemap.hex_legs) / 2. * index.y,
+    );
+#else
     return vec2<f32>(
         tilemap.slot_size.x * (index.x - 0.5 * index.y),
         (tilemap.slot_size.y + tilemap.hex_legs) / 2. * index.y,
     );
+#endif
 }
@@ -2,10 +2,21 @@
 
 #import bevy_entitiles::common::{VertexInput, tilemap}
 
+#ifdef STAGGERED
+#import bevy_entitiles::common::stagger_shift
+#endif
+
 fn get_mesh_origin(input: VertexInput) -> vec2<f32> {
-    let index = vec2<f32>(input.index.xy);
+    let index = vec2<f32>(input.index);
+#ifdef STAGGERED
+    return vec2<f32>(
+        index.x + stagger_shift(input.index.y),
+        index.y / 2.
+    ) * tilemap.slot_size;
+#else
     return vec2<f32>(
         (index.x - index.y),
         (index.x + index.y)
     ) / 2. * tilemap.slot_size;
+#endif
 }
@@ -3,6 +3,6 @@
 #import bevy_entitiles::common::{VertexInput, tilemap}
 
 fn get_mesh_origin(input: VertexInput) -> vec2<f32> {
-    let index = vec2<f32>(input.index.xy);
+    let index = vec2<f32>(input.index);
     return index.xy * tilemap.slot_size;
 }
@@ -233,7 +233,7 @@ pub fn load_physics_layer(
             let mut new_chunk = vec![None; (chunk_size * chunk_size) as usize];
             chunk.tiles.into_iter().for_each(|(in_chunk_index, tile)| {
                 new_chunk[(in_chunk_index.y * chunk_size + in_chunk_index.x) as usize] =
-                    Some(tile.spawn(&mut commands, *ty));
+                    Some(tile.spawn(&mut commands, *ty, entity));
             });
             physics_tilemap.storage.set_chunk(chunk_index, new_chunk);
         });
@@ -239,7 +239,7 @@ pub fn save_path_layer(
             );
 
             if remove_after_save {
-                path_tilemap.storage.remove_chunk(chunk_index);
+                path_tilemap.remove_chunk(chunk_index);
             }
         });
     });
@@ -140,6 +140,7 @@ pub fn load(
 
             commands.entity(entity).insert(PathTilemap {
                 storage: path_storage,
+                ..Default::default()
             });
         }
 
@@ -161,7 +162,7 @@ pub fn load(
                     physics_storage.set_elem_precise(
                         chunk_index,
                         in_chunk_index,
-                        tile.spawn(&mut commands, ser_tilemap.ty),
+                        tile.spawn(&mut commands, ser_tilemap.ty, entity),
                     );
                 });
 
@@ -98,7 +98,7 @@ impl SerializedTilemap {
             tile_render_size: self.tile_render_size,
             slot_size: self.slot_size,
             tile_pivot: self.tile_pivot,
-            layer_opacities: self.layer_opacities,
+            layer_opacities: self.layer_opacities.clone(),
             storage: TilemapStorage {
                 tilemap,
                 storage: ChunkedStorage::new(self.chunk_size),
@@ -118,7 +118,7 @@ impl SerializedTilemap {
             tile_render_size: self.tile_render_size,
             slot_size: self.slot_size,
             tile_pivot: self.tile_pivot,
-            layer_opacities: self.layer_opacities,
+            layer_opacities: self.layer_opacities.clone(),
             storage: TilemapStorage {
                 tilemap,
                 storage: ChunkedStorage::new(self.chunk_size),
@@ -196,3 +196,61 @@ bitflags::bitflags! {
         const PHYSICS = 1 << 2;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::math::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_load_old_tilemap() {
+        // Saved before the free rotation and the list of layer opacities.
+        let tilemap = ron::from_str::<SerializedTilemap>(
+            r#"(
+                name: ("old"),
+                tile_render_size: ((16.0, 16.0)),
+                slot_size: ((16.0, 16.0)),
+                ty: Square,
+                tile_pivot: ((0.0, 0.0)),
+                layer_opacities: ((1.0, 0.5, 0.25, 1.0)),
+                tilemap_transform: (translation: (8.0, -8.0), z_index: 3, rotation: Cw90),
+                texture: Some((
+                    path: "test_square.png",
+                    desc: (size: (32, 32), tile_size: (16, 16), filter_mode: Nearest),
+                    rotation: None,
+                )),
+                animations: Some(([])),
+                layers: ("COLOR | PATH"),
+                chunk_size: 16,
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(tilemap.layer_opacities.0, vec![1., 0.5, 0.25, 1.]);
+        assert_eq!(
+            tilemap.tilemap_transform,
+            TilemapTransform {
+                translation: Vec2::new(8., -8.),
+                z_index: 3,
+                rotation: FRAC_PI_2,
+                scale: Vec2::ONE,
+            }
+        );
+        assert_eq!(tilemap.layers, TilemapLayer::COLOR | TilemapLayer::PATH);
+    }
+
+    #[test]
+    fn test_layer_opacities_round_trip() {
+        for opacities in [vec![], vec![0.5], vec![1., 0.25, 0., 0.75, 0.5]] {
+            let opacities = TilemapLayerOpacities(opacities);
+            let ron = ron::to_string(&opacities).unwrap();
+            assert_eq!(
+                ron::from_str::<TilemapLayerOpacities>(&ron).unwrap(),
+                opacities
+            );
+        }
+    }
+}
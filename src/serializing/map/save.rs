@@ -106,7 +106,7 @@ pub fn save(
                 *slot_size,
                 *ty,
                 *tile_pivot,
-                *layer_opacities,
+                layer_opacities.clone(),
                 storage.clone(),
                 transform.clone(),
                 texture.cloned(),
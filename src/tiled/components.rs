@@ -0,0 +1,35 @@
+use bevy::{
+    ecs::{component::Component, entity::Entity, system::Commands},
+    math::Vec2,
+    reflect::Reflect,
+};
+
+#[derive(Component, Reflect, Default)]
+pub struct TiledLoader {
+    pub(crate) map: String,
+    pub(crate) trans_ovrd: Option<Vec2>,
+}
+
+#[derive(Component, Reflect, Default)]
+pub struct TiledUnloader;
+
+#[derive(Component)]
+pub struct TiledUnloadLayer;
+
+#[derive(Component, Reflect)]
+pub struct TiledLoadedTilemap {
+    pub name: String,
+    /// The tilemap entities of each tile layer.
+    ///
+    /// As a tilemap can only use one texture, layers that use multiple tilesets
+    /// are split into several tilemaps.
+    pub layers: Vec<Entity>,
+}
+
+impl TiledLoadedTilemap {
+    pub fn unload(&self, commands: &mut Commands) {
+        self.layers.iter().for_each(|e| {
+            commands.entity(*e).insert(TiledUnloadLayer);
+        });
+    }
+}
@@ -0,0 +1,12 @@
+use bevy::{ecs::event::Event, reflect::Reflect};
+
+#[derive(Event)]
+pub enum TiledEvent {
+    MapLoaded(MapEvent),
+    MapUnloaded(MapEvent),
+}
+
+#[derive(Reflect, Debug, Clone)]
+pub struct MapEvent {
+    pub name: String,
+}
@@ -0,0 +1,298 @@
+//! `.tmj` and `.tsj` files are deserialized into the structures below,
+//! and then converted into the xml structures, so the loader only needs to handle one format.
+
+use serde::Deserialize;
+
+use super::xml::{
+    layer::{
+        DataCompression, DataEncoding, TiledChunk, TiledDataElement, TiledGroupLayer,
+        TiledLayerData, TiledTileGid, TiledTileLayer,
+    },
+    tileset::{TiledAnimation, TiledFrame, TiledImage, TiledTile, TiledTileset},
+    MapOrientation, StaggerAxis, StaggerIndex, TiledMapElement, TiledXml,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJson {
+    pub orientation: MapOrientation,
+    pub width: u32,
+    pub height: u32,
+    #[serde(rename = "tilewidth")]
+    pub tile_width: u32,
+    #[serde(rename = "tileheight")]
+    pub tile_height: u32,
+    #[serde(rename = "hexsidelength", default)]
+    pub hex_side_length: u32,
+    #[serde(rename = "staggeraxis", default)]
+    pub stagger_axis: StaggerAxis,
+    #[serde(rename = "staggerindex", default)]
+    pub stagger_index: StaggerIndex,
+    #[serde(default)]
+    pub tilesets: Vec<TiledJsonTileset>,
+    #[serde(default)]
+    pub layers: Vec<TiledJsonLayer>,
+}
+
+impl From<TiledJson> for TiledXml {
+    fn from(value: TiledJson) -> Self {
+        TiledXml {
+            orientation: value.orientation,
+            width: value.width,
+            height: value.height,
+            tile_width: value.tile_width,
+            tile_height: value.tile_height,
+            hex_side_length: value.hex_side_length,
+            stagger_axis: value.stagger_axis,
+            stagger_index: value.stagger_index,
+            elements: value
+                .tilesets
+                .into_iter()
+                .map(|tileset| TiledMapElement::Tileset(tileset.into()))
+                .chain(value.layers.into_iter().map(|layer| layer.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonTileset {
+    #[serde(rename = "firstgid", default)]
+    pub first_gid: u32,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "tilewidth", default)]
+    pub tile_width: u32,
+    #[serde(rename = "tileheight", default)]
+    pub tile_height: u32,
+    #[serde(rename = "tilecount", default)]
+    pub tile_count: u32,
+    #[serde(default)]
+    pub columns: u32,
+    #[serde(default)]
+    pub spacing: u32,
+    #[serde(default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(rename = "imagewidth", default)]
+    pub image_width: u32,
+    #[serde(rename = "imageheight", default)]
+    pub image_height: u32,
+    #[serde(default)]
+    pub tiles: Vec<TiledJsonTile>,
+}
+
+impl From<TiledJsonTileset> for TiledTileset {
+    fn from(value: TiledJsonTileset) -> Self {
+        TiledTileset {
+            first_gid: value.first_gid,
+            source: value.source,
+            name: value.name,
+            tile_width: value.tile_width,
+            tile_height: value.tile_height,
+            tile_count: value.tile_count,
+            columns: value.columns,
+            spacing: value.spacing,
+            margin: value.margin,
+            image: value.image.map(|source| TiledImage {
+                source,
+                width: value.image_width,
+                height: value.image_height,
+            }),
+            tiles: value
+                .tiles
+                .into_iter()
+                .map(|tile| TiledTile {
+                    id: tile.id,
+                    animation: tile.animation.map(|frames| TiledAnimation {
+                        frames: frames
+                            .into_iter()
+                            .map(|frame| TiledFrame {
+                                tile_id: frame.tile_id,
+                                duration: frame.duration,
+                            })
+                            .collect(),
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonTile {
+    pub id: u32,
+    #[serde(default)]
+    pub animation: Option<Vec<TiledJsonFrame>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonFrame {
+    #[serde(rename = "tileid")]
+    pub tile_id: u32,
+    pub duration: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TiledJsonLayer {
+    TileLayer(TiledJsonTileLayer),
+    Group(TiledJsonGroupLayer),
+    #[serde(other)]
+    Other,
+}
+
+impl From<TiledJsonLayer> for TiledMapElement {
+    fn from(value: TiledJsonLayer) -> Self {
+        match value {
+            TiledJsonLayer::TileLayer(layer) => TiledMapElement::Layer(layer.into()),
+            TiledJsonLayer::Group(group) => TiledMapElement::Group(TiledGroupLayer {
+                name: group.name,
+                opacity: group.opacity,
+                visible: group.visible,
+                offset_x: group.offset_x,
+                offset_y: group.offset_y,
+                elements: group.layers.into_iter().map(|layer| layer.into()).collect(),
+            }),
+            TiledJsonLayer::Other => TiledMapElement::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonTileLayer {
+    #[serde(default)]
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(rename = "offsetx", default)]
+    pub offset_x: f32,
+    #[serde(rename = "offsety", default)]
+    pub offset_y: f32,
+    #[serde(rename = "tintcolor", default)]
+    pub tint_color: Option<String>,
+    #[serde(default)]
+    pub encoding: Option<DataEncoding>,
+    /// Empty string if the data is not compressed.
+    #[serde(default)]
+    pub compression: String,
+    #[serde(default)]
+    pub data: Option<TiledJsonData>,
+    /// Only for infinite maps.
+    #[serde(default)]
+    pub chunks: Vec<TiledJsonChunk>,
+}
+
+impl From<TiledJsonTileLayer> for TiledTileLayer {
+    fn from(value: TiledJsonTileLayer) -> Self {
+        let encoding = match value.encoding {
+            Some(DataEncoding::Base64) => Some(DataEncoding::Base64),
+            // Arrays of gids are treated as `<tile>` elements.
+            _ => None,
+        };
+        let compression = match value.compression.as_str() {
+            "zlib" => Some(DataCompression::Zlib),
+            "gzip" => Some(DataCompression::Gzip),
+            "zstd" => Some(DataCompression::Zstd),
+            _ => None,
+        };
+
+        let elements = if value.chunks.is_empty() {
+            value
+                .data
+                .map(|data| data.into_elements())
+                .unwrap_or_default()
+        } else {
+            value
+                .chunks
+                .into_iter()
+                .map(|chunk| {
+                    TiledDataElement::Chunk(TiledChunk {
+                        x: chunk.x,
+                        y: chunk.y,
+                        width: chunk.width,
+                        height: chunk.height,
+                        elements: chunk.data.into_elements(),
+                    })
+                })
+                .collect()
+        };
+
+        TiledTileLayer {
+            id: value.id,
+            name: value.name,
+            width: value.width,
+            height: value.height,
+            opacity: value.opacity,
+            visible: value.visible,
+            offset_x: value.offset_x,
+            offset_y: value.offset_y,
+            tint_color: value.tint_color,
+            data: TiledLayerData {
+                encoding,
+                compression,
+                elements,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonGroupLayer {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(rename = "offsetx", default)]
+    pub offset_x: f32,
+    #[serde(rename = "offsety", default)]
+    pub offset_y: f32,
+    #[serde(default)]
+    pub layers: Vec<TiledJsonLayer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledJsonChunk {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub data: TiledJsonData,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TiledJsonData {
+    Gids(Vec<u32>),
+    Base64(String),
+}
+
+impl TiledJsonData {
+    fn into_elements(self) -> Vec<TiledDataElement> {
+        match self {
+            TiledJsonData::Gids(gids) => gids
+                .into_iter()
+                .map(|gid| TiledDataElement::Tile(TiledTileGid { gid }))
+                .collect(),
+            TiledJsonData::Base64(data) => vec![TiledDataElement::Text(data)],
+        }
+    }
+}
+
+fn default_opacity() -> f32 {
+    1.
+}
+
+fn default_visible() -> bool {
+    true
+}
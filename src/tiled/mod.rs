@@ -0,0 +1,463 @@
+use bevy::{
+    app::{Plugin, Startup, Update},
+    asset::AssetServer,
+    ecs::{
+        entity::Entity,
+        event::EventWriter,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+    },
+    log::error,
+    math::{IVec2, Vec2, Vec4},
+    prelude::SpatialBundle,
+    transform::components::Transform,
+    utils::HashMap,
+};
+
+use crate::{
+    tilemap::{
+        bundles::TilemapBundle,
+        map::{
+            TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName, TilemapSlotSize,
+            TilemapStorage, TilemapTransform,
+        },
+        tile::{TileAnimation, TileBuilder, TileFlip, TileLayer},
+    },
+    DEFAULT_CHUNK_SIZE,
+};
+
+use self::{
+    components::{TiledLoadedTilemap, TiledLoader, TiledUnloadLayer, TiledUnloader},
+    events::{MapEvent, TiledEvent},
+    resources::{TiledAssets, TiledLoadConfig, TiledTilemapManager},
+    xml::{
+        layer::TiledTileLayer, tileset::TiledTileset, TiledXml, FLIPPED_DIAGONALLY,
+        FLIPPED_HORIZONTALLY, FLIPPED_VERTICALLY, GID_MASK,
+    },
+};
+
+pub mod components;
+pub mod events;
+pub mod json;
+pub mod resources;
+pub mod xml;
+
+pub struct EntiTilesTiledPlugin;
+
+impl Plugin for EntiTilesTiledPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Startup, parse_tiled_xml);
+        app.add_systems(
+            Update,
+            (load_tiled_xml, unload_tiled_map, unload_tiled_layer),
+        );
+
+        app.init_resource::<TiledLoadConfig>()
+            .init_resource::<TiledTilemapManager>()
+            .init_resource::<TiledAssets>();
+
+        app.add_event::<TiledEvent>();
+
+        app.register_type::<TiledLoader>()
+            .register_type::<TiledUnloader>()
+            .register_type::<TiledLoadedTilemap>()
+            .register_type::<MapEvent>();
+
+        app.register_type::<TiledLoadConfig>()
+            .register_type::<TiledTilemapManager>()
+            .register_type::<TiledAssets>();
+    }
+}
+
+fn parse_tiled_xml(mut manager: ResMut<TiledTilemapManager>, config: Res<TiledLoadConfig>) {
+    manager.reload_xml(&config);
+}
+
+pub fn unload_tiled_map(
+    mut commands: Commands,
+    query: Query<(Entity, &TiledLoadedTilemap), With<TiledUnloader>>,
+    mut tiled_events: EventWriter<TiledEvent>,
+) {
+    query.for_each(|(entity, map)| {
+        tiled_events.send(TiledEvent::MapUnloaded(MapEvent {
+            name: map.name.clone(),
+        }));
+        map.unload(&mut commands);
+        commands.entity(entity).despawn();
+    });
+}
+
+pub fn unload_tiled_layer(
+    mut commands: Commands,
+    mut query: Query<&mut TilemapStorage, With<TiledUnloadLayer>>,
+) {
+    query.iter_mut().for_each(|mut storage| {
+        storage.despawn(&mut commands);
+    });
+}
+
+pub fn load_tiled_xml(
+    mut commands: Commands,
+    loader_query: Query<(Entity, &TiledLoader)>,
+    asset_server: Res<AssetServer>,
+    manager: Res<TiledTilemapManager>,
+    config: Res<TiledLoadConfig>,
+    mut tiled_assets: ResMut<TiledAssets>,
+    mut tiled_events: EventWriter<TiledEvent>,
+) {
+    for (entity, loader) in loader_query.iter() {
+        commands.entity(entity).remove::<TiledLoader>();
+
+        let Some(tiled) = manager.get_cached_data(&loader.map) else {
+            error!("Could not find tiled map: {}!", loader.map);
+            continue;
+        };
+
+        let Some(ty) = tiled.get_tilemap_type() else {
+            error!(
+                "Tiled maps with orientation {:?} and stagger axis {:?} are not supported!",
+                tiled.orientation, tiled.stagger_axis
+            );
+            continue;
+        };
+
+        let translation = loader.trans_ovrd.unwrap_or_default();
+        let template = TilemapBundle {
+            ty,
+            slot_size: TilemapSlotSize(tiled.slot_size()),
+            ..Default::default()
+        };
+        let layers = tiled
+            .tile_layers()
+            .iter()
+            .enumerate()
+            .flat_map(|(layer_index, layer)| {
+                load_layer(
+                    &mut commands,
+                    tiled,
+                    layer,
+                    TilemapBundle {
+                        tilemap_transform: TilemapTransform::from_translation_3d(
+                            translation,
+                            config.z_index + layer_index as i32,
+                        ),
+                        ..template.clone()
+                    },
+                    &config,
+                    &asset_server,
+                    &mut tiled_assets,
+                )
+            })
+            .collect();
+
+        commands.entity(entity).insert((
+            TiledLoadedTilemap {
+                name: loader.map.clone(),
+                layers,
+            },
+            SpatialBundle {
+                transform: Transform::from_translation(translation.extend(0.)),
+                ..Default::default()
+            },
+        ));
+
+        tiled_events.send(TiledEvent::MapLoaded(MapEvent {
+            name: loader.map.clone(),
+        }));
+    }
+}
+
+/// Spawn a tilemap for each tileset used in the layer.
+///
+/// The `template` should contain the properties shared by all the layers.
+fn load_layer(
+    commands: &mut Commands,
+    tiled: &TiledXml,
+    layer: &TiledTileLayer,
+    template: TilemapBundle,
+    config: &TiledLoadConfig,
+    asset_server: &AssetServer,
+    tiled_assets: &mut TiledAssets,
+) -> Vec<Entity> {
+    let mut tiles_by_tileset = HashMap::<u32, Vec<(IVec2, u32)>>::new();
+    layer
+        .data
+        .decode(layer.width)
+        .into_iter()
+        .for_each(|(index, gid)| {
+            if let Some(tileset) = tiled.get_tileset(gid) {
+                tiles_by_tileset
+                    .entry(tileset.first_gid)
+                    .or_default()
+                    .push((index, gid));
+            }
+        });
+
+    let mut first_gids = tiles_by_tileset.keys().copied().collect::<Vec<_>>();
+    first_gids.sort();
+
+    let color = layer.tint();
+    let opacity = layer.opacity * color.w;
+    let color = Vec4::new(color.x, color.y, color.z, 1.);
+
+    first_gids
+        .into_iter()
+        .filter_map(|first_gid| {
+            let tileset = tiled.get_tileset(first_gid).unwrap();
+            let texture = tiled_assets
+                .get_or_load_tileset(tileset, config, asset_server)?
+                .clone();
+
+            let tilemap_entity = commands.spawn_empty().id();
+            let mut tilemap = TilemapBundle {
+                name: TilemapName(layer.name.clone()),
+                tile_render_size: TileRenderSize(tileset.tile_size().as_vec2()),
+                texture,
+                storage: TilemapStorage::new(DEFAULT_CHUNK_SIZE, tilemap_entity),
+                tilemap_transform: TilemapTransform {
+                    translation: template.tilemap_transform.translation
+                        + Vec2::new(layer.offset_x, -layer.offset_y),
+                    ..template.tilemap_transform
+                },
+                layer_opacities: TilemapLayerOpacities(vec![opacity]),
+                ..template.clone()
+            };
+
+            let mut animations = HashMap::<u32, TileAnimation>::new();
+            tiles_by_tileset[&first_gid]
+                .iter()
+                .for_each(|(index, gid)| {
+                    let builder =
+                        build_tile(tileset, *gid, &mut tilemap.animations, &mut animations)
+                            .with_color(color);
+                    tilemap
+                        .storage
+                        .set(commands, tiled.transform_index(*index), builder);
+                });
+
+            commands.entity(tilemap_entity).insert(tilemap);
+            Some(tilemap_entity)
+        })
+        .collect()
+}
+
+fn build_tile(
+    tileset: &TiledTileset,
+    gid: u32,
+    tilemap_animations: &mut TilemapAnimations,
+    registered: &mut HashMap<u32, TileAnimation>,
+) -> TileBuilder {
+    let local_id = (gid & GID_MASK) - tileset.first_gid;
+
+    let mut tile_layer = TileLayer::new().with_texture_index(local_id);
+    if let Some(animation) = tileset.get_animation(local_id) {
+        let animation = *registered.entry(local_id).or_insert_with(|| {
+            tilemap_animations.register_animation(animation.fps(), animation.sequence())
+        });
+        tile_layer = tile_layer.with_animation(animation);
+    }
+
+    if gid & FLIPPED_DIAGONALLY != 0 {
+        tile_layer = tile_layer.with_flip(TileFlip::ANTI_DIAGONAL);
+    }
+    if gid & FLIPPED_HORIZONTALLY != 0 {
+        tile_layer = tile_layer.with_flip(TileFlip::HORIZONTAL);
+    }
+    if gid & FLIPPED_VERTICALLY != 0 {
+        tile_layer = tile_layer.with_flip(TileFlip::VERTICAL);
+    }
+
+    TileBuilder::new().with_layer(0, tile_layer)
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::UVec2;
+
+    use crate::tilemap::{map::TilemapType, tile::TileTexture};
+
+    use super::{
+        json::TiledJson,
+        xml::{
+            layer::parse_hex_color,
+            tileset::{TiledAnimation, TiledFrame},
+            MapOrientation, StaggerIndex,
+        },
+        *,
+    };
+
+    const TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" name="test" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="test.png" width="32" height="32"/>
+  <tile id="0">
+   <animation>
+    <frame tileid="0" duration="100"/>
+    <frame tileid="1" duration="100"/>
+   </animation>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2" tintcolor="#80ff0000">
+  <data encoding="csv">
+1,2147483650,0,
+0,1610612739,4
+</data>
+ </layer>
+</map>"##;
+
+    const TMJ: &str = r##"{
+        "orientation": "orthogonal",
+        "width": 3,
+        "height": 2,
+        "tilewidth": 16,
+        "tileheight": 16,
+        "tilesets": [{
+            "firstgid": 1,
+            "name": "test",
+            "tilewidth": 16,
+            "tileheight": 16,
+            "tilecount": 4,
+            "columns": 2,
+            "image": "test.png",
+            "imagewidth": 32,
+            "imageheight": 32,
+            "tiles": [{
+                "id": 0,
+                "animation": [
+                    { "tileid": 0, "duration": 100 },
+                    { "tileid": 1, "duration": 100 }
+                ]
+            }]
+        }],
+        "layers": [{
+            "type": "tilelayer",
+            "name": "ground",
+            "width": 3,
+            "height": 2,
+            "tintcolor": "#80ff0000",
+            "data": [1, 2147483650, 0, 0, 1610612739, 4]
+        }]
+    }"##;
+
+    fn assert_fixture(tiled: &TiledXml) {
+        assert_eq!(tiled.get_tilemap_type(), Some(TilemapType::Square));
+        assert_eq!(tiled.slot_size(), Vec2::splat(16.));
+
+        let tileset = tiled.get_tileset(2 | FLIPPED_HORIZONTALLY).unwrap();
+        assert_eq!(tileset.first_gid, 1);
+        assert_eq!(tileset.tiled_size(), UVec2::splat(32));
+        assert_eq!(tileset.get_animation(0).unwrap().fps(), 10);
+        assert!(tileset.get_animation(1).is_none());
+
+        let layers = tiled.tile_layers();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].tint(), Vec4::new(1., 0., 0., 128. / 255.));
+        assert_eq!(
+            layers[0].data.decode(layers[0].width),
+            vec![
+                (IVec2::new(0, 0), 1),
+                (IVec2::new(1, 0), 2 | FLIPPED_HORIZONTALLY),
+                (
+                    IVec2::new(1, 1),
+                    3 | FLIPPED_DIAGONALLY | FLIPPED_VERTICALLY
+                ),
+                (IVec2::new(2, 1), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_tmx() {
+        assert_fixture(&quick_xml::de::from_str::<TiledXml>(TMX).unwrap());
+    }
+
+    #[test]
+    fn test_load_tmj() {
+        assert_fixture(&serde_json::from_str::<TiledJson>(TMJ).unwrap().into());
+    }
+
+    #[test]
+    fn test_transform_index() {
+        let mut tiled = quick_xml::de::from_str::<TiledXml>(TMX).unwrap();
+        let index = IVec2::new(2, 3);
+
+        assert_eq!(tiled.transform_index(index), IVec2::new(2, -4));
+
+        tiled.orientation = MapOrientation::Isometric;
+        assert_eq!(tiled.transform_index(index), IVec2::new(-4, -3));
+
+        tiled.orientation = MapOrientation::Staggered;
+        assert_eq!(tiled.transform_index(index), IVec2::new(2, -3));
+
+        tiled.orientation = MapOrientation::Hexagonal;
+        assert_eq!(tiled.transform_index(index), IVec2::new(1, -3));
+        assert_eq!(tiled.transform_index(IVec2::new(2, 2)), IVec2::new(1, -2));
+        tiled.stagger_index = StaggerIndex::Even;
+        assert_eq!(tiled.transform_index(index), IVec2::new(0, -3));
+        assert_eq!(tiled.transform_index(IVec2::new(2, 2)), IVec2::new(1, -2));
+    }
+
+    #[test]
+    fn test_gid_flags() {
+        let tiled = quick_xml::de::from_str::<TiledXml>(TMX).unwrap();
+        let tileset = tiled.get_tileset(1).unwrap();
+        let mut animations = TilemapAnimations::default();
+        let mut registered = HashMap::default();
+        let mut layer_of = |gid: u32| {
+            let builder = build_tile(tileset, gid, &mut animations, &mut registered);
+            let TileTexture::Static(layers) = builder.texture else {
+                panic!("Tiled tiles should be static!");
+            };
+            layers[0]
+        };
+
+        let layer = layer_of(2 | FLIPPED_HORIZONTALLY);
+        assert_eq!(layer.texture_index, 1);
+        assert_eq!(layer.flip, TileFlip::HORIZONTAL.bits());
+
+        let layer = layer_of(3 | FLIPPED_DIAGONALLY | FLIPPED_VERTICALLY);
+        assert_eq!(layer.texture_index, 2);
+        assert_eq!(
+            layer.flip,
+            (TileFlip::ANTI_DIAGONAL | TileFlip::VERTICAL).bits()
+        );
+
+        // The flags are kept for animated tiles as well.
+        let layer = layer_of(1 | FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY);
+        assert_eq!(layer.texture_index, 0);
+        assert_eq!(layer.flip, TileFlip::BOTH.bits());
+        assert_eq!(layer.animation.unwrap().fps, 10);
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#ffffff"), Some(Vec4::ONE));
+        assert_eq!(parse_hex_color("#00ff00"), Some(Vec4::new(0., 1., 0., 1.)));
+        assert_eq!(parse_hex_color("ff0000ff"), Some(Vec4::new(0., 0., 1., 1.)));
+        assert_eq!(parse_hex_color("#00000000"), Some(Vec4::ZERO));
+        assert_eq!(parse_hex_color("#fff"), None);
+        assert_eq!(parse_hex_color("#gggggg"), None);
+    }
+
+    #[test]
+    fn test_animation_fps() {
+        let animation = |durations: &[u32]| TiledAnimation {
+            frames: durations
+                .iter()
+                .map(|&duration| TiledFrame {
+                    tile_id: 0,
+                    duration,
+                })
+                .collect(),
+        };
+
+        assert_eq!(animation(&[100, 100]).fps(), 10);
+        // The average duration is used.
+        assert_eq!(animation(&[50, 150]).fps(), 10);
+        assert_eq!(animation(&[300, 300, 400]).fps(), 3);
+        // Never slower than 1 fps.
+        assert_eq!(animation(&[5000]).fps(), 1);
+        assert_eq!(animation(&[]).fps(), 1);
+        assert_eq!(animation(&[0, 0]).fps(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    fs::read_to_string,
+    path::{Component, Path, PathBuf},
+};
+
+use bevy::{
+    asset::AssetServer,
+    ecs::{
+        entity::Entity,
+        system::{Commands, Resource},
+    },
+    log::{error, warn},
+    math::Vec2,
+    reflect::Reflect,
+    render::render_resource::FilterMode,
+    utils::HashMap,
+};
+
+use crate::tilemap::map::{TilemapRotation, TilemapTexture, TilemapTextureDescriptor};
+
+use super::{
+    components::{TiledLoader, TiledUnloader},
+    json::{TiledJson, TiledJsonTileset},
+    xml::{tileset::TiledTileset, TiledXml},
+};
+
+#[derive(Resource, Default, Reflect)]
+pub struct TiledLoadConfig {
+    /// Paths to the `.tmx` or `.tmj` files, relative to the working directory.
+    ///
+    /// Maps are identified by their file stems.
+    pub map_path: Vec<String>,
+    /// The path to the directory containing the maps, relative to the asset folder.
+    pub asset_path_prefix: String,
+    #[reflect(ignore)]
+    pub filter_mode: FilterMode,
+    pub z_index: i32,
+}
+
+#[derive(Resource, Default, Reflect)]
+pub struct TiledAssets {
+    /// image path to texture
+    pub(crate) tilesets: HashMap<String, TilemapTexture>,
+}
+
+impl TiledAssets {
+    /// Get the texture of the tileset, load it if it's not loaded yet.
+    ///
+    /// Returns `None` if the tileset is a collection of images, which is not supported.
+    pub fn get_or_load_tileset(
+        &mut self,
+        tileset: &TiledTileset,
+        config: &TiledLoadConfig,
+        asset_server: &AssetServer,
+    ) -> Option<&TilemapTexture> {
+        let Some(image) = tileset.image.as_ref() else {
+            error!(
+                "Tileset {} is a collection of images, which is not supported!",
+                tileset.name
+            );
+            return None;
+        };
+
+        if !self.tilesets.contains_key(&image.source) {
+            if tileset.spacing != 0 || tileset.margin != 0 {
+                warn!(
+                    "Tileset {} has spacing or margin, which is not supported! \
+                    The tiles may be displayed incorrectly.",
+                    tileset.name
+                );
+            }
+
+            let texture = TilemapTexture::new(
+                asset_server.load(normalize_path(
+                    &Path::new(&config.asset_path_prefix).join(&image.source),
+                )),
+                TilemapTextureDescriptor::new(
+                    tileset.tiled_size(),
+                    tileset.tile_size(),
+                    config.filter_mode,
+                ),
+                TilemapRotation::None,
+            );
+            self.tilesets.insert(image.source.clone(), texture);
+        }
+
+        self.tilesets.get(&image.source)
+    }
+}
+
+#[derive(Resource, Default, Reflect)]
+pub struct TiledTilemapManager {
+    #[reflect(ignore)]
+    pub(crate) cache: HashMap<String, TiledXml>,
+    pub(crate) loaded_maps: HashMap<String, Entity>,
+}
+
+impl TiledTilemapManager {
+    /// Reloads all the maps and refresh the cache.
+    pub fn reload_xml(&mut self, config: &TiledLoadConfig) {
+        self.cache.clear();
+
+        for path in config.map_path.iter() {
+            let path = Path::new(path);
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                error!("Invalid tiled map path: {:?}!", path);
+                continue;
+            };
+
+            let mut tiled = match path.extension().and_then(|e| e.to_str()) {
+                Some("tmx") => quick_xml::de::from_str::<TiledXml>(&read_file(path))
+                    .unwrap_or_else(|e| panic!("Could not parse file at path: {:?}!\n{}", path, e)),
+                Some("tmj") | Some("json") => serde_json::from_str::<TiledJson>(&read_file(path))
+                    .unwrap_or_else(|e| panic!("Could not parse file at path: {:?}!\n{}", path, e))
+                    .into(),
+                _ => {
+                    error!("Unsupported tiled map format: {:?}!", path);
+                    continue;
+                }
+            };
+
+            let map_dir = path.parent().unwrap_or(Path::new(""));
+            tiled.tilesets_mut().for_each(|tileset| {
+                load_external_tileset(tileset, map_dir);
+            });
+
+            self.cache.insert(name.to_string(), tiled);
+        }
+    }
+
+    #[inline]
+    pub fn get_cached_data(&self, map: &str) -> Option<&TiledXml> {
+        self.cache.get(map)
+    }
+
+    pub fn load(&mut self, commands: &mut Commands, map: String, trans_ovrd: Option<Vec2>) {
+        if self.loaded_maps.contains_key(&map) {
+            error!("Trying to load {:?} that is already loaded!", map);
+        } else {
+            let entity = commands.spawn(TiledLoader {
+                map: map.clone(),
+                trans_ovrd,
+            });
+            self.loaded_maps.insert(map, entity.id());
+        }
+    }
+
+    pub fn switch_to(&mut self, commands: &mut Commands, map: String, trans_ovrd: Option<Vec2>) {
+        if self.loaded_maps.contains_key(&map) {
+            error!("Trying to load {:?} that is already loaded!", map);
+        } else {
+            self.unload_all(commands);
+            self.load(commands, map, trans_ovrd);
+        }
+    }
+
+    pub fn unload(&mut self, commands: &mut Commands, map: String) {
+        if let Some(l) = self.loaded_maps.remove(&map) {
+            commands.entity(l).insert(TiledUnloader);
+        } else {
+            error!("Trying to unload {:?} that is not loaded!", map);
+        }
+    }
+
+    pub fn unload_all(&mut self, commands: &mut Commands) {
+        for (_, l) in self.loaded_maps.drain() {
+            commands.entity(l).insert(TiledUnloader);
+        }
+    }
+
+    #[inline]
+    pub fn is_loaded(&self, map: String) -> bool {
+        self.loaded_maps.contains_key(&map)
+    }
+}
+
+fn read_file(path: &Path) -> String {
+    match read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => panic!("Could not read file at path: {:?}!\n{}", path, e),
+    }
+}
+
+/// Replace the tileset with the content of the external tileset file,
+/// and make the image path relative to the map.
+fn load_external_tileset(tileset: &mut TiledTileset, map_dir: &Path) {
+    let Some(source) = tileset.source.clone() else {
+        return;
+    };
+
+    let path = map_dir.join(&source);
+    let mut external: TiledTileset = match path.extension().and_then(|e| e.to_str()) {
+        Some("tsx") => quick_xml::de::from_str(&read_file(&path))
+            .unwrap_or_else(|e| panic!("Could not parse file at path: {:?}!\n{}", path, e)),
+        Some("tsj") | Some("json") => serde_json::from_str::<TiledJsonTileset>(&read_file(&path))
+            .unwrap_or_else(|e| panic!("Could not parse file at path: {:?}!\n{}", path, e))
+            .into(),
+        _ => {
+            error!("Unsupported tiled tileset format: {:?}!", path);
+            return;
+        }
+    };
+
+    let tileset_dir = Path::new(&source).parent().unwrap_or(Path::new(""));
+    if let Some(image) = external.image.as_mut() {
+        image.source = normalize_path(&tileset_dir.join(&image.source))
+            .to_string_lossy()
+            .to_string();
+    }
+    external.first_gid = tileset.first_gid;
+    external.source = Some(source);
+    *tileset = external;
+}
+
+/// Resolve `..` and `.` without touching the file system,
+/// as the asset server doesn't accept them.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,262 @@
+use std::io::Read;
+
+use base64::Engine;
+use bevy::{
+    log::error,
+    math::{IVec2, Vec4},
+};
+use serde::Deserialize;
+
+use super::TiledMapElement;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledTileLayer {
+    #[serde(rename = "@id", default)]
+    pub id: u32,
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    /// Width of the layer in tiles.
+    #[serde(rename = "@width")]
+    pub width: u32,
+    /// Height of the layer in tiles.
+    #[serde(rename = "@height")]
+    pub height: u32,
+    #[serde(rename = "@opacity", default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(rename = "@visible", default = "default_visible")]
+    pub visible: bool,
+    /// Horizontal offset in pixels.
+    #[serde(rename = "@offsetx", default)]
+    pub offset_x: f32,
+    /// Vertical offset in pixels. Points down.
+    #[serde(rename = "@offsety", default)]
+    pub offset_y: f32,
+    /// A color that is multiplied with every tile of this layer.
+    #[serde(rename = "@tintcolor", default)]
+    pub tint_color: Option<String>,
+    pub data: TiledLayerData,
+}
+
+impl TiledTileLayer {
+    /// Get the tint color of the layer.
+    pub fn tint(&self) -> Vec4 {
+        self.tint_color
+            .as_ref()
+            .and_then(|c| parse_hex_color(c))
+            .unwrap_or(Vec4::ONE)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledGroupLayer {
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "@opacity", default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(rename = "@visible", default = "default_visible")]
+    pub visible: bool,
+    #[serde(rename = "@offsetx", default)]
+    pub offset_x: f32,
+    #[serde(rename = "@offsety", default)]
+    pub offset_y: f32,
+    #[serde(rename = "$value", default)]
+    pub elements: Vec<TiledMapElement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataEncoding {
+    Csv,
+    Base64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataCompression {
+    Zlib,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledLayerData {
+    /// `None` means the data is stored as `<tile>` elements.
+    #[serde(rename = "@encoding", default)]
+    pub encoding: Option<DataEncoding>,
+    #[serde(rename = "@compression", default)]
+    pub compression: Option<DataCompression>,
+    #[serde(rename = "$value", default)]
+    pub elements: Vec<TiledDataElement>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiledDataElement {
+    /// The data of infinite maps are split into chunks.
+    Chunk(TiledChunk),
+    Tile(TiledTileGid),
+    #[serde(rename = "$text")]
+    Text(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledChunk {
+    #[serde(rename = "@x")]
+    pub x: i32,
+    #[serde(rename = "@y")]
+    pub y: i32,
+    #[serde(rename = "@width")]
+    pub width: u32,
+    #[serde(rename = "@height")]
+    pub height: u32,
+    #[serde(rename = "$value", default)]
+    pub elements: Vec<TiledDataElement>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledTileGid {
+    #[serde(rename = "@gid", default)]
+    pub gid: u32,
+}
+
+impl TiledLayerData {
+    /// Decode the data into indices (in Tiled) and gids with flip flags.
+    ///
+    /// Empty tiles are skipped.
+    pub fn decode(&self, width: u32) -> Vec<(IVec2, u32)> {
+        let chunks = self
+            .elements
+            .iter()
+            .filter_map(|e| match e {
+                TiledDataElement::Chunk(chunk) => Some(chunk),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if chunks.is_empty() {
+            self.decode_elements(&self.elements, IVec2::ZERO, width)
+        } else {
+            chunks
+                .into_iter()
+                .flat_map(|chunk| {
+                    self.decode_elements(&chunk.elements, IVec2::new(chunk.x, chunk.y), chunk.width)
+                })
+                .collect()
+        }
+    }
+
+    fn decode_elements(
+        &self,
+        elements: &[TiledDataElement],
+        origin: IVec2,
+        width: u32,
+    ) -> Vec<(IVec2, u32)> {
+        let gids = match self.encoding {
+            Some(DataEncoding::Csv) => elements
+                .iter()
+                .filter_map(|e| match e {
+                    TiledDataElement::Text(text) => Some(text),
+                    _ => None,
+                })
+                .flat_map(|text| text.split(','))
+                .filter_map(|gid| gid.trim().parse::<u32>().ok())
+                .collect::<Vec<_>>(),
+            Some(DataEncoding::Base64) => elements
+                .iter()
+                .filter_map(|e| match e {
+                    TiledDataElement::Text(text) => Some(text),
+                    _ => None,
+                })
+                .flat_map(|text| self.decode_base64(text))
+                .collect(),
+            None => elements
+                .iter()
+                .filter_map(|e| match e {
+                    TiledDataElement::Tile(tile) => Some(tile.gid),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        gids.into_iter()
+            .enumerate()
+            .filter(|(_, gid)| *gid != 0)
+            .map(|(i, gid)| {
+                let i = i as u32;
+                (
+                    origin + IVec2::new((i % width) as i32, (i / width) as i32),
+                    gid,
+                )
+            })
+            .collect()
+    }
+
+    fn decode_base64(&self, text: &str) -> Vec<u32> {
+        let bytes = match base64::engine::general_purpose::STANDARD.decode(text.trim()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Failed to decode base64 layer data! {}", e);
+                return Vec::new();
+            }
+        };
+
+        let bytes = match self.compression {
+            None => bytes,
+            Some(DataCompression::Zlib) => {
+                let mut decompressed = Vec::new();
+                if let Err(e) =
+                    flate2::read::ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)
+                {
+                    error!("Failed to decompress zlib layer data! {}", e);
+                }
+                decompressed
+            }
+            Some(DataCompression::Gzip) => {
+                let mut decompressed = Vec::new();
+                if let Err(e) =
+                    flate2::read::GzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)
+                {
+                    error!("Failed to decompress gzip layer data! {}", e);
+                }
+                decompressed
+            }
+            Some(DataCompression::Zstd) => {
+                error!("Zstandard compressed layer data is not supported! Please use zlib or gzip instead.");
+                Vec::new()
+            }
+        };
+
+        bytes
+            .chunks_exact(4)
+            .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+            .collect()
+    }
+}
+
+/// Parse colors like `#rrggbb` or `#aarrggbb` into rgba.
+pub fn parse_hex_color(color: &str) -> Option<Vec4> {
+    let hex = color.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let (a, rgb) = match hex.len() {
+        6 => (255, value),
+        8 => (value >> 24, value & 0xFFFFFF),
+        _ => return None,
+    };
+
+    Some(
+        Vec4::new(
+            ((rgb >> 16) & 0xFF) as f32,
+            ((rgb >> 8) & 0xFF) as f32,
+            (rgb & 0xFF) as f32,
+            a as f32,
+        ) / 255.,
+    )
+}
+
+fn default_opacity() -> f32 {
+    1.
+}
+
+fn default_visible() -> bool {
+    true
+}
@@ -0,0 +1,212 @@
+use bevy::{
+    math::{IVec2, Vec2},
+    reflect::Reflect,
+};
+use serde::Deserialize;
+
+use crate::tilemap::map::{TilemapStagger, TilemapType};
+
+use self::tileset::TiledTileset;
+
+pub mod layer;
+pub mod tileset;
+
+/// The gid of a tile with this bit set is flipped horizontally.
+pub const FLIPPED_HORIZONTALLY: u32 = 0x80000000;
+/// The gid of a tile with this bit set is flipped vertically.
+pub const FLIPPED_VERTICALLY: u32 = 0x40000000;
+/// The gid of a tile with this bit set is flipped anti-diagonally.
+pub const FLIPPED_DIAGONALLY: u32 = 0x20000000;
+/// The gid of a hexagonal tile with this bit set is rotated by 120 degrees.
+pub const ROTATED_HEXAGONAL_120: u32 = 0x10000000;
+/// Mask out all the flags and keep the actual gid.
+pub const GID_MASK: u32 =
+    !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120);
+
+/// The root element of a `.tmx` file.
+///
+/// `.tmj` files are converted into this structure as well.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledXml {
+    #[serde(rename = "@orientation")]
+    pub orientation: MapOrientation,
+    /// Width of the map in tiles.
+    #[serde(rename = "@width")]
+    pub width: u32,
+    /// Height of the map in tiles.
+    #[serde(rename = "@height")]
+    pub height: u32,
+    /// Width of a tile slot in pixels.
+    #[serde(rename = "@tilewidth")]
+    pub tile_width: u32,
+    /// Height of a tile slot in pixels.
+    #[serde(rename = "@tileheight")]
+    pub tile_height: u32,
+    /// Only for hexagonal maps. The length of the side of a hexagon in pixels,
+    /// which is the `legs` of `TilemapType::Hexagonal`.
+    #[serde(rename = "@hexsidelength", default)]
+    pub hex_side_length: u32,
+    /// For staggered and hexagonal maps.
+    #[serde(rename = "@staggeraxis", default)]
+    pub stagger_axis: StaggerAxis,
+    /// For staggered and hexagonal maps.
+    #[serde(rename = "@staggerindex", default)]
+    pub stagger_index: StaggerIndex,
+    #[serde(rename = "$value", default)]
+    pub elements: Vec<TiledMapElement>,
+}
+
+impl TiledXml {
+    /// Get the corresponding tilemap type of this map.
+    ///
+    /// Returns `None` if the layout is not supported.
+    pub fn get_tilemap_type(&self) -> Option<TilemapType> {
+        match self.orientation {
+            MapOrientation::Orthogonal => Some(TilemapType::Square),
+            MapOrientation::Isometric => Some(TilemapType::Isometric),
+            MapOrientation::Hexagonal => match self.stagger_axis {
+                StaggerAxis::Y => Some(TilemapType::Hexagonal(self.hex_side_length)),
+                StaggerAxis::X => None,
+            },
+            MapOrientation::Staggered => match self.stagger_axis {
+                StaggerAxis::Y => Some(TilemapType::StaggeredIsometric(self.stagger_index.into())),
+                StaggerAxis::X => None,
+            },
+        }
+    }
+
+    #[inline]
+    pub fn slot_size(&self) -> Vec2 {
+        Vec2::new(self.tile_width as f32, self.tile_height as f32)
+    }
+
+    /// Transform the index in Tiled into the index in this crate.
+    ///
+    /// The y axis in Tiled points down, so the map will be spawned below the origin.
+    pub fn transform_index(&self, index: IVec2) -> IVec2 {
+        match self.orientation {
+            MapOrientation::Orthogonal => IVec2::new(index.x, -index.y - 1),
+            MapOrientation::Isometric => IVec2::new(-index.y - 1, -index.x - 1),
+            MapOrientation::Hexagonal => {
+                let x = match self.stagger_index {
+                    StaggerIndex::Odd => index.x - index.y.div_euclid(2),
+                    StaggerIndex::Even => index.x - (index.y + 1).div_euclid(2),
+                };
+                IVec2::new(x, -index.y)
+            }
+            // Keep the parity of the rows so the same rows are shifted.
+            MapOrientation::Staggered => IVec2::new(index.x, -index.y),
+        }
+    }
+
+    #[inline]
+    pub fn tilesets(&self) -> impl Iterator<Item = &TiledTileset> {
+        self.elements.iter().filter_map(|e| match e {
+            TiledMapElement::Tileset(tileset) => Some(tileset),
+            _ => None,
+        })
+    }
+
+    #[inline]
+    pub fn tilesets_mut(&mut self) -> impl Iterator<Item = &mut TiledTileset> {
+        self.elements.iter_mut().filter_map(|e| match e {
+            TiledMapElement::Tileset(tileset) => Some(tileset),
+            _ => None,
+        })
+    }
+
+    /// Get the tileset which the `gid` belongs to.
+    pub fn get_tileset(&self, gid: u32) -> Option<&TiledTileset> {
+        let gid = gid & GID_MASK;
+        self.tilesets()
+            .filter(|tileset| tileset.first_gid <= gid)
+            .max_by_key(|tileset| tileset.first_gid)
+    }
+
+    /// Collect all the tile layers from bottom to top, including those in groups.
+    ///
+    /// The offsets, opacities and visibilities of groups are applied to their children.
+    pub fn tile_layers(&self) -> Vec<layer::TiledTileLayer> {
+        let mut layers = Vec::new();
+        TiledMapElement::collect_tile_layers(&self.elements, Vec2::ZERO, 1., &mut layers);
+        layers
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiledMapElement {
+    Tileset(TiledTileset),
+    Layer(layer::TiledTileLayer),
+    Group(layer::TiledGroupLayer),
+    #[serde(other)]
+    Other,
+}
+
+impl TiledMapElement {
+    fn collect_tile_layers(
+        elements: &[TiledMapElement],
+        offset: Vec2,
+        opacity: f32,
+        layers: &mut Vec<layer::TiledTileLayer>,
+    ) {
+        elements.iter().for_each(|e| match e {
+            TiledMapElement::Layer(layer) => {
+                if !layer.visible {
+                    return;
+                }
+                let mut layer = layer.clone();
+                layer.offset_x += offset.x;
+                layer.offset_y += offset.y;
+                layer.opacity *= opacity;
+                layers.push(layer);
+            }
+            TiledMapElement::Group(group) => {
+                if !group.visible {
+                    return;
+                }
+                Self::collect_tile_layers(
+                    &group.elements,
+                    offset + Vec2::new(group.offset_x, group.offset_y),
+                    opacity * group.opacity,
+                    layers,
+                );
+            }
+            _ => {}
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Reflect)]
+#[serde(rename_all = "lowercase")]
+pub enum MapOrientation {
+    Orthogonal,
+    Isometric,
+    Staggered,
+    Hexagonal,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Reflect)]
+#[serde(rename_all = "lowercase")]
+pub enum StaggerAxis {
+    X,
+    #[default]
+    Y,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Reflect)]
+#[serde(rename_all = "lowercase")]
+pub enum StaggerIndex {
+    #[default]
+    Odd,
+    Even,
+}
+
+impl From<StaggerIndex> for TilemapStagger {
+    fn from(value: StaggerIndex) -> Self {
+        match value {
+            StaggerIndex::Odd => TilemapStagger::Odd,
+            StaggerIndex::Even => TilemapStagger::Even,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::math::UVec2;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledTileset {
+    /// The first global tile id of this tileset.
+    ///
+    /// This is `0` in `.tsx` files, and will be assigned after the
+    /// tileset is referenced in a map.
+    #[serde(rename = "@firstgid", default)]
+    pub first_gid: u32,
+    /// If this tileset is stored in an external `.tsx` file,
+    /// this is the path to that file, relative to the map.
+    #[serde(rename = "@source", default)]
+    pub source: Option<String>,
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "@tilewidth", default)]
+    pub tile_width: u32,
+    #[serde(rename = "@tileheight", default)]
+    pub tile_height: u32,
+    #[serde(rename = "@tilecount", default)]
+    pub tile_count: u32,
+    #[serde(rename = "@columns", default)]
+    pub columns: u32,
+    /// Spacing between tiles in pixels.
+    #[serde(rename = "@spacing", default)]
+    pub spacing: u32,
+    /// Margin around the tiles in pixels.
+    #[serde(rename = "@margin", default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub image: Option<TiledImage>,
+    #[serde(rename = "tile", default)]
+    pub tiles: Vec<TiledTile>,
+}
+
+impl TiledTileset {
+    #[inline]
+    pub fn tile_size(&self) -> UVec2 {
+        UVec2::new(self.tile_width, self.tile_height)
+    }
+
+    /// The size of the area which is covered by tiles in pixels.
+    ///
+    /// This can be smaller than the actual image if the tiles can't fill the whole image.
+    pub fn tiled_size(&self) -> UVec2 {
+        let columns = self.columns.max(1);
+        UVec2::new(
+            columns * self.tile_width,
+            self.tile_count.div_ceil(columns) * self.tile_height,
+        )
+    }
+
+    #[inline]
+    pub fn get_animation(&self, local_id: u32) -> Option<&TiledAnimation> {
+        self.tiles
+            .iter()
+            .find(|tile| tile.id == local_id)
+            .and_then(|tile| tile.animation.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledImage {
+    /// Path to the image, relative to the file that defines the tileset.
+    #[serde(rename = "@source")]
+    pub source: String,
+    #[serde(rename = "@width", default)]
+    pub width: u32,
+    #[serde(rename = "@height", default)]
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledTile {
+    /// The local id of the tile within the tileset.
+    #[serde(rename = "@id")]
+    pub id: u32,
+    #[serde(default)]
+    pub animation: Option<TiledAnimation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledAnimation {
+    #[serde(rename = "frame", default)]
+    pub frames: Vec<TiledFrame>,
+}
+
+impl TiledAnimation {
+    /// As animations in this crate have a fixed frame rate, the average duration
+    /// of frames is used to calculate the fps.
+    pub fn fps(&self) -> u32 {
+        let total = self.frames.iter().map(|f| f.duration).sum::<u32>();
+        if total == 0 {
+            return 1;
+        }
+        ((1000 * self.frames.len() as u32) as f32 / total as f32)
+            .round()
+            .max(1.) as u32
+    }
+
+    #[inline]
+    pub fn sequence(&self) -> Vec<u32> {
+        self.frames.iter().map(|f| f.tile_id).collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledFrame {
+    /// The local id of the tile within the tileset.
+    #[serde(rename = "@tileid")]
+    pub tile_id: u32,
+    /// Duration of this frame in milliseconds.
+    #[serde(rename = "@duration")]
+    pub duration: u32,
+}
@@ -1,4 +1,4 @@
-use bevy::{ecs::component::Component, math::IVec2, reflect::Reflect};
+use bevy::{ecs::component::Component, math::IVec2, reflect::Reflect, utils::HashMap};
 
 use crate::{
     math::TileArea,
@@ -16,22 +16,26 @@ pub struct PathTile {
 
 impl Tiles for PathTile {}
 
-#[derive(Component, Debug, Clone, Reflect)]
+#[derive(Component, Debug, Default, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathTilemap {
     pub(crate) storage: PathTileChunkedStorage,
+    /// How many times each chunk has been modified.
+    /// Used to find the outdated chunks in the data built from this tilemap.
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serializing", serde(skip))]
+    pub(crate) versions: HashMap<IVec2, u32>,
 }
 
 impl PathTilemap {
     pub fn new() -> Self {
-        Self {
-            storage: ChunkedStorage::default(),
-        }
+        Self::default()
     }
 
     pub fn new_with_chunk_size(chunk_size: u32) -> Self {
         Self {
             storage: ChunkedStorage::new(chunk_size),
+            ..Default::default()
         }
     }
 
@@ -40,17 +44,43 @@ impl PathTilemap {
     }
 
     pub fn get_mut(&mut self, index: IVec2) -> Option<&mut PathTile> {
+        self.mark_modified(index);
         self.storage.get_elem_mut(index)
     }
 
     pub fn set(&mut self, index: IVec2, tile: PathTile) {
+        self.mark_modified(index);
         self.storage.set_elem(index, tile)
     }
 
     pub fn remove(&mut self, index: IVec2) -> Option<PathTile> {
+        self.mark_modified(index);
         self.storage.remove_elem(index)
     }
 
+    pub fn remove_chunk(&mut self, chunk_index: IVec2) -> Option<Vec<Option<PathTile>>> {
+        let version = self.versions.entry(chunk_index).or_default();
+        *version = version.wrapping_add(1);
+        self.storage.remove_chunk(chunk_index)
+    }
+
+    #[inline]
+    pub fn chunk_size(&self) -> u32 {
+        self.storage.chunk_size
+    }
+
+    /// The version of the chunk, which increases every time the chunk is modified.
+    #[inline]
+    pub fn get_chunk_version(&self, chunk_index: IVec2) -> u32 {
+        self.versions.get(&chunk_index).copied().unwrap_or_default()
+    }
+
+    fn mark_modified(&mut self, index: IVec2) {
+        let chunk_index = self.storage.transform_index(index).0;
+        let version = self.versions.entry(chunk_index).or_default();
+        *version = version.wrapping_add(1);
+    }
+
     /// Set path-finding data using a custom function.
     pub fn fill_path_rect_custom(
         &mut self,
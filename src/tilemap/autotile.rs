@@ -0,0 +1,494 @@
+//! Autotiling, or "Wang tiling".
+//!
+//! Register terrains to `TilemapAutoTiles` and paint them using `TilemapAutoTiles::set`.
+//! The texture of the painted tile and its neighbours will be chosen automatically.
+//!
+//! Only square and isometric tilemaps are supported.
+
+use bevy::{
+    ecs::{component::Component, system::Commands},
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+    utils::HashSet,
+};
+
+use crate::math::TileArea;
+
+use super::{
+    chunking::storage::ChunkedStorage,
+    map::TilemapStorage,
+    tile::{LayerUpdater, TileBuilder, TileLayer, TileLayerPosition, TileUpdater},
+};
+
+pub const NEIGHBOUR_N: u8 = 1 << 0;
+pub const NEIGHBOUR_NE: u8 = 1 << 1;
+pub const NEIGHBOUR_E: u8 = 1 << 2;
+pub const NEIGHBOUR_SE: u8 = 1 << 3;
+pub const NEIGHBOUR_S: u8 = 1 << 4;
+pub const NEIGHBOUR_SW: u8 = 1 << 5;
+pub const NEIGHBOUR_W: u8 = 1 << 6;
+pub const NEIGHBOUR_NW: u8 = 1 << 7;
+
+/// The offsets of the neighbours, in the same order as the bits of the neighbour mask.
+const NEIGHBOUR_OFFSETS: [IVec2; 8] = [
+    IVec2::new(0, 1),
+    IVec2::new(1, 1),
+    IVec2::new(1, 0),
+    IVec2::new(1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(-1, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, 1),
+];
+
+/// Remove the corners that are not surrounded by both of the adjacent edges.
+pub const fn reduce_neighbour_mask(mask: u8) -> u8 {
+    let mut result = mask & (NEIGHBOUR_N | NEIGHBOUR_E | NEIGHBOUR_S | NEIGHBOUR_W);
+    if mask & NEIGHBOUR_NE != 0 && mask & NEIGHBOUR_N != 0 && mask & NEIGHBOUR_E != 0 {
+        result |= NEIGHBOUR_NE;
+    }
+    if mask & NEIGHBOUR_SE != 0 && mask & NEIGHBOUR_S != 0 && mask & NEIGHBOUR_E != 0 {
+        result |= NEIGHBOUR_SE;
+    }
+    if mask & NEIGHBOUR_SW != 0 && mask & NEIGHBOUR_S != 0 && mask & NEIGHBOUR_W != 0 {
+        result |= NEIGHBOUR_SW;
+    }
+    if mask & NEIGHBOUR_NW != 0 && mask & NEIGHBOUR_N != 0 && mask & NEIGHBOUR_W != 0 {
+        result |= NEIGHBOUR_NW;
+    }
+    result
+}
+
+/// All the 47 valid neighbour masks of a blob tileset, in ascending order.
+///
+/// The `i`th texture of `AutoTileRule::Blob47` is used for the tiles with the mask `BLOB_47_MASKS[i]`.
+pub const BLOB_47_MASKS: [u8; 47] = {
+    let mut masks = [0; 47];
+    let mut mask = 0;
+    let mut count = 0;
+    while mask < 256 {
+        if reduce_neighbour_mask(mask as u8) == mask as u8 {
+            masks[count] = mask as u8;
+            count += 1;
+        }
+        mask += 1;
+    }
+    masks
+};
+
+/// Maps a reduced neighbour mask to its position in `BLOB_47_MASKS`.
+const BLOB_47_INDICES: [u8; 256] = {
+    let mut indices = [0; 256];
+    let mut i = 0;
+    while i < 47 {
+        indices[BLOB_47_MASKS[i] as usize] = i as u8;
+        i += 1;
+    }
+    indices
+};
+
+/// Defines how to choose the textures for a terrain.
+///
+/// The "north" is the `+y` direction of the tilemap.
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum AutoTileRule {
+    /// The blob tileset, which takes all the 8 neighbours into account.
+    /// A corner neighbour only counts when both of the adjacent edge neighbours are the same terrain.
+    ///
+    /// `textures` should contain 47 texture indices. See `BLOB_47_MASKS` for the order.
+    Blob47 { textures: Vec<u32> },
+    /// The 16-tile edge Wang set, which takes the 4 edge neighbours into account.
+    ///
+    /// `textures` should contain 16 texture indices,
+    /// indexed by the mask of N = 1, E = 2, S = 4 and W = 8.
+    WangEdge { textures: Vec<u32> },
+    /// The 16-tile corner Wang set. A corner is filled when the tiles around it
+    /// are all the same terrain.
+    ///
+    /// `textures` should contain 16 texture indices,
+    /// indexed by the mask of NE = 1, SE = 2, SW = 4 and NW = 8.
+    WangCorner { textures: Vec<u32> },
+    /// The RPG Maker style autotile, which composes every tile from 4 sub-tiles.
+    ///
+    /// The tilemap should use a texture that is sliced into sub-tiles
+    /// and have half of the slot size, as every terrain tile occupies 2x2 tiles
+    /// of the tilemap. So terrain tile `[x, y]` is rendered at `[2x, 2y]` to `[2x + 1, 2y + 1]`.
+    ///
+    /// The autotile itself is 2 tiles wide and 3 tiles tall, same as RPG Maker:
+    /// the top right tile contains the inner corners, and the bottom 2x2 tiles
+    /// are an enclosed area which provides the outer corners, edges and the fill.
+    SubTile {
+        /// The top left sub-tile of the autotile, in sub-tiles.
+        origin: UVec2,
+        /// The number of sub-tiles in a row of the texture.
+        columns: u32,
+    },
+}
+
+impl AutoTileRule {
+    fn validate(&self) {
+        let (len, expected) = match self {
+            AutoTileRule::Blob47 { textures } => (textures.len(), 47),
+            AutoTileRule::WangEdge { textures } | AutoTileRule::WangCorner { textures } => {
+                (textures.len(), 16)
+            }
+            AutoTileRule::SubTile { .. } => return,
+        };
+        assert_eq!(
+            len, expected,
+            "Expected {} textures for {:?} but found {}!",
+            expected, self, len
+        );
+    }
+
+    /// Returns the texture indices of the tile with the given neighbour mask,
+    /// along with the tile index offsets relative to `[2x, 2y]` for `AutoTileRule::SubTile`.
+    fn get_textures(&self, mask: u8) -> Vec<(IVec2, u32)> {
+        let mask = reduce_neighbour_mask(mask);
+        match self {
+            AutoTileRule::Blob47 { textures } => {
+                vec![(
+                    IVec2::ZERO,
+                    textures[BLOB_47_INDICES[mask as usize] as usize],
+                )]
+            }
+            AutoTileRule::WangEdge { textures } => {
+                let edges = [NEIGHBOUR_N, NEIGHBOUR_E, NEIGHBOUR_S, NEIGHBOUR_W];
+                vec![(IVec2::ZERO, textures[to_sub_mask(mask, edges)])]
+            }
+            AutoTileRule::WangCorner { textures } => {
+                let corners = [NEIGHBOUR_NE, NEIGHBOUR_SE, NEIGHBOUR_SW, NEIGHBOUR_NW];
+                vec![(IVec2::ZERO, textures[to_sub_mask(mask, corners)])]
+            }
+            AutoTileRule::SubTile { origin, columns } => [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .into_iter()
+                .map(|(qx, qy)| {
+                    let sub = get_sub_tile(mask, qx, qy) + *origin;
+                    // The texture goes downwards while the tilemap goes upwards.
+                    (
+                        IVec2::new(qx as i32, 1 - qy as i32),
+                        sub.y * columns + sub.x,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+fn to_sub_mask(mask: u8, bits: [u8; 4]) -> usize {
+    bits.into_iter()
+        .enumerate()
+        .filter(|(_, bit)| mask & bit != 0)
+        .fold(0, |acc, (i, _)| acc | (1 << i))
+}
+
+/// Choose the sub-tile for the quarter `[qx, qy]`, relative to the origin of the autotile.
+/// `qy = 0` means the upper quarter.
+fn get_sub_tile(mask: u8, qx: u32, qy: u32) -> UVec2 {
+    let (horizontal, vertical, diagonal) = match (qx, qy) {
+        (0, 0) => (NEIGHBOUR_W, NEIGHBOUR_N, NEIGHBOUR_NW),
+        (1, 0) => (NEIGHBOUR_E, NEIGHBOUR_N, NEIGHBOUR_NE),
+        (0, _) => (NEIGHBOUR_W, NEIGHBOUR_S, NEIGHBOUR_SW),
+        _ => (NEIGHBOUR_E, NEIGHBOUR_S, NEIGHBOUR_SE),
+    };
+    let horizontal = mask & horizontal != 0;
+    let vertical = mask & vertical != 0;
+
+    if horizontal && vertical && mask & diagonal == 0 {
+        return UVec2::new(2 + qx, qy);
+    }
+
+    let x = match (qx, horizontal) {
+        (0, true) => 2,
+        (0, false) => 0,
+        (_, true) => 1,
+        (_, false) => 3,
+    };
+    let y = match (qy, vertical) {
+        (0, true) => 2,
+        (0, false) => 0,
+        (_, true) => 1,
+        (_, false) => 3,
+    };
+    UVec2::new(x, y + 2)
+}
+
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoTileTerrain {
+    pub rule: AutoTileRule,
+    /// The tile layer that the textures are written to.
+    pub layer: usize,
+}
+
+/// Stores the terrains of a tilemap.
+/// Insert this component to the tilemap entity.
+#[derive(Component, Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapAutoTiles {
+    pub(crate) terrains: Vec<AutoTileTerrain>,
+    pub(crate) storage: ChunkedStorage<u32>,
+}
+
+impl Default for TilemapAutoTiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TilemapAutoTiles {
+    pub fn new() -> Self {
+        Self {
+            terrains: Vec::new(),
+            storage: ChunkedStorage::default(),
+        }
+    }
+
+    /// Register a terrain so you can paint it using `TilemapAutoTiles::set`.
+    /// Returns the id of the terrain.
+    ///
+    /// The textures will be written to the tile layer `layer`.
+    pub fn register_terrain(&mut self, rule: AutoTileRule, layer: usize) -> u32 {
+        rule.validate();
+        self.terrains.push(AutoTileTerrain { rule, layer });
+        self.terrains.len() as u32 - 1
+    }
+
+    #[inline]
+    pub fn get_terrain(&self, terrain: u32) -> Option<&AutoTileTerrain> {
+        self.terrains.get(terrain as usize)
+    }
+
+    /// Get the terrain at the index.
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<u32> {
+        self.storage.get_elem(index).copied()
+    }
+
+    /// Paint a terrain at the index, and update the textures of it and its neighbours.
+    pub fn set(
+        &mut self,
+        commands: &mut Commands,
+        storage: &mut TilemapStorage,
+        index: IVec2,
+        terrain: u32,
+    ) {
+        self.set_terrain(commands, storage, index, terrain);
+        self.refresh(commands, storage, Self::with_neighbours(index));
+    }
+
+    /// Erase the terrain at the index, and update the textures of its neighbours.
+    pub fn remove(&mut self, commands: &mut Commands, storage: &mut TilemapStorage, index: IVec2) {
+        let Some(terrain) = self.storage.remove_elem(index) else {
+            return;
+        };
+
+        let terrain = &self.terrains[terrain as usize];
+        let layer = terrain.layer;
+        for (offset, _) in terrain.rule.get_textures(0) {
+            Self::write_texture(
+                commands,
+                storage,
+                self.tile_index(terrain, index) + offset,
+                layer,
+                TileLayer::new(),
+            );
+        }
+        self.refresh(commands, storage, Self::with_neighbours(index));
+    }
+
+    /// Paint a terrain in a rectangle area.
+    pub fn fill_rect(
+        &mut self,
+        commands: &mut Commands,
+        storage: &mut TilemapStorage,
+        area: TileArea,
+        terrain: u32,
+    ) {
+        let mut dirty = HashSet::with_capacity(area.size());
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                let index = IVec2 { x, y };
+                self.set_terrain(commands, storage, index, terrain);
+                dirty.extend(Self::with_neighbours(index));
+            }
+        }
+        self.refresh(commands, storage, dirty);
+    }
+
+    fn set_terrain(
+        &mut self,
+        commands: &mut Commands,
+        storage: &mut TilemapStorage,
+        index: IVec2,
+        terrain: u32,
+    ) {
+        // Clear the old textures as the new terrain may use another layer.
+        if self.get(index).is_some_and(|t| t != terrain) {
+            self.remove(commands, storage, index);
+        }
+        self.storage.set_elem(index, terrain);
+    }
+
+    #[inline]
+    fn with_neighbours(index: IVec2) -> impl Iterator<Item = IVec2> {
+        std::iter::once(index).chain(NEIGHBOUR_OFFSETS.into_iter().map(move |o| index + o))
+    }
+
+    #[inline]
+    fn tile_index(&self, terrain: &AutoTileTerrain, index: IVec2) -> IVec2 {
+        match terrain.rule {
+            AutoTileRule::SubTile { .. } => index * 2,
+            _ => index,
+        }
+    }
+
+    fn neighbour_mask(&self, index: IVec2, terrain: u32) -> u8 {
+        NEIGHBOUR_OFFSETS
+            .into_iter()
+            .enumerate()
+            .filter(|(_, offset)| self.get(index + *offset) == Some(terrain))
+            .fold(0, |mask, (bit, _)| mask | (1 << bit))
+    }
+
+    fn refresh(
+        &self,
+        commands: &mut Commands,
+        storage: &mut TilemapStorage,
+        indices: impl IntoIterator<Item = IVec2>,
+    ) {
+        for index in indices {
+            let Some(terrain_id) = self.get(index) else {
+                continue;
+            };
+
+            let terrain = &self.terrains[terrain_id as usize];
+            let origin = self.tile_index(terrain, index);
+            for (offset, texture) in terrain
+                .rule
+                .get_textures(self.neighbour_mask(index, terrain_id))
+            {
+                Self::write_texture(
+                    commands,
+                    storage,
+                    origin + offset,
+                    terrain.layer,
+                    TileLayer::new().with_texture_index(texture),
+                );
+            }
+        }
+    }
+
+    fn write_texture(
+        commands: &mut Commands,
+        storage: &mut TilemapStorage,
+        index: IVec2,
+        layer_index: usize,
+        layer: TileLayer,
+    ) {
+        if storage.get(index).is_some() {
+            storage.update(
+                commands,
+                index,
+                TileUpdater {
+                    layers: vec![LayerUpdater {
+                        position: TileLayerPosition::Index(layer_index),
+                        layer,
+                    }],
+                    ..Default::default()
+                },
+            );
+        } else if layer.texture_index >= 0 {
+            storage.set(
+                commands,
+                index,
+                TileBuilder::new().with_layer(layer_index, layer),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            entity::Entity,
+            system::{CommandQueue, RunSystemOnce},
+            world::World,
+        },
+        tasks::{ComputeTaskPool, TaskPool},
+    };
+
+    use crate::tilemap::tile::{tile_updater, Tile, TileTexture};
+
+    use super::*;
+
+    #[test]
+    fn test_blob_47() {
+        assert_eq!(BLOB_47_MASKS[0], 0);
+        assert_eq!(BLOB_47_MASKS[46], u8::MAX);
+        assert!(BLOB_47_MASKS.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            BLOB_47_INDICES[reduce_neighbour_mask(NEIGHBOUR_NE) as usize],
+            0
+        );
+    }
+
+    #[test]
+    fn test_sub_tile() {
+        // isolated tile uses the outer corners of the enclosed area
+        assert_eq!(get_sub_tile(0, 0, 0), UVec2::new(0, 2));
+        assert_eq!(get_sub_tile(0, 1, 1), UVec2::new(3, 5));
+        // surrounded tile uses the fill
+        assert_eq!(get_sub_tile(u8::MAX, 0, 0), UVec2::new(2, 4));
+        assert_eq!(get_sub_tile(u8::MAX, 1, 1), UVec2::new(1, 3));
+        // inner corner
+        let mask = reduce_neighbour_mask(u8::MAX & !NEIGHBOUR_NW);
+        assert_eq!(get_sub_tile(mask, 0, 0), UVec2::new(2, 0));
+    }
+
+    #[test]
+    fn test_change_terrain_layer() {
+        // `tile_updater` iterates in parallel.
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let mut world = World::new();
+        let mut storage = TilemapStorage::new(16, Entity::PLACEHOLDER);
+        let mut auto_tiles = TilemapAutoTiles::new();
+        let grass = auto_tiles.register_terrain(
+            AutoTileRule::WangEdge {
+                textures: (0..16).collect(),
+            },
+            0,
+        );
+        let water = auto_tiles.register_terrain(
+            AutoTileRule::WangEdge {
+                textures: (100..116).collect(),
+            },
+            1,
+        );
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        auto_tiles.set(&mut commands, &mut storage, IVec2::ZERO, grass);
+        auto_tiles.set(&mut commands, &mut storage, IVec2::X, grass);
+        queue.apply(&mut world);
+        world.run_system_once(tile_updater);
+
+        // Clearing the grass and painting the water update the same tile in one frame.
+        let mut commands = Commands::new(&mut queue, &world);
+        auto_tiles.set(&mut commands, &mut storage, IVec2::ZERO, water);
+        queue.apply(&mut world);
+        world.run_system_once(tile_updater);
+
+        let layers = |index: IVec2| {
+            let tile = world.get::<Tile>(storage.get(index).unwrap()).unwrap();
+            let TileTexture::Static(layers) = &tile.texture else {
+                unreachable!();
+            };
+            layers.iter().map(|l| l.texture_index).collect::<Vec<_>>()
+        };
+        assert_eq!(layers(IVec2::ZERO), vec![-1, 100]);
+        assert_eq!(layers(IVec2::X), vec![0]);
+    }
+}
@@ -5,8 +5,8 @@ use bevy::{
 };
 
 use super::map::{
-    TilePivot, TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
-    TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTransform, TilemapType,
+    TilePivot, TileRenderSize, TilemapAnimationPlayback, TilemapAnimations, TilemapLayerOpacities,
+    TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTransform, TilemapType,
 };
 
 /// The bundle of the tilemap with no actual tiles.
@@ -52,9 +52,7 @@ pub struct TilemapBundle {
     pub ty: TilemapType,
     /// The pivot of the tiles.
     pub tile_pivot: TilePivot,
-    /// The opacities of each **rendered** layer.
-    ///
-    /// Only the top 4 layers will be rendered.
+    /// The opacities of each layer.
     pub layer_opacities: TilemapLayerOpacities,
     /// The storage of the tilemap. The entities of each tiles are divided into chunks and stored in it.
     ///
@@ -63,11 +61,15 @@ pub struct TilemapBundle {
     /// The transform of the tilemap. It's not the same one as `Transform`.
     /// If you want to move or rotate the tilemap, you need to change this.
     /// Modify the `Transform` component will not work.
+    ///
+    /// If the tilemap has a parent, this is relative to the parent.
     pub tilemap_transform: TilemapTransform,
     /// The texture of the tilemap.
     pub texture: TilemapTexture,
     /// All the animation sequences of the tilemap.
     pub animations: TilemapAnimations,
+    /// The playback state of the animations.
+    pub animation_playback: TilemapAnimationPlayback,
     /// Just to make sure the child sprites are correctly rendered.
     pub visibility: Visibility,
     /// Just to make sure the child sprites are correctly rendered.
@@ -76,7 +78,7 @@ pub struct TilemapBundle {
     pub view_visibility: ViewVisibility,
     /// Modify `TilemapTransform` instead of this one.
     pub transform: Transform,
-    /// Just to make sure the child sprites are correctly rendered.
+    /// The final transform used for rendering, culling and physics.
     pub global_transform: GlobalTransform,
 }
 
@@ -127,9 +129,7 @@ pub struct PureColorTilemapBundle {
     pub ty: TilemapType,
     /// The pivot of the tiles.
     pub tile_pivot: TilePivot,
-    /// The opacities of each **rendered** layer.
-    ///
-    /// Only the top 4 layers will be rendered.
+    /// The opacities of each layer.
     pub layer_opacities: TilemapLayerOpacities,
     /// The storage of the tilemap. The entities of each tiles are divided into chunks and stored in it.
     ///
@@ -138,6 +138,8 @@ pub struct PureColorTilemapBundle {
     /// The transform of the tilemap. It's not the same one as `Transform`.
     /// If you want to move or rotate the tilemap, you need to change this.
     /// Modify the `Transform` component will not work.
+    ///
+    /// If the tilemap has a parent, this is relative to the parent.
     pub tilemap_transform: TilemapTransform,
     /// Just to make sure the child sprites are correctly rendered.
     pub visibility: Visibility,
@@ -147,6 +149,6 @@ pub struct PureColorTilemapBundle {
     pub view_visibility: ViewVisibility,
     /// Modify `TilemapTransform` instead of this one.
     pub transform: Transform,
-    /// Just to make sure the child sprites are correctly rendered.
+    /// The final transform used for rendering, culling and physics.
     pub global_transform: GlobalTransform,
 }
@@ -137,6 +137,13 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
         chunk_index * isize + idx
     }
 
+    /// Move all the elements into chunks of another size.
+    pub fn rechunk(&mut self, chunk_size: u32) {
+        if self.chunk_size != chunk_size {
+            *self = Self::from_mapper(std::mem::take(self).into_mapper(), Some(chunk_size));
+        }
+    }
+
     pub fn into_mapper(mut self) -> HashMap<IVec2, T> {
         let mut mapper = HashMap::new();
         self.chunks.drain().for_each(|(chunk_index, chunk)| {
@@ -1,6 +1,13 @@
-use bevy::math::{IVec2, UVec2, Vec2};
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{Query, SystemParam},
+    },
+    math::{IVec2, UVec2, Vec2},
+    transform::components::GlobalTransform,
+};
 
-use super::map::{TilemapTransform, TilemapType};
+use super::map::{TilePivot, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType};
 
 /// Get the world position of the center of a slot.
 pub fn index_to_world(
@@ -10,6 +17,12 @@ pub fn index_to_world(
     pivot: Vec2,
     slot_size: Vec2,
 ) -> Vec2 {
+    let shift = match ty {
+        TilemapType::StaggeredIsometric(stagger) | TilemapType::StaggeredHexagonal(_, stagger) => {
+            stagger.get_shift(index.y) as f32 / 2.
+        }
+        _ => 0.,
+    };
     let index = index.as_vec2();
     transform.transform_point({
         match ty {
@@ -26,10 +39,88 @@ pub fn index_to_world(
                 x: slot_size.x * (index.x - 0.5 * index.y - pivot.x),
                 y: (slot_size.y + *legs as f32) / 2. * (index.y - pivot.y),
             },
+            TilemapType::StaggeredIsometric(_) => {
+                (Vec2 {
+                    x: index.x + shift,
+                    y: index.y / 2.,
+                } - pivot)
+                    * slot_size
+            }
+            TilemapType::StaggeredHexagonal(legs, _) => Vec2 {
+                x: slot_size.x * (index.x + shift - pivot.x),
+                y: (slot_size.y + *legs as f32) / 2. * (index.y - pivot.y),
+            },
         }
     })
 }
 
+/// Get the index of the slot that contains the world position.
+///
+/// This is the inverse of `index_to_world`. The slot of a tile is the area
+/// covered by its collider, which starts at `index_to_world` and has the size of `slot_size`.
+pub fn world_to_index(
+    world: Vec2,
+    ty: &TilemapType,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+) -> IVec2 {
+    // Relative to the bottom left of the slot at [0, 0].
+    let origin = index_to_world(
+        IVec2::ZERO,
+        ty,
+        &TilemapTransform::IDENTITY,
+        pivot,
+        slot_size,
+    );
+    let local = transform.inverse_transform_point(world) - origin;
+
+    // Staggered tilemaps have the same shapes relative to the slot at [0, 0],
+    // only the indices are different.
+    let index = match ty.unstaggered() {
+        TilemapType::Square => (local / slot_size).floor().as_ivec2(),
+        TilemapType::Isometric => {
+            let rel = (local - slot_size / 2.) / slot_size;
+            Vec2 {
+                x: rel.x + rel.y,
+                y: rel.y - rel.x,
+            }
+            .round()
+            .as_ivec2()
+        }
+        TilemapType::Hexagonal(legs) => {
+            let rel = local - slot_size / 2.;
+            let row_height = (slot_size.y + legs as f32) / 2.;
+            let half_size = slot_size / 2.;
+            let half_leg = legs as f32 / 2.;
+
+            // The point is between the centers of these two rows,
+            // so it must be inside the closest hexagon of one of them.
+            let lower = (rel.y / row_height).floor() as i32;
+            [lower, lower + 1]
+                .into_iter()
+                .map(|y| {
+                    let x = (rel.x / slot_size.x + 0.5 * y as f32).round() as i32;
+                    let d = (rel
+                        - Vec2 {
+                            x: slot_size.x * (x as f32 - 0.5 * y as f32),
+                            y: row_height * y as f32,
+                        })
+                    .abs();
+                    let edge = half_leg
+                        + (half_size.y - half_leg) * (1. - d.x / half_size.x.max(f32::EPSILON));
+                    (IVec2 { x, y }, d.y - edge)
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap()
+                .0
+        }
+        _ => unreachable!(),
+    };
+
+    ty.from_unstaggered_index(index)
+}
+
 pub fn index_to_rel(
     index: IVec2,
     ty: &TilemapType,
@@ -47,25 +138,49 @@ pub fn get_tile_collider(
     transform: &TilemapTransform,
     pivot: Vec2,
 ) -> Vec<Vec2> {
+    get_local_tile_collider(ty, slot_size, size, pivot)
+        .into_iter()
+        .map(|v| transform.transform_point(v))
+        .collect()
+}
+
+/// The collider without the tilemap transform applied.
+///
+/// The tiles in staggered tilemaps have the same shape as the unstaggered ones,
+/// but only a single tile, or a row of hexagonal tiles can be covered.
+fn get_local_tile_collider(
+    ty: &TilemapType,
+    slot_size: Vec2,
+    size: UVec2,
+    pivot: Vec2,
+) -> Vec<Vec2> {
+    let transform = &TilemapTransform::IDENTITY;
+    if ty.unstaggered() != *ty {
+        let offset = index_to_world(IVec2::ZERO, ty, transform, pivot, slot_size)
+            - index_to_world(IVec2::ZERO, &ty.unstaggered(), transform, pivot, slot_size);
+        return get_local_tile_collider(&ty.unstaggered(), slot_size, size, pivot)
+            .into_iter()
+            .map(|v| v + offset)
+            .collect();
+    }
+
     let size = size.as_ivec2();
     match ty {
-        TilemapType::Square => {
-            let left_down = index_to_world(IVec2::ZERO, ty, transform, pivot, slot_size);
-            let up_right = index_to_world(size, ty, transform, pivot, slot_size);
-
-            vec![
-                left_down,
-                Vec2::new(up_right.x, left_down.y),
-                up_right,
-                Vec2::new(left_down.x, up_right.y),
-            ]
-        }
+        TilemapType::Square => [
+            IVec2::ZERO,
+            IVec2::new(size.x, 0),
+            size,
+            IVec2::new(0, size.y),
+        ]
+        .into_iter()
+        .map(|corner| index_to_world(corner, ty, transform, pivot, slot_size))
+        .collect(),
         TilemapType::Isometric => {
             let down = index_to_world(IVec2::ZERO, ty, transform, pivot, slot_size);
             let up = index_to_world(size, ty, transform, pivot, slot_size);
             let left = index_to_world(IVec2::new(0, size.y), ty, transform, pivot, slot_size);
             let right = index_to_world(IVec2::new(size.x, 0), ty, transform, pivot, slot_size);
-            let offset = transform.apply_rotation(Vec2::new(slot_size.x / 2., 0.));
+            let offset = Vec2::new(slot_size.x / 2., 0.);
 
             vec![down + offset, up + offset, right + offset, left + offset]
         }
@@ -126,6 +241,7 @@ pub fn get_tile_collider(
             vertices.push(vertices[0]);
             vertices
         }
+        _ => unreachable!(),
     }
 }
 
@@ -137,9 +253,152 @@ pub fn get_tile_collider_world(
     pivot: Vec2,
     slot_size: Vec2,
 ) -> Vec<Vec2> {
-    let offset = index_to_rel(origin, ty, transform, pivot, slot_size) + pivot * slot_size;
-    get_tile_collider(ty, slot_size, size, transform, pivot)
+    let offset = index_to_world(origin, ty, &TilemapTransform::IDENTITY, pivot, slot_size)
+        - index_to_world(
+            IVec2::ZERO,
+            ty,
+            &TilemapTransform::IDENTITY,
+            pivot,
+            slot_size,
+        );
+    get_local_tile_collider(ty, slot_size, size, pivot)
         .into_iter()
-        .map(|v| v + offset)
+        .map(|v| transform.transform_point(v + offset))
         .collect()
 }
+
+/// Convert between world positions and tile indices of tilemaps.
+///
+/// The `GlobalTransform` of the tilemap is used, so this also works for tilemaps
+/// that are children of other entities.
+#[derive(SystemParam)]
+pub struct TilemapCoordinates<'w, 's> {
+    tilemaps_query: Query<
+        'w,
+        's,
+        (
+            &'static TilemapType,
+            &'static TilePivot,
+            &'static TilemapSlotSize,
+            &'static GlobalTransform,
+            Option<&'static TilemapStorage>,
+        ),
+    >,
+}
+
+impl<'w, 's> TilemapCoordinates<'w, 's> {
+    /// Get the index of the slot that contains the world position.
+    /// Returns `None` if the entity is not a tilemap.
+    pub fn world_to_index(&self, tilemap: Entity, world: Vec2) -> Option<IVec2> {
+        self.tilemaps_query
+            .get(tilemap)
+            .ok()
+            .map(|(ty, pivot, slot_size, transform, _)| {
+                world_to_index(
+                    world,
+                    ty,
+                    &TilemapTransform::from_global(transform),
+                    pivot.0,
+                    slot_size.0,
+                )
+            })
+    }
+
+    /// Get the world position of the center of the slot.
+    /// Returns `None` if the entity is not a tilemap.
+    pub fn index_to_world(&self, tilemap: Entity, index: IVec2) -> Option<Vec2> {
+        self.tilemaps_query
+            .get(tilemap)
+            .ok()
+            .map(|(ty, pivot, slot_size, transform, _)| {
+                let transform = TilemapTransform::from_global(transform);
+                let center =
+                    index_to_world(index, ty, &TilemapTransform::IDENTITY, pivot.0, slot_size.0)
+                        + slot_size.0 / 2.;
+                transform.transform_point(center)
+            })
+    }
+
+    /// Get the tile entity at the world position, if there's one.
+    pub fn get_tile(&self, tilemap: Entity, world: Vec2) -> Option<Entity> {
+        let index = self.world_to_index(tilemap, world)?;
+        self.tilemaps_query
+            .get(tilemap)
+            .ok()
+            .and_then(|(.., storage)| storage)
+            .and_then(|storage| storage.get(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, Vec2};
+
+    use crate::tilemap::map::{TilemapStagger, TilemapTransform, TilemapType};
+
+    use super::{index_to_world, world_to_index};
+
+    #[test]
+    fn test_world_to_index() {
+        let slot_size = Vec2::new(32., 24.);
+        let pivot = Vec2::new(0.5, 0.25);
+        let transform = TilemapTransform::from_translation(Vec2::new(30., -20.))
+            .with_rotation(0.7)
+            .with_scale(Vec2::new(1.5, 0.8));
+
+        for ty in [
+            TilemapType::Square,
+            TilemapType::Isometric,
+            TilemapType::Hexagonal(0),
+            TilemapType::Hexagonal(8),
+            TilemapType::Hexagonal(24),
+            TilemapType::StaggeredIsometric(TilemapStagger::Odd),
+            TilemapType::StaggeredIsometric(TilemapStagger::Even),
+            TilemapType::StaggeredHexagonal(8, TilemapStagger::Odd),
+            TilemapType::StaggeredHexagonal(8, TilemapStagger::Even),
+        ] {
+            for x in -5..5 {
+                for y in -5..5 {
+                    let index = IVec2 { x, y };
+                    let origin =
+                        index_to_world(index, &ty, &TilemapTransform::IDENTITY, pivot, slot_size);
+
+                    // Staggered tiles keep their positions relative to [0, 0].
+                    let unstaggered = ty.to_unstaggered_index(index);
+                    assert_eq!(ty.from_unstaggered_index(unstaggered), index);
+                    let relative = |index, ty| {
+                        index_to_world(index, &ty, &TilemapTransform::IDENTITY, pivot, slot_size)
+                            - index_to_world(
+                                IVec2::ZERO,
+                                &ty,
+                                &TilemapTransform::IDENTITY,
+                                pivot,
+                                slot_size,
+                            )
+                    };
+                    assert!(relative(index, ty)
+                        .abs_diff_eq(relative(unstaggered, ty.unstaggered()), 1e-3));
+                    let center = transform.transform_point(origin + slot_size / 2.);
+                    assert_eq!(
+                        world_to_index(center, &ty, &transform, pivot, slot_size),
+                        index
+                    );
+
+                    // Close to the edges of the slot.
+                    for offset in [
+                        Vec2::new(0., -0.45),
+                        Vec2::new(0., 0.45),
+                        Vec2::new(-0.45, 0.),
+                        Vec2::new(0.45, 0.),
+                    ] {
+                        let world = transform.transform_point(origin + slot_size * (offset + 0.5));
+                        assert_eq!(
+                            world_to_index(world, &ty, &transform, pivot, slot_size),
+                            index
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
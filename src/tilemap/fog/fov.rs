@@ -0,0 +1,182 @@
+//! Field of view calculations.
+//!
+//! Square and isometric tilemaps use the symmetric shadowcasting,
+//! see <https://www.albertford.com/shadowcasting/> for the details.
+//! Hexagonal tilemaps check the line of sight to every tile in the range instead.
+
+use bevy::{math::IVec2, utils::HashSet};
+
+use crate::{math::extension::HexIndex, tilemap::map::TilemapType};
+
+/// Calculate the tiles that are visible from `origin` within `radius`.
+///
+/// `is_opaque` returns if a tile blocks the sight. Opaque tiles themselves can be seen.
+pub fn compute_fov(
+    ty: TilemapType,
+    origin: IVec2,
+    radius: u32,
+    is_opaque: impl Fn(IVec2) -> bool,
+) -> HashSet<IVec2> {
+    // Staggered tilemaps have the same neighbours as the unstaggered ones.
+    if ty.unstaggered() != ty {
+        return compute_unstaggered_fov(
+            ty.unstaggered(),
+            ty.to_unstaggered_index(origin),
+            radius,
+            |i| is_opaque(ty.from_unstaggered_index(i)),
+        )
+        .into_iter()
+        .map(|i| ty.from_unstaggered_index(i))
+        .collect();
+    }
+
+    compute_unstaggered_fov(ty, origin, radius, is_opaque)
+}
+
+fn compute_unstaggered_fov(
+    ty: TilemapType,
+    origin: IVec2,
+    radius: u32,
+    is_opaque: impl Fn(IVec2) -> bool,
+) -> HashSet<IVec2> {
+    let mut visible = HashSet::new();
+    visible.insert(origin);
+
+    match ty {
+        TilemapType::Square | TilemapType::Isometric => {
+            for quadrant in [IVec2::Y, IVec2::X, IVec2::NEG_Y, IVec2::NEG_X] {
+                let mut caster = ShadowCaster {
+                    origin,
+                    quadrant,
+                    radius: radius as i32,
+                    is_opaque: &is_opaque,
+                    visible: &mut visible,
+                };
+                caster.scan(Row {
+                    depth: 1,
+                    start_slope: -1.,
+                    end_slope: 1.,
+                });
+            }
+        }
+        TilemapType::Hexagonal(_) => {
+            for target in origin.hex_range(radius) {
+                if target == origin {
+                    continue;
+                }
+
+                let line = origin.hex_line(target);
+                if line[1..line.len().saturating_sub(1)]
+                    .iter()
+                    .all(|t| !is_opaque(*t))
+                {
+                    visible.insert(target);
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    visible
+}
+
+struct Row {
+    depth: i32,
+    start_slope: f32,
+    end_slope: f32,
+}
+
+impl Row {
+    fn cols(&self) -> impl Iterator<Item = i32> {
+        let min_col = (self.depth as f32 * self.start_slope + 0.5).floor() as i32;
+        let max_col = (self.depth as f32 * self.end_slope - 0.5).ceil() as i32;
+        min_col..=max_col
+    }
+
+    fn next(&self) -> Self {
+        Self {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+
+    fn is_symmetric(&self, col: i32) -> bool {
+        let col = col as f32;
+        let depth = self.depth as f32;
+        col >= depth * self.start_slope && col <= depth * self.end_slope
+    }
+}
+
+fn slope(depth: i32, col: i32) -> f32 {
+    (2 * col - 1) as f32 / (2 * depth) as f32
+}
+
+struct ShadowCaster<'a, F: Fn(IVec2) -> bool> {
+    origin: IVec2,
+    /// The direction of the quadrant.
+    quadrant: IVec2,
+    radius: i32,
+    is_opaque: &'a F,
+    visible: &'a mut HashSet<IVec2>,
+}
+
+impl<'a, F: Fn(IVec2) -> bool> ShadowCaster<'a, F> {
+    fn transform(&self, depth: i32, col: i32) -> IVec2 {
+        // `perp` is the direction that `col` grows towards.
+        let perp = IVec2::new(self.quadrant.y, -self.quadrant.x);
+        self.origin + self.quadrant * depth + perp * col
+    }
+
+    fn scan(&mut self, mut row: Row) {
+        if row.depth > self.radius {
+            return;
+        }
+
+        let mut prev_opaque = None;
+        for col in row.cols() {
+            let tile = self.transform(row.depth, col);
+            let opaque = (self.is_opaque)(tile);
+
+            if (opaque || row.is_symmetric(col))
+                && row.depth * row.depth + col * col <= self.radius * self.radius
+            {
+                self.visible.insert(tile);
+            }
+            if prev_opaque == Some(true) && !opaque {
+                row.start_slope = slope(row.depth, col);
+            }
+            if prev_opaque == Some(false) && opaque {
+                let mut next = row.next();
+                next.end_slope = slope(row.depth, col);
+                self.scan(next);
+            }
+            prev_opaque = Some(opaque);
+        }
+
+        if prev_opaque == Some(false) {
+            self.scan(row.next());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_walls_block_sight() {
+        let wall = IVec2::new(2, 0);
+        let visible = compute_fov(TilemapType::Square, IVec2::ZERO, 5, |i| i == wall);
+        assert!(visible.contains(&IVec2::new(1, 0)));
+        assert!(visible.contains(&wall));
+        assert!(!visible.contains(&IVec2::new(3, 0)));
+        assert!(visible.contains(&IVec2::new(0, 5)));
+        assert!(!visible.contains(&IVec2::new(0, 6)));
+
+        let visible = compute_fov(TilemapType::Hexagonal(0), IVec2::ZERO, 3, |i| i == wall);
+        assert!(visible.contains(&wall));
+        assert!(!visible.contains(&IVec2::new(3, 0)));
+        assert!(visible.contains(&IVec2::new(3, 3)));
+        assert!(!visible.contains(&IVec2::new(3, -1)));
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::{
+    ecs::{
+        change_detection::DetectChangesMut, component::Component, query::Changed, system::Query,
+    },
+    math::{IVec2, Vec4},
+    reflect::Reflect,
+    utils::HashSet,
+};
+
+use crate::{math::TileArea, DEFAULT_CHUNK_SIZE};
+
+use super::{
+    chunking::storage::ChunkedStorage,
+    map::{TilemapStorage, TilemapType},
+};
+
+pub mod fov;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum FogState {
+    /// The tile has never been seen.
+    #[default]
+    Unexplored = 0,
+    /// The tile has been seen but it's not visible now.
+    Explored = 1,
+    Visible = 2,
+}
+
+/// The colors that are multiplied with the tiles in different states.
+#[derive(Debug, Clone, Copy, Reflect)]
+pub struct FogColors {
+    pub unexplored: Vec4,
+    pub explored: Vec4,
+}
+
+impl Default for FogColors {
+    fn default() -> Self {
+        Self {
+            unexplored: Vec4::ZERO,
+            explored: Vec4::new(0.5, 0.5, 0.5, 1.),
+        }
+    }
+}
+
+/// The fog of war of a tilemap. Insert this component to the tilemap entity.
+///
+/// The fog is re-chunked to the chunk size of the tilemap after it's inserted,
+/// so `TilemapFog::default()` works for any tilemap.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapFog {
+    pub colors: FogColors,
+    pub(crate) states: ChunkedStorage<FogState>,
+    /// The tiles that block the sight.
+    pub(crate) opacity: ChunkedStorage<bool>,
+    /// The tiles that are visible now, which will become explored in the next `update_fov`.
+    pub(crate) visible: HashSet<IVec2>,
+    /// The chunks that need to be re-uploaded to the GPU.
+    pub(crate) dirty_chunks: HashSet<IVec2>,
+}
+
+impl Default for TilemapFog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl TilemapFog {
+    pub fn new(chunk_size: u32) -> Self {
+        Self {
+            colors: FogColors::default(),
+            states: ChunkedStorage::new(chunk_size),
+            opacity: ChunkedStorage::new(chunk_size),
+            visible: HashSet::new(),
+            dirty_chunks: HashSet::new(),
+        }
+    }
+
+    pub fn with_colors(mut self, colors: FogColors) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    #[inline]
+    pub fn get(&self, index: IVec2) -> FogState {
+        self.states.get_elem(index).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, index: IVec2, state: FogState) {
+        let (chunk_index, in_chunk_index) = self.states.transform_index(index);
+        self.states
+            .set_elem_precise(chunk_index, in_chunk_index, state);
+        self.dirty_chunks.insert(chunk_index);
+
+        if state == FogState::Visible {
+            self.visible.insert(index);
+        } else {
+            self.visible.remove(&index);
+        }
+    }
+
+    /// Set the state of all the tiles in the area.
+    pub fn fill_rect(&mut self, area: TileArea, state: FogState) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set(IVec2 { x, y }, state);
+            }
+        }
+    }
+
+    /// Returns if the tile blocks the sight.
+    #[inline]
+    pub fn is_opaque(&self, index: IVec2) -> bool {
+        self.opacity.get_elem(index).is_some()
+    }
+
+    /// Mark the tile as opaque, so it blocks the sight in `update_fov`.
+    #[inline]
+    pub fn set_opaque(&mut self, index: IVec2, opaque: bool) {
+        if opaque {
+            self.opacity.set_elem(index, true);
+        } else {
+            self.opacity.remove_elem(index);
+        }
+    }
+
+    pub fn fill_opaque_rect(&mut self, area: TileArea, opaque: bool) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set_opaque(IVec2 { x, y }, opaque);
+            }
+        }
+    }
+
+    /// Turn the currently visible tiles into explored,
+    /// and mark the tiles that the viewers can see as visible.
+    ///
+    /// `viewers` are the positions and the sight radii.
+    pub fn update_fov(&mut self, ty: TilemapType, viewers: impl IntoIterator<Item = (IVec2, u32)>) {
+        let mut visible = HashSet::new();
+        for (origin, radius) in viewers {
+            visible.extend(fov::compute_fov(ty, origin, radius, |i| self.is_opaque(i)));
+        }
+
+        let previous = std::mem::take(&mut self.visible);
+        previous
+            .difference(&visible)
+            .for_each(|index| self.set(*index, FogState::Explored));
+        visible
+            .difference(&previous)
+            .for_each(|index| self.set(*index, FogState::Visible));
+        self.visible = visible;
+    }
+
+    /// Returns the states of a chunk in the layout of the GPU buffer.
+    pub(crate) fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<u32> {
+        let size = (self.states.chunk_size * self.states.chunk_size) as usize;
+        self.states
+            .get_chunk(chunk_index)
+            .map(|chunk| chunk.iter().map(|s| s.unwrap_or_default() as u32).collect())
+            .unwrap_or_else(|| vec![0; size])
+    }
+
+    #[inline]
+    pub fn chunk_size(&self) -> u32 {
+        self.states.chunk_size
+    }
+
+    /// Move the states into chunks of another size. All the chunks will be re-uploaded.
+    pub(crate) fn rechunk(&mut self, chunk_size: u32) {
+        self.states.rechunk(chunk_size);
+        self.opacity.rechunk(chunk_size);
+        self.dirty_chunks = self.states.chunks.keys().copied().collect();
+    }
+}
+
+/// Makes the chunk size of the fog the same as the tilemap.
+pub fn fog_chunk_size_syncer(
+    mut fogs_query: Query<(&mut TilemapFog, &TilemapStorage), Changed<TilemapFog>>,
+) {
+    fogs_query.for_each_mut(|(mut fog, storage)| {
+        let chunk_size = storage.storage.chunk_size;
+        if fog.chunk_size() != chunk_size {
+            fog.rechunk(chunk_size);
+        }
+    });
+}
+
+/// The dirty chunks are extracted at the end of the frame, so clear them at the beginning.
+pub fn fog_dirty_chunks_clearer(mut fogs_query: Query<&mut TilemapFog>) {
+    fogs_query.for_each_mut(|mut fog| {
+        if !fog.dirty_chunks.is_empty() {
+            fog.bypass_change_detection().dirty_chunks.clear();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{system::RunSystemOnce, world::World};
+
+    use super::*;
+
+    #[test]
+    fn test_rechunk() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        let mut fog = TilemapFog::default();
+        fog.set(IVec2::new(-3, 20), FogState::Explored);
+        fog.set_opaque(IVec2::new(40, -1), true);
+        world
+            .entity_mut(tilemap)
+            .insert((TilemapStorage::new(32, tilemap), fog));
+
+        world.run_system_once(fog_chunk_size_syncer);
+
+        let fog = world.get::<TilemapFog>(tilemap).unwrap();
+        assert_eq!(fog.chunk_size(), 32);
+        assert_eq!(fog.get(IVec2::new(-3, 20)), FogState::Explored);
+        assert_eq!(fog.get(IVec2::new(-3, 21)), FogState::Unexplored);
+        assert!(fog.is_opaque(IVec2::new(40, -1)));
+        assert_eq!(fog.dirty_chunks, HashSet::from_iter([IVec2::new(-1, 0)]));
+        assert_eq!(fog.get_chunk_data(IVec2::new(-1, 0))[20 * 32 + 29], 1);
+    }
+}
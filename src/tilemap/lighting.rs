@@ -0,0 +1,478 @@
+//! 2D lighting for tilemaps.
+//!
+//! The light is calculated per tile on the cpu and accumulated into a lightmap,
+//! which is uploaded per chunk and multiplied with the tiles in the fragment shader.
+//! The occluders block the light using the same field of view calculation as the fog.
+
+use bevy::{
+    ecs::{
+        change_detection::{DetectChanges, DetectChangesMut},
+        component::Component,
+        query::{Changed, Or, With},
+        removal_detection::RemovedComponents,
+        system::Query,
+        world::Ref,
+    },
+    math::{IVec2, Vec2, Vec4},
+    reflect::Reflect,
+    render::color::Color,
+    transform::components::GlobalTransform,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{math::TileArea, DEFAULT_CHUNK_SIZE};
+
+use super::{
+    chunking::storage::ChunkedStorage,
+    coordinates::{index_to_world, world_to_index},
+    fog::fov,
+    map::{
+        TilePivot, TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType,
+    },
+};
+
+/// A light that emits in all directions.
+///
+/// The light is placed using the `GlobalTransform` of the entity,
+/// and lights all the tilemaps that have `TilemapLighting`.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapPointLight {
+    pub color: Color,
+    pub intensity: f32,
+    /// The distance in world space where the light fades out.
+    pub range: f32,
+}
+
+impl Default for TilemapPointLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            range: 128.,
+        }
+    }
+}
+
+/// A light that emits in a cone.
+///
+/// The cone points to the local x axis of the `GlobalTransform` of the entity.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapSpotLight {
+    pub color: Color,
+    pub intensity: f32,
+    /// The distance in world space where the light fades out.
+    pub range: f32,
+    /// The angle in radians between the direction and the edge of the fully lit area.
+    pub inner_angle: f32,
+    /// The angle in radians between the direction and the edge of the cone.
+    pub outer_angle: f32,
+}
+
+impl Default for TilemapSpotLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            range: 128.,
+            inner_angle: 0.,
+            outer_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Enables the lighting on a tilemap. Insert this component to the tilemap entity.
+///
+/// The lighting is re-chunked to the chunk size of the tilemap after it's inserted,
+/// so `TilemapLighting::default()` works for any tilemap.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapLighting {
+    /// The light that all the tiles receive.
+    pub ambient: Color,
+    /// The tiles that block the light.
+    pub(crate) occluders: ChunkedStorage<bool>,
+    /// The accumulated light of every tile, without the ambient light.
+    pub(crate) lightmap: HashMap<IVec2, Vec<Vec4>>,
+    /// The lightmap needs to be recalculated even if the lights are not changed.
+    pub(crate) dirty: bool,
+    /// The chunks that need to be re-uploaded to the GPU.
+    pub(crate) dirty_chunks: HashSet<IVec2>,
+}
+
+impl Default for TilemapLighting {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl TilemapLighting {
+    pub fn new(chunk_size: u32) -> Self {
+        Self {
+            ambient: Color::BLACK,
+            occluders: ChunkedStorage::new(chunk_size),
+            lightmap: HashMap::new(),
+            dirty: true,
+            dirty_chunks: HashSet::new(),
+        }
+    }
+
+    pub fn with_ambient(mut self, ambient: Color) -> Self {
+        self.ambient = ambient;
+        self
+    }
+
+    /// Returns if the tile blocks the light.
+    #[inline]
+    pub fn is_occluder(&self, index: IVec2) -> bool {
+        self.occluders.get_elem(index).is_some()
+    }
+
+    /// Mark the tile as an occluder, so it casts shadows.
+    pub fn set_occluder(&mut self, index: IVec2, occluder: bool) {
+        if occluder {
+            self.occluders.set_elem(index, true);
+        } else {
+            self.occluders.remove_elem(index);
+        }
+        self.dirty = true;
+    }
+
+    pub fn fill_occluder_rect(&mut self, area: TileArea, occluder: bool) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set_occluder(IVec2 { x, y }, occluder);
+            }
+        }
+    }
+
+    /// Get the light that the tile receives from the lights, without the ambient light.
+    pub fn get_light(&self, index: IVec2) -> Vec4 {
+        let (chunk_index, in_chunk_index) = self.occluders.transform_index(index);
+        self.lightmap
+            .get(&chunk_index)
+            .map(|chunk| chunk[in_chunk_index])
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn chunk_size(&self) -> u32 {
+        self.occluders.chunk_size
+    }
+
+    /// Returns the light of a chunk in the layout of the GPU buffer.
+    pub(crate) fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<Vec4> {
+        self.lightmap
+            .get(&chunk_index)
+            .cloned()
+            .unwrap_or_else(|| vec![Vec4::ZERO; (self.chunk_size() * self.chunk_size()) as usize])
+    }
+
+    /// Move the occluders into chunks of another size. The lightmap will be recalculated.
+    pub(crate) fn rechunk(&mut self, chunk_size: u32) {
+        self.occluders.rechunk(chunk_size);
+        self.lightmap.clear();
+        self.dirty_chunks.clear();
+        self.dirty = true;
+    }
+
+    fn recalculate(&mut self, lights: &[LightSource], tilemap: &TilemapGeometry) {
+        let mut lightmap = HashMap::<IVec2, Vec<Vec4>>::new();
+        let chunk_size = self.chunk_size();
+
+        for light in lights {
+            let origin = tilemap.world_to_index(light.position);
+            let radius = (light.range / tilemap.min_step()).ceil() as u32 + 1;
+
+            fov::compute_fov(tilemap.ty, origin, radius, |i| self.is_occluder(i))
+                .into_iter()
+                .for_each(|index| {
+                    let strength = light.strength_at(tilemap.tile_center(index));
+                    if strength <= 0. {
+                        return;
+                    }
+
+                    let (chunk_index, in_chunk_index) = self.occluders.transform_index(index);
+                    lightmap
+                        .entry(chunk_index)
+                        .or_insert_with(|| vec![Vec4::ZERO; (chunk_size * chunk_size) as usize])
+                        [in_chunk_index] += light.color * strength;
+                });
+        }
+
+        let prev = std::mem::take(&mut self.lightmap);
+        self.dirty_chunks.extend(
+            prev.keys()
+                .filter(|index| !lightmap.contains_key(*index))
+                .copied(),
+        );
+        self.dirty_chunks.extend(
+            lightmap
+                .iter()
+                .filter(|(index, chunk)| prev.get(*index) != Some(*chunk))
+                .map(|(index, _)| *index),
+        );
+        self.lightmap = lightmap;
+        self.dirty = false;
+    }
+}
+
+struct LightSource {
+    position: Vec2,
+    /// The linear color multiplied by the intensity.
+    color: Vec4,
+    range: f32,
+    /// The direction, the cosine of the inner angle and the outer angle.
+    cone: Option<(Vec2, f32, f32)>,
+}
+
+impl LightSource {
+    fn strength_at(&self, target: Vec2) -> f32 {
+        let offset = target - self.position;
+        let falloff = (1. - offset.length() / self.range).clamp(0., 1.);
+        let mut strength = falloff * falloff;
+
+        if let Some((direction, cos_inner, cos_outer)) = self.cone {
+            let cos = direction.dot(offset.normalize_or_zero());
+            let t = ((cos - cos_outer) / (cos_inner - cos_outer).max(f32::EPSILON)).clamp(0., 1.);
+            strength *= t * t * (3. - 2. * t);
+        }
+
+        strength
+    }
+}
+
+struct TilemapGeometry {
+    ty: TilemapType,
+    transform: TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+    tile_render_size: Vec2,
+}
+
+impl TilemapGeometry {
+    /// The offset from the mesh origin of a tile to its center.
+    #[inline]
+    fn center_offset(&self) -> Vec2 {
+        (Vec2::splat(0.5) - self.pivot) * self.tile_render_size
+    }
+
+    fn tile_center(&self, index: IVec2) -> Vec2 {
+        index_to_world(index, &self.ty, &self.transform, Vec2::ZERO, self.slot_size)
+            + self.transform.transform_vector(self.center_offset())
+    }
+
+    /// The inverse of `tile_center`, rounded to the nearest tile.
+    fn world_to_index(&self, world: Vec2) -> IVec2 {
+        // Move the center of the tile to the center of its slot.
+        let offset = self.center_offset() - self.slot_size / 2.;
+        world_to_index(
+            world - self.transform.transform_vector(offset),
+            &self.ty,
+            &self.transform,
+            Vec2::ZERO,
+            self.slot_size,
+        )
+    }
+
+    /// The shortest distance in world space between two tiles that are one tile apart
+    /// in the field of view calculation.
+    fn min_step(&self) -> f32 {
+        let Vec2 { x, y } = self.slot_size;
+        let step = match self.ty.unstaggered() {
+            TilemapType::Square => x.min(y),
+            TilemapType::Isometric => x.min(y) / std::f32::consts::SQRT_2,
+            TilemapType::Hexagonal(legs) => x.min((y + legs as f32) / 2.) * 0.75f32.sqrt(),
+            _ => unreachable!(),
+        };
+        (step * self.transform.scale.abs().min_element()).max(f32::EPSILON)
+    }
+}
+
+/// Makes the chunk size of the lighting the same as the tilemap.
+pub fn lighting_chunk_size_syncer(
+    mut lightings_query: Query<(&mut TilemapLighting, &TilemapStorage), Changed<TilemapLighting>>,
+) {
+    lightings_query.for_each_mut(|(mut lighting, storage)| {
+        let chunk_size = storage.storage.chunk_size;
+        if lighting.chunk_size() != chunk_size {
+            lighting.rechunk(chunk_size);
+        }
+    });
+}
+
+pub fn tilemap_lighting_updater(
+    mut tilemaps_query: Query<(
+        &mut TilemapLighting,
+        &TilemapType,
+        Ref<GlobalTransform>,
+        &TilePivot,
+        &TilemapSlotSize,
+        &TileRenderSize,
+    )>,
+    point_lights_query: Query<(&TilemapPointLight, &GlobalTransform)>,
+    spot_lights_query: Query<(&TilemapSpotLight, &GlobalTransform)>,
+    changed_lights_query: Query<
+        (),
+        (
+            Or<(With<TilemapPointLight>, With<TilemapSpotLight>)>,
+            Or<(
+                Changed<GlobalTransform>,
+                Changed<TilemapPointLight>,
+                Changed<TilemapSpotLight>,
+            )>,
+        ),
+    >,
+    mut removed_point_lights: RemovedComponents<TilemapPointLight>,
+    mut removed_spot_lights: RemovedComponents<TilemapSpotLight>,
+) {
+    let lights_changed = !changed_lights_query.is_empty()
+        | (removed_point_lights.read().count() > 0)
+        | (removed_spot_lights.read().count() > 0);
+
+    let mut lights = None;
+
+    tilemaps_query.for_each_mut(
+        |(mut lighting, ty, global_transform, pivot, slot_size, render_size)| {
+            if !lights_changed && !lighting.dirty && !global_transform.is_changed() {
+                return;
+            }
+
+            let lights = lights.get_or_insert_with(|| {
+                let point_lights =
+                    point_lights_query
+                        .iter()
+                        .map(|(light, transform)| LightSource {
+                            position: transform.translation().truncate(),
+                            color: Vec4::from_array(light.color.as_linear_rgba_f32())
+                                * light.intensity,
+                            range: light.range,
+                            cone: None,
+                        });
+                let spot_lights = spot_lights_query
+                    .iter()
+                    .map(|(light, transform)| LightSource {
+                        position: transform.translation().truncate(),
+                        color: Vec4::from_array(light.color.as_linear_rgba_f32()) * light.intensity,
+                        range: light.range,
+                        cone: Some((
+                            transform.right().truncate().normalize_or_zero(),
+                            light.inner_angle.cos(),
+                            light.outer_angle.cos(),
+                        )),
+                    });
+                point_lights.chain(spot_lights).collect::<Vec<_>>()
+            });
+
+            lighting.recalculate(
+                lights,
+                &TilemapGeometry {
+                    ty: *ty,
+                    transform: TilemapTransform::from_global(&global_transform),
+                    pivot: pivot.0,
+                    slot_size: slot_size.0,
+                    tile_render_size: render_size.0,
+                },
+            );
+        },
+    );
+}
+
+/// The dirty chunks are extracted at the end of the frame, so clear them at the beginning.
+pub fn lighting_dirty_chunks_clearer(mut lightings_query: Query<&mut TilemapLighting>) {
+    lightings_query.for_each_mut(|mut lighting| {
+        if !lighting.dirty_chunks.is_empty() {
+            lighting.bypass_change_detection().dirty_chunks.clear();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::{system::RunSystemOnce, world::World};
+
+    use crate::tilemap::map::TilemapStagger;
+
+    use super::*;
+
+    #[test]
+    fn test_world_to_index() {
+        for ty in [
+            TilemapType::Square,
+            TilemapType::Isometric,
+            TilemapType::Hexagonal(16),
+            TilemapType::StaggeredIsometric(TilemapStagger::Odd),
+            TilemapType::StaggeredHexagonal(16, TilemapStagger::Even),
+        ] {
+            for pivot in [Vec2::ZERO, Vec2::new(0.5, 0.), Vec2::new(0.25, 0.75)] {
+                let geometry = TilemapGeometry {
+                    ty,
+                    transform: TilemapTransform {
+                        translation: Vec2::new(30., -20.),
+                        z_index: 0,
+                        rotation: 1.2,
+                        scale: Vec2::new(1.5, 0.5),
+                    },
+                    pivot,
+                    slot_size: Vec2::new(32., 16.),
+                    // Larger than the slot, so the center of the tile is not the center of the slot.
+                    tile_render_size: Vec2::new(32., 24.),
+                };
+
+                for index in [IVec2::ZERO, IVec2::new(3, -7), IVec2::new(-12, 5)] {
+                    let center = geometry.tile_center(index);
+                    assert_eq!(geometry.world_to_index(center), index);
+                    assert_eq!(geometry.world_to_index(center + Vec2::splat(2.)), index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaled_light_range() {
+        let geometry = TilemapGeometry {
+            ty: TilemapType::Square,
+            transform: TilemapTransform {
+                scale: Vec2::splat(0.5),
+                ..Default::default()
+            },
+            pivot: Vec2::ZERO,
+            slot_size: Vec2::splat(16.),
+            tile_render_size: Vec2::splat(16.),
+        };
+        let light = LightSource {
+            position: geometry.tile_center(IVec2::ZERO),
+            color: Vec4::ONE,
+            range: 128.,
+            cone: None,
+        };
+
+        let mut lighting = TilemapLighting::default();
+        lighting.recalculate(&[light], &geometry);
+
+        // The tiles are 8 units apart, so the light reaches 16 tiles.
+        assert!(lighting.get_light(IVec2::new(15, 0)).x > 0.);
+        assert!(lighting.get_light(IVec2::new(-15, 0)).x > 0.);
+        assert_eq!(lighting.get_light(IVec2::new(16, 0)), Vec4::ZERO);
+    }
+
+    #[test]
+    fn test_rechunk() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        let mut lighting = TilemapLighting::new(8);
+        lighting.set_occluder(IVec2::new(-5, 9), true);
+        lighting.lightmap.insert(IVec2::ZERO, vec![Vec4::ONE; 64]);
+        world
+            .entity_mut(tilemap)
+            .insert((TilemapStorage::new(DEFAULT_CHUNK_SIZE, tilemap), lighting));
+
+        world.run_system_once(lighting_chunk_size_syncer);
+
+        let lighting = world.get::<TilemapLighting>(tilemap).unwrap();
+        assert_eq!(lighting.chunk_size(), DEFAULT_CHUNK_SIZE);
+        assert!(lighting.is_occluder(IVec2::new(-5, 9)));
+        assert!(!lighting.is_occluder(IVec2::new(-5, 8)));
+        // The old lightmap is in the wrong layout.
+        assert!(lighting.lightmap.is_empty());
+        assert!(lighting.dirty);
+    }
+}
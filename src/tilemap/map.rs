@@ -1,14 +1,21 @@
-use std::{f32::consts::SQRT_2, fmt::Debug};
+use std::fmt::Debug;
 
 use bevy::{
     asset::Handle,
-    ecs::{component::Component, query::Changed, system::Query},
-    math::{Mat2, Quat, Vec4},
-    prelude::{Assets, Commands, Entity, IVec2, Image, ResMut, UVec2, Vec2},
+    ecs::{
+        change_detection::{DetectChanges, Ref},
+        component::Component,
+        query::{Changed, Or},
+        system::Query,
+        world::World,
+    },
+    math::{EulerRot, Mat2, Quat},
+    prelude::{Assets, Commands, Entity, IVec2, Image, Res, ResMut, UVec2, Vec2},
     reflect::Reflect,
     render::render_resource::{FilterMode, TextureUsages},
     sprite::TextureAtlas,
-    transform::components::Transform,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
     utils::{HashMap, HashSet},
 };
 
@@ -21,7 +28,7 @@ use super::{
     buffers::TileBuilderBuffer,
     chunking::storage::{ChunkedStorage, EntityChunkedStorage},
     despawn::DespawnMe,
-    tile::{TileAnimation, TileBuilder, TileUpdater},
+    tile::{TileAnimation, TileAnimationMode, TileBuilder, TileUpdater},
 };
 
 /// Defines the shape of tiles in a tilemap.
@@ -33,6 +40,78 @@ pub enum TilemapType {
     Square,
     Isometric,
     Hexagonal(u32),
+    /// Isometric tiles arranged in rows like bricks, instead of a diamond.
+    /// Every other row is shifted to the right by half a tile.
+    StaggeredIsometric(TilemapStagger),
+    /// Hexagonal tiles arranged in offset rows, instead of the axial coordinates
+    /// used by `Hexagonal`. Every other row is shifted to the right by half a tile.
+    StaggeredHexagonal(u32, TilemapStagger),
+}
+
+impl TilemapType {
+    /// The type with the same shape of tiles but not staggered.
+    pub fn unstaggered(self) -> Self {
+        match self {
+            TilemapType::StaggeredIsometric(_) => TilemapType::Isometric,
+            TilemapType::StaggeredHexagonal(legs, _) => TilemapType::Hexagonal(legs),
+            _ => self,
+        }
+    }
+
+    /// Convert the index of a staggered tilemap into the index in the unstaggered one,
+    /// where the tiles have the same neighbours.
+    ///
+    /// The tile at `[0, 0]` stays the same, and the other tiles keep their positions
+    /// relative to it. Indices of other types are returned unchanged.
+    pub fn to_unstaggered_index(self, index: IVec2) -> IVec2 {
+        match self {
+            TilemapType::StaggeredIsometric(stagger) => {
+                let d = 2 * index.x + stagger.get_shift(index.y) - stagger.get_shift(0);
+                IVec2::new((index.y + d) / 2, (index.y - d) / 2)
+            }
+            TilemapType::StaggeredHexagonal(_, stagger) => IVec2::new(
+                index.x + (index.y + stagger.get_shift(index.y) - stagger.get_shift(0)) / 2,
+                index.y,
+            ),
+            _ => index,
+        }
+    }
+
+    /// The inverse of `to_unstaggered_index`.
+    pub fn from_unstaggered_index(self, index: IVec2) -> IVec2 {
+        match self {
+            TilemapType::StaggeredIsometric(stagger) => {
+                let y = index.x + index.y;
+                let d = index.x - index.y;
+                IVec2::new((d + stagger.get_shift(0) - stagger.get_shift(y)) / 2, y)
+            }
+            TilemapType::StaggeredHexagonal(_, stagger) => IVec2::new(
+                index.x - (index.y + stagger.get_shift(index.y) - stagger.get_shift(0)) / 2,
+                index.y,
+            ),
+            _ => index,
+        }
+    }
+}
+
+/// Which rows are shifted in staggered tilemaps.
+#[derive(Default, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapStagger {
+    #[default]
+    Odd,
+    Even,
+}
+
+impl TilemapStagger {
+    /// Returns 1 if the row is shifted by half a tile, otherwise 0.
+    #[inline]
+    pub fn get_shift(self, y: i32) -> i32 {
+        match self {
+            TilemapStagger::Odd => y.rem_euclid(2),
+            TilemapStagger::Even => 1 - y.rem_euclid(2),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, Reflect)]
@@ -45,19 +124,37 @@ pub enum TilemapRotation {
     Cw270 = 270,
 }
 
-#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serializing", serde(default))]
 pub struct TilemapTransform {
     pub translation: Vec2,
     pub z_index: i32,
-    pub rotation: TilemapRotation,
+    /// The counterclockwise rotation in radians.
+    #[cfg_attr(
+        feature = "serializing",
+        serde(
+            serialize_with = "serialize_tilemap_rotation",
+            deserialize_with = "deserialize_tilemap_rotation"
+        )
+    )]
+    pub rotation: f32,
+    /// Applied before the rotation.
+    pub scale: Vec2,
+}
+
+impl Default for TilemapTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
 }
 
 impl TilemapTransform {
     pub const IDENTITY: Self = Self {
         translation: Vec2::ZERO,
         z_index: 0,
-        rotation: TilemapRotation::None,
+        rotation: 0.,
+        scale: Vec2::ONE,
     };
 
     #[inline]
@@ -85,51 +182,82 @@ impl TilemapTransform {
         }
     }
 
+    /// Get the transform in world space, including the transforms of the parents.
+    ///
+    /// Shearing caused by non-uniform scaled parents can't be represented and is ignored.
+    pub fn from_global(global: &GlobalTransform) -> Self {
+        let (scale, rotation, translation) = global.to_scale_rotation_translation();
+        Self {
+            translation: translation.truncate(),
+            z_index: translation.z.round() as i32,
+            rotation: rotation.to_euler(EulerRot::ZYX).0,
+            scale: scale.truncate(),
+        }
+    }
+
+    #[inline]
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    #[inline]
+    pub fn with_scale(mut self, scale: Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
     #[inline]
     pub fn transform_point(&self, point: Vec2) -> Vec2 {
-        self.apply_translation(self.apply_rotation(point))
+        self.apply_translation(self.transform_vector(point))
     }
 
-    pub fn transform_aabb(&self, aabb: Aabb2d) -> Aabb2d {
-        let min = self.transform_point(aabb.min);
-        let max = self.transform_point(aabb.max);
+    /// Apply the scale and the rotation but not the translation.
+    #[inline]
+    pub fn transform_vector(&self, vector: Vec2) -> Vec2 {
+        self.get_matrix() * vector
+    }
 
-        match self.rotation {
-            TilemapRotation::None => Aabb2d { min, max },
-            TilemapRotation::Cw90 => Aabb2d::new(max.x, min.y, min.x, max.y),
-            TilemapRotation::Cw180 => Aabb2d::new(max.x, max.y, min.x, min.y),
-            TilemapRotation::Cw270 => Aabb2d::new(min.x, max.y, max.x, min.y),
+    /// Map a point in world space back into the space of the tilemap.
+    #[inline]
+    pub fn inverse_transform_point(&self, point: Vec2) -> Vec2 {
+        self.get_matrix().inverse() * (point - self.translation)
+    }
+
+    pub fn transform_aabb(&self, aabb: Aabb2d) -> Aabb2d {
+        let corners = [
+            aabb.min,
+            Vec2::new(aabb.max.x, aabb.min.y),
+            aabb.max,
+            Vec2::new(aabb.min.x, aabb.max.y),
+        ]
+        .map(|p| self.transform_point(p));
+
+        Aabb2d {
+            min: corners.into_iter().reduce(Vec2::min).unwrap(),
+            max: corners.into_iter().reduce(Vec2::max).unwrap(),
         }
     }
 
+    /// The matrix that scales and then rotates.
+    #[inline]
+    pub fn get_matrix(&self) -> Mat2 {
+        self.get_rotation_matrix() * Mat2::from_diagonal(self.scale)
+    }
+
     #[inline]
     pub fn get_rotation_matrix(&self) -> Mat2 {
-        match self.rotation {
-            TilemapRotation::None => Mat2::from_cols_array(&[1., 0., 0., 1.]),
-            TilemapRotation::Cw90 => Mat2::from_cols_array(&[0., 1., -1., 0.]),
-            TilemapRotation::Cw180 => Mat2::from_cols_array(&[-1., 0., 0., -1.]),
-            TilemapRotation::Cw270 => Mat2::from_cols_array(&[0., -1., 1., 0.]),
-        }
+        Mat2::from_angle(self.rotation)
     }
 
     #[inline]
     pub fn get_rotation_quat(&self) -> Quat {
-        match self.rotation {
-            TilemapRotation::None => Quat::IDENTITY,
-            TilemapRotation::Cw90 => Quat::from_xyzw(0., 0., SQRT_2 / 2., SQRT_2 / 2.),
-            TilemapRotation::Cw180 => Quat::from_xyzw(0., 0., 1., 0.),
-            TilemapRotation::Cw270 => Quat::from_xyzw(0., 0., SQRT_2 / 2., -SQRT_2 / 2.),
-        }
+        Quat::from_rotation_z(self.rotation)
     }
 
     #[inline]
     pub fn apply_rotation(&self, point: Vec2) -> Vec2 {
-        match self.rotation {
-            TilemapRotation::None => point,
-            TilemapRotation::Cw90 => Vec2::new(-point.y, point.x),
-            TilemapRotation::Cw180 => Vec2::new(-point.x, -point.y),
-            TilemapRotation::Cw270 => Vec2::new(point.y, -point.x),
-        }
+        self.get_rotation_matrix() * point
     }
 
     #[inline]
@@ -138,12 +266,55 @@ impl TilemapTransform {
     }
 }
 
+/// The rotation of `TilemapTransform` in the saved maps.
+///
+/// The maps saved before the free rotation use the `TilemapRotation` variants.
+#[cfg(feature = "serializing")]
+#[derive(serde::Serialize, serde::Deserialize)]
+enum SerializedRotation {
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+    Radians(f32),
+}
+
+#[cfg(feature = "serializing")]
+impl From<SerializedRotation> for f32 {
+    fn from(value: SerializedRotation) -> Self {
+        // Despite the names, the old rotation matrices turned the tilemaps counterclockwise,
+        // so the steps are kept as they are to render the maps the same way.
+        match value {
+            SerializedRotation::None => 0.,
+            SerializedRotation::Cw90 => std::f32::consts::FRAC_PI_2,
+            SerializedRotation::Cw180 => std::f32::consts::PI,
+            SerializedRotation::Cw270 => std::f32::consts::PI * 1.5,
+            SerializedRotation::Radians(radians) => radians,
+        }
+    }
+}
+
+#[cfg(feature = "serializing")]
+fn serialize_tilemap_rotation<S: serde::Serializer>(
+    rotation: &f32,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&SerializedRotation::Radians(*rotation), serializer)
+}
+
+#[cfg(feature = "serializing")]
+fn deserialize_tilemap_rotation<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<f32, D::Error> {
+    <SerializedRotation as serde::Deserialize>::deserialize(deserializer).map(Into::into)
+}
+
 impl Into<Transform> for TilemapTransform {
     fn into(self) -> Transform {
         Transform {
             translation: self.translation.extend(self.z_index as f32),
-            rotation: Quat::from_rotation_z(self.rotation as u32 as f32),
-            ..Default::default()
+            rotation: self.get_rotation_quat(),
+            scale: self.scale.extend(1.),
         }
     }
 }
@@ -254,14 +425,50 @@ pub struct TilemapSlotSize(pub Vec2);
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TilePivot(pub Vec2);
 
-#[derive(Component, Debug, Clone, Copy, Reflect)]
+/// The opacity of each tile layer, from the bottom to the top.
+/// The layers without a specified opacity are fully opaque.
+#[derive(Component, Default, Debug, Clone, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
-pub struct TilemapLayerOpacities(pub Vec4);
+pub struct TilemapLayerOpacities(
+    #[cfg_attr(
+        feature = "serializing",
+        serde(deserialize_with = "deserialize_layer_opacities")
+    )]
+    pub Vec<f32>,
+);
+
+impl TilemapLayerOpacities {
+    #[inline]
+    pub fn get(&self, layer: usize) -> f32 {
+        self.0.get(layer).copied().unwrap_or(1.)
+    }
+}
 
-impl Default for TilemapLayerOpacities {
-    fn default() -> Self {
-        Self(Vec4::ONE)
+/// Accepts both the list and the `Vec4` tuple that the maps saved before
+/// the opacities became a list use.
+#[cfg(feature = "serializing")]
+fn deserialize_layer_opacities<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<f32>, D::Error> {
+    struct OpacitiesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for OpacitiesVisitor {
+        type Value = Vec<f32>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a list or a tuple of opacities")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<f32>, A::Error> {
+            let mut opacities = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+            while let Some(opacity) = seq.next_element()? {
+                opacities.push(opacity);
+            }
+            Ok(opacities)
+        }
     }
+
+    deserializer.deserialize_any(OpacitiesVisitor)
 }
 
 #[derive(Component, Default, Debug, Clone, Copy, Reflect)]
@@ -354,10 +561,20 @@ impl TilemapStorage {
     }
 
     /// Update some properties of a tile.
-    #[inline]
+    ///
+    /// The updates to the same tile in one frame are merged in order.
     pub fn update(&mut self, commands: &mut Commands, index: IVec2, updater: TileUpdater) {
         if let Some(entity) = self.get(index) {
-            commands.entity(entity).insert(updater);
+            commands.add(move |world: &mut World| {
+                let Some(mut tile) = world.get_entity_mut(entity) else {
+                    return;
+                };
+                if let Some(mut pending) = tile.get_mut::<TileUpdater>() {
+                    pending.merge(updater);
+                } else {
+                    tile.insert(updater);
+                }
+            });
         }
     }
 
@@ -588,10 +805,70 @@ impl TilemapAnimations {
         let start = self.0.len() as u32;
         let length = seq.len() as u32;
         self.0.extend(seq.into_iter().map(|i| i as i32));
-        TileAnimation { start, length, fps }
+        TileAnimation {
+            start,
+            length,
+            fps,
+            mode: TileAnimationMode::Loop,
+            offset: 0.,
+        }
+    }
+}
+
+/// Controls how the animations of a tilemap are played.
+///
+/// Tilemaps without this component use `Time::elapsed_seconds` as the animation time.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapAnimationPlayback {
+    pub(crate) time: f32,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for TilemapAnimationPlayback {
+    fn default() -> Self {
+        Self {
+            time: 0.,
+            speed: 1.,
+            paused: false,
+        }
+    }
+}
+
+impl TilemapAnimationPlayback {
+    /// The time in seconds the animations have been played for.
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
     }
 }
 
+pub fn animation_playback_updater(
+    mut playbacks_query: Query<&mut TilemapAnimationPlayback>,
+    time: Res<Time>,
+) {
+    playbacks_query.for_each_mut(|mut playback| {
+        if !playback.paused {
+            playback.time += time.delta_seconds() * playback.speed;
+        }
+    });
+}
+
 pub fn transform_syncer(
     mut tilemap_query: Query<(&TilemapTransform, &mut Transform), Changed<TilemapTransform>>,
 ) {
@@ -600,6 +877,7 @@ pub fn transform_syncer(
             .translation
             .extend(tilemap_transform.z_index as f32);
         transform.rotation = tilemap_transform.get_rotation_quat();
+        transform.scale = tilemap_transform.scale.extend(1.);
     });
 }
 
@@ -609,12 +887,20 @@ pub fn queued_chunk_aabb_calculator(
         &TilemapType,
         &TilePivot,
         &TilemapSlotSize,
-        &TilemapTransform,
+        Ref<GlobalTransform>,
     )>,
 ) {
     tilemaps_query.par_iter_mut().for_each(
-        |(mut storage, ty, tile_pivot, slot_size, transform)| {
+        |(mut storage, ty, tile_pivot, slot_size, global_transform)| {
             let chunk_size = storage.storage.chunk_size;
+            let transform = TilemapTransform::from_global(&global_transform);
+
+            // The tilemap or its parents are moved, so all the aabbs are outdated.
+            if global_transform.is_changed() {
+                let reserved = storage.reserved.keys().copied().collect::<Vec<_>>();
+                storage.calc_queue.extend(reserved);
+            }
+
             let ext = storage
                 .calc_queue
                 .drain()
@@ -627,7 +913,7 @@ pub fn queued_chunk_aabb_calculator(
                             *ty,
                             tile_pivot.0,
                             slot_size.0,
-                            *transform,
+                            transform,
                         ),
                     )
                 })
@@ -645,13 +931,14 @@ pub fn tilemap_aabb_calculator(
             &TilemapType,
             &TilePivot,
             &TilemapSlotSize,
-            &TilemapTransform,
+            &GlobalTransform,
         ),
-        Changed<TilemapStorage>,
+        Or<(Changed<TilemapStorage>, Changed<GlobalTransform>)>,
     >,
 ) {
     tilemaps_query.par_iter_mut().for_each(
-        |(mut aabbs, storage, ty, tile_pivot, slot_size, transform)| {
+        |(mut aabbs, storage, ty, tile_pivot, slot_size, global_transform)| {
+            let transform = TilemapTransform::from_global(global_transform);
             let mut chunk_aabb: Option<IAabb2d> = None;
             storage.storage.chunks.keys().for_each(|chunk_index| {
                 if let Some(aabb) = &mut chunk_aabb {
@@ -671,7 +958,7 @@ pub fn tilemap_aabb_calculator(
                 *ty,
                 tile_pivot.0,
                 slot_size.0,
-                *transform,
+                transform,
             );
             let world_min = Aabb2d::from_tilemap(
                 chunk_aabb.min,
@@ -679,7 +966,7 @@ pub fn tilemap_aabb_calculator(
                 *ty,
                 tile_pivot.0,
                 slot_size.0,
-                *transform,
+                transform,
             );
 
             aabbs.chunk_aabb = chunk_aabb;
@@ -690,3 +977,40 @@ pub fn tilemap_aabb_calculator(
         },
     );
 }
+
+#[cfg(all(test, feature = "serializing"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_rotation() {
+        // Saved before the rotation became an angle.
+        for (rotation, rotated) in [
+            ("None", Vec2::new(1., 2.)),
+            ("Cw90", Vec2::new(-2., 1.)),
+            ("Cw180", Vec2::new(-1., -2.)),
+            ("Cw270", Vec2::new(2., -1.)),
+        ] {
+            let transform = ron::from_str::<TilemapTransform>(&format!(
+                "(translation: (10.0, -4.0), z_index: 2, rotation: {})",
+                rotation
+            ))
+            .unwrap();
+            assert_eq!(transform.translation, Vec2::new(10., -4.));
+            assert_eq!(transform.z_index, 2);
+            assert_eq!(transform.scale, Vec2::ONE);
+            assert!(transform
+                .apply_rotation(Vec2::new(1., 2.))
+                .abs_diff_eq(rotated, 1e-5));
+        }
+
+        let transform = TilemapTransform {
+            translation: Vec2::new(3., 5.),
+            z_index: -1,
+            rotation: 0.7,
+            scale: Vec2::new(2., 0.5),
+        };
+        let ron = ron::to_string(&transform).unwrap();
+        assert_eq!(ron::from_str::<TilemapTransform>(&ron).unwrap(), transform);
+    }
+}
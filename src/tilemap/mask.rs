@@ -0,0 +1,233 @@
+use bevy::{
+    ecs::{
+        change_detection::DetectChangesMut, component::Component, query::Changed, system::Query,
+    },
+    math::{IVec2, Vec4},
+    reflect::Reflect,
+    utils::HashSet,
+};
+
+use crate::math::TileArea;
+
+use super::{chunking::storage::ChunkedStorage, map::TilemapStorage};
+
+#[derive(Debug, Default, Clone, Copy, Reflect)]
+pub enum TilemapMaskMode {
+    /// The masked tiles won't be rendered.
+    #[default]
+    Hide,
+    /// The color of the masked tiles will be multiplied by this brightness.
+    Dim(f32),
+}
+
+/// Hides or dims tiles in some areas. Insert this component to the tilemap entity.
+///
+/// A tile is masked if it's inside any of the `areas` or it's marked using `TilemapMask::set`.
+///
+/// The mask is uploaded per chunk, and only the chunks where the marked tiles or the `areas`
+/// are changed will be re-uploaded. Changing `mode` or `inverted` doesn't upload anything.
+#[derive(Component, Default, Debug, Clone, Reflect)]
+pub struct TilemapMask {
+    pub mode: TilemapMaskMode,
+    /// Mask the tiles outside the areas instead.
+    /// This is useful when you want to reveal something.
+    pub inverted: bool,
+    pub areas: Vec<TileArea>,
+    pub(crate) storage: ChunkedStorage<bool>,
+    /// The `areas` when the dirty chunks were last calculated.
+    pub(crate) prev_areas: Vec<TileArea>,
+    /// The chunks that need to be re-uploaded to the GPU.
+    pub(crate) dirty_chunks: HashSet<IVec2>,
+}
+
+impl TilemapMask {
+    pub fn new(mode: TilemapMaskMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    pub fn with_area(mut self, area: TileArea) -> Self {
+        self.areas.push(area);
+        self
+    }
+
+    /// Mark a single tile as masked or not.
+    #[inline]
+    pub fn set(&mut self, index: IVec2, masked: bool) {
+        let (chunk_index, in_chunk_index) = self.storage.transform_index(index);
+        if masked {
+            self.storage
+                .set_elem_precise(chunk_index, in_chunk_index, true);
+        } else {
+            self.storage.remove_elem(index);
+        }
+        self.dirty_chunks.insert(chunk_index);
+    }
+
+    /// Mark all the tiles in the area as masked or not.
+    pub fn fill_rect(&mut self, area: TileArea, masked: bool) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set(IVec2 { x, y }, masked);
+            }
+        }
+    }
+
+    /// Unmark all the tiles marked by `TilemapMask::set`.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.dirty_chunks
+            .extend(self.storage.chunks.drain().map(|(index, _)| index));
+    }
+
+    /// Returns if the tile at the index is masked.
+    #[inline]
+    pub fn is_masked(&self, index: IVec2) -> bool {
+        self.is_marked(index) != self.inverted
+    }
+
+    /// Returns if the tile is inside the areas or marked, regardless of `inverted`.
+    fn is_marked(&self, index: IVec2) -> bool {
+        self.storage.get_elem(index).is_some()
+            || self.areas.iter().any(|area| area.aabb().contains(index))
+    }
+
+    /// The color to be multiplied with the masked tiles.
+    pub fn color(&self) -> Vec4 {
+        match self.mode {
+            TilemapMaskMode::Hide => Vec4::ZERO,
+            TilemapMaskMode::Dim(brightness) => Vec4::new(brightness, brightness, brightness, 1.),
+        }
+    }
+
+    #[inline]
+    pub fn chunk_size(&self) -> u32 {
+        self.storage.chunk_size
+    }
+
+    /// All the chunks that have any marked tiles.
+    pub(crate) fn chunks(&self) -> HashSet<IVec2> {
+        let mut chunks = self.storage.chunks.keys().copied().collect::<HashSet<_>>();
+        self.areas
+            .iter()
+            .for_each(|area| chunks.extend(self.area_chunks(area)));
+        chunks
+    }
+
+    /// Returns the marked tiles of a chunk in the layout of the GPU buffer.
+    ///
+    /// `inverted` is applied in the shader, so the chunks without any marked tiles never change.
+    pub(crate) fn get_chunk_data(&self, chunk_index: IVec2) -> Vec<u32> {
+        let size = self.chunk_size() as i32;
+        let origin = chunk_index * size;
+        (0..size * size)
+            .map(|i| self.is_marked(origin + IVec2::new(i % size, i / size)) as u32)
+            .collect()
+    }
+
+    fn area_chunks(&self, area: &TileArea) -> impl Iterator<Item = IVec2> {
+        let size = IVec2::splat(self.chunk_size() as i32);
+        let min = area.origin.div_euclid(size);
+        let max = area.dest.div_euclid(size);
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2 { x, y }))
+    }
+
+    /// Move the marked tiles into chunks of another size. All the chunks will be re-uploaded.
+    pub(crate) fn rechunk(&mut self, chunk_size: u32) {
+        self.storage.rechunk(chunk_size);
+        self.dirty_chunks = self.chunks();
+    }
+
+    /// Mark the chunks covered by the old or the new areas as dirty if the areas are changed.
+    fn sync_areas(&mut self) {
+        if self.areas == self.prev_areas {
+            return;
+        }
+
+        let prev_areas = std::mem::replace(&mut self.prev_areas, self.areas.clone());
+        let dirty = prev_areas
+            .iter()
+            .chain(self.areas.iter())
+            .flat_map(|area| self.area_chunks(area))
+            .collect::<Vec<_>>();
+        self.dirty_chunks.extend(dirty);
+    }
+}
+
+/// Makes the chunk size of the mask the same as the tilemap,
+/// and finds the chunks that are affected by the changes of `areas`.
+pub fn mask_chunks_syncer(
+    mut masks_query: Query<(&mut TilemapMask, &TilemapStorage), Changed<TilemapMask>>,
+) {
+    masks_query.for_each_mut(|(mut mask, storage)| {
+        let chunk_size = storage.storage.chunk_size;
+        if mask.chunk_size() != chunk_size {
+            mask.rechunk(chunk_size);
+        }
+        mask.sync_areas();
+    });
+}
+
+/// The dirty chunks are extracted at the end of the frame, so clear them at the beginning.
+pub fn mask_dirty_chunks_clearer(mut masks_query: Query<&mut TilemapMask>) {
+    masks_query.for_each_mut(|mut mask| {
+        if !mask.dirty_chunks.is_empty() {
+            mask.bypass_change_detection().dirty_chunks.clear();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{system::RunSystemOnce, world::World},
+        math::UVec2,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_dirty_chunks() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        let mut mask = TilemapMask::default()
+            .with_area(TileArea::new(IVec2::new(30, 0), UVec2::new(4, 1)))
+            .with_inverted(true);
+        mask.set(IVec2::new(-3, 20), true);
+        world
+            .entity_mut(tilemap)
+            .insert((TilemapStorage::new(32, tilemap), mask));
+
+        world.run_system_once(mask_chunks_syncer);
+
+        let mask = world.get::<TilemapMask>(tilemap).unwrap();
+        assert_eq!(mask.chunk_size(), 32);
+        assert_eq!(
+            mask.dirty_chunks,
+            HashSet::from_iter([IVec2::new(-1, 0), IVec2::new(0, 0), IVec2::new(1, 0)])
+        );
+        assert!(!mask.is_masked(IVec2::new(-3, 20)));
+        assert!(mask.is_masked(IVec2::new(-3, 21)));
+        assert_eq!(mask.get_chunk_data(IVec2::new(-1, 0))[20 * 32 + 29], 1);
+        assert_eq!(mask.get_chunk_data(IVec2::new(1, 0))[..3], [1, 1, 0]);
+
+        world.run_system_once(mask_dirty_chunks_clearer);
+        world.get_mut::<TilemapMask>(tilemap).unwrap().areas[0] =
+            TileArea::new(IVec2::new(0, 70), UVec2::ONE);
+        world.run_system_once(mask_chunks_syncer);
+
+        let mask = world.get::<TilemapMask>(tilemap).unwrap();
+        assert_eq!(
+            mask.dirty_chunks,
+            HashSet::from_iter([IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(0, 2)])
+        );
+        assert_eq!(mask.get_chunk_data(IVec2::new(1, 0))[0], 0);
+    }
+}
@@ -1,40 +1,64 @@
-use bevy::app::{Plugin, PostUpdate, PreUpdate, Update};
+use bevy::{
+    app::{First, Plugin, PostUpdate, PreUpdate, Update},
+    ecs::schedule::IntoSystemConfigs,
+    transform::TransformSystem,
+};
 
 use self::{
+    autotile::{AutoTileRule, AutoTileTerrain, TilemapAutoTiles},
     chunking::camera::{CameraChunkUpdater, CameraChunkUpdation},
+    fog::{FogColors, FogState, TilemapFog},
+    lighting::{TilemapLighting, TilemapPointLight, TilemapSpotLight},
     map::{
-        TilePivot, TileRenderSize, TilemapAabbs, TilemapAnimations, TilemapLayerOpacities,
-        TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
-        TilemapTransform, TilemapType,
+        TilePivot, TileRenderSize, TilemapAabbs, TilemapAnimationPlayback, TilemapAnimations,
+        TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStagger, TilemapStorage,
+        TilemapTexture, TilemapTextureDescriptor, TilemapTransform, TilemapType,
+    },
+    mask::{TilemapMask, TilemapMaskMode},
+    tile::{
+        LayerUpdater, Tile, TileAnimation, TileAnimationFinished, TileAnimationMode, TileFlip,
+        TileLayer, TileRotation, TileTexture, TileUpdater,
     },
-    tile::{LayerUpdater, Tile, TileLayer, TileTexture, TileUpdater},
 };
 
 #[cfg(feature = "algorithm")]
 pub mod algorithm;
+pub mod autotile;
 pub mod buffers;
 pub mod bundles;
 pub mod chunking;
 pub mod coordinates;
 pub mod despawn;
+pub mod fog;
+pub mod lighting;
 pub mod map;
+pub mod mask;
 #[cfg(feature = "physics")]
 pub mod physics;
+pub mod picking;
 pub mod tile;
 
 pub struct EntiTilesTilemapPlugin;
 
 impl Plugin for EntiTilesTilemapPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            First,
+            (
+                fog::fog_dirty_chunks_clearer,
+                lighting::lighting_dirty_chunks_clearer,
+                mask::mask_dirty_chunks_clearer,
+            ),
+        );
         app.add_systems(PreUpdate, despawn::despawn_applier);
 
         app.add_systems(
             Update,
             (
                 map::transform_syncer,
-                map::queued_chunk_aabb_calculator,
-                map::tilemap_aabb_calculator,
+                map::animation_playback_updater,
                 tile::tile_updater,
+                tile::animation_finish_notifier.after(map::animation_playback_updater),
                 chunking::camera::camera_chunk_update,
             ),
         );
@@ -46,6 +70,17 @@ impl Plugin for EntiTilesTilemapPlugin {
                 despawn::despawn_tiles,
                 #[cfg(feature = "physics")]
                 despawn::despawn_physics_tilemaps,
+                fog::fog_chunk_size_syncer,
+                mask::mask_chunks_syncer,
+                lighting::tilemap_lighting_updater
+                    .after(TransformSystem::TransformPropagate)
+                    .after(lighting::lighting_chunk_size_syncer),
+                lighting::lighting_chunk_size_syncer,
+                (
+                    map::queued_chunk_aabb_calculator,
+                    map::tilemap_aabb_calculator,
+                )
+                    .after(TransformSystem::TransformPropagate),
             ),
         );
 
@@ -53,12 +88,18 @@ impl Plugin for EntiTilesTilemapPlugin {
             .register_type::<LayerUpdater>()
             .register_type::<TileUpdater>()
             .register_type::<Tile>()
-            .register_type::<TileTexture>();
+            .register_type::<TileTexture>()
+            .register_type::<TileAnimation>()
+            .register_type::<TileAnimationMode>()
+            .register_type::<TileAnimationFinished>()
+            .register_type::<TileFlip>()
+            .register_type::<TileRotation>();
 
         app.register_type::<TilemapName>()
             .register_type::<TileRenderSize>()
             .register_type::<TilemapSlotSize>()
             .register_type::<TilemapType>()
+            .register_type::<TilemapStagger>()
             .register_type::<TilePivot>()
             .register_type::<TilemapLayerOpacities>()
             .register_type::<TilemapStorage>()
@@ -66,12 +107,26 @@ impl Plugin for EntiTilesTilemapPlugin {
             .register_type::<TilemapTransform>()
             .register_type::<TilemapTexture>()
             .register_type::<TilemapTextureDescriptor>()
-            .register_type::<TilemapAnimations>();
+            .register_type::<TilemapAnimations>()
+            .register_type::<TilemapAnimationPlayback>()
+            .register_type::<TilemapMask>()
+            .register_type::<TilemapMaskMode>()
+            .register_type::<TilemapFog>()
+            .register_type::<FogState>()
+            .register_type::<FogColors>()
+            .register_type::<TilemapLighting>()
+            .register_type::<TilemapPointLight>()
+            .register_type::<TilemapSpotLight>();
+
+        app.register_type::<TilemapAutoTiles>()
+            .register_type::<AutoTileTerrain>()
+            .register_type::<AutoTileRule>();
 
         app.register_type::<CameraChunkUpdation>()
             .register_type::<CameraChunkUpdater>();
 
-        app.add_event::<CameraChunkUpdation>();
+        app.add_event::<CameraChunkUpdation>()
+            .add_event::<TileAnimationFinished>();
 
         #[cfg(feature = "algorithm")]
         app.add_plugins(algorithm::EntiTilesAlgorithmTilemapPlugin);
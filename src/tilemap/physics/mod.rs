@@ -6,8 +6,10 @@ use bevy::{
         event::{Event, EventReader, EventWriter},
         system::{Commands, Query},
     },
+    hierarchy::BuildChildren,
     math::{IVec2, UVec2, Vec2},
     reflect::Reflect,
+    transform::TransformBundle,
     utils::HashMap,
 };
 use bevy_xpbd_2d::{
@@ -68,12 +70,16 @@ impl Into<PhysicsTile> for PackedPhysicsTile {
 impl Tiles for PackedPhysicsTile {}
 
 impl PackedPhysicsTile {
-    pub fn spawn(&self, commands: &mut Commands, ty: TilemapType) -> Entity {
+    /// Spawn the collider as a child of the tilemap.
+    /// The vertices are in the local space of the tilemap.
+    pub fn spawn(&self, commands: &mut Commands, ty: TilemapType, tilemap: Entity) -> Entity {
         let mut entity = commands.spawn(match ty {
-            TilemapType::Square | TilemapType::Isometric => {
+            TilemapType::Square | TilemapType::Isometric | TilemapType::StaggeredIsometric(_) => {
                 Collider::convex_hull(self.collider.clone()).unwrap()
             }
-            TilemapType::Hexagonal(_) => Collider::polyline(self.collider.clone(), None),
+            TilemapType::Hexagonal(_) | TilemapType::StaggeredHexagonal(..) => {
+                Collider::polyline(self.collider.clone(), None)
+            }
         });
         if self.physics_tile.rigid_body {
             entity.insert(RigidBody::Static);
@@ -81,6 +87,7 @@ impl PackedPhysicsTile {
         if let Some(friction) = &self.physics_tile.friction {
             entity.insert(Friction::new(*friction));
         }
+        entity.insert(TransformBundle::default()).set_parent(tilemap);
         entity.id()
     }
 }
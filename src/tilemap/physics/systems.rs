@@ -20,15 +20,15 @@ use super::{DataPhysicsTilemap, PackedPhysicsTile, PhysicsTilemap};
 pub fn spawn_colliders(
     commands: ParallelCommands,
     mut tilemaps_query: Query<(
+        Entity,
         &mut PhysicsTilemap,
         &TilemapType,
-        &TilemapTransform,
         &TilePivot,
         &TilemapSlotSize,
     )>,
 ) {
     tilemaps_query.par_iter_mut().for_each(
-        |(mut physics_tilemap, ty, transform, tile_pivot, slot_size)| {
+        |(entity, mut physics_tilemap, ty, tile_pivot, slot_size)| {
             let physics_tiles = physics_tilemap.spawn_queue.drain(..).collect::<Vec<_>>();
             physics_tiles.into_iter().for_each(|(aabb, physics_tile)| {
                 commands.command_scope(|mut c| {
@@ -36,7 +36,7 @@ pub fn spawn_colliders(
                         aabb.min,
                         ty,
                         aabb.size().as_uvec2(),
-                        transform,
+                        &TilemapTransform::IDENTITY,
                         tile_pivot.0,
                         slot_size.0,
                     );
@@ -49,7 +49,7 @@ pub fn spawn_colliders(
 
                     physics_tilemap
                         .storage
-                        .set_elem(aabb.min, packed_tile.spawn(&mut c, *ty));
+                        .set_elem(aabb.min, packed_tile.spawn(&mut c, *ty, entity));
                     physics_tilemap.data.set_elem(aabb.min, packed_tile);
                 });
             });
@@ -59,14 +59,26 @@ pub fn spawn_colliders(
 
 pub fn data_physics_tilemap_analyzer(
     commands: ParallelCommands,
-    mut tilemaps_query: Query<(Entity, &mut DataPhysicsTilemap, Option<&mut PhysicsTilemap>)>,
+    mut tilemaps_query: Query<(
+        Entity,
+        &mut DataPhysicsTilemap,
+        Option<&mut PhysicsTilemap>,
+        Option<&TilemapType>,
+    )>,
 ) {
     tilemaps_query
         .par_iter_mut()
-        .for_each(|(entity, mut data_tilemap, mut physics_tilemap)| {
+        .for_each(|(entity, mut data_tilemap, mut physics_tilemap, ty)| {
             let mut aabbs = Vec::new();
             let size = data_tilemap.size;
             let air = data_tilemap.air;
+            // The colliders of staggered tilemaps can only cover a single tile,
+            // or a row of hexagonal tiles.
+            let (merge_x, merge_y) = match ty {
+                Some(TilemapType::StaggeredIsometric(_)) => (false, false),
+                Some(TilemapType::StaggeredHexagonal(..)) => (true, false),
+                _ => (true, true),
+            };
 
             for y in 0..size.y {
                 for x in 0..size.x {
@@ -81,8 +93,8 @@ pub fn data_physics_tilemap_analyzer(
                     };
 
                     let mut d = UVec2 {
-                        x: if x == size.x - 1 { 0 } else { 1 },
-                        y: if y == size.y - 1 { 0 } else { 1 },
+                        x: if x == size.x - 1 || !merge_x { 0 } else { 1 },
+                        y: if y == size.y - 1 || !merge_y { 0 } else { 1 },
                     };
                     let mut dst = cur;
                     while d.x != 0 || d.y != 0 {
@@ -0,0 +1,263 @@
+//! Picking tiles with the cursor.
+//!
+//! Add `TilemapPickingPlugin` to receive `TileHovered`, `TileClicked` and `TileDragged` events.
+//! The cursor of each camera is cast into all the visible tilemaps,
+//! and the tile on the top is picked.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventWriter},
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Local, Query, Res, ResMut, Resource},
+    },
+    input::{mouse::MouseButton, Input},
+    math::IVec2,
+    reflect::Reflect,
+    render::{
+        camera::{Camera, NormalizedRenderTarget},
+        view::InheritedVisibility,
+    },
+    transform::components::GlobalTransform,
+    utils::HashMap,
+    window::{PrimaryWindow, Window},
+};
+
+use crate::render::culling::{self, InvisibleTilemap};
+
+use super::{
+    coordinates,
+    map::{
+        TilePivot, TilemapAabbs, TilemapLayerOpacities, TilemapSlotSize, TilemapStorage,
+        TilemapTexture, TilemapTransform, TilemapType,
+    },
+    tile::{Tile, TileTexture},
+};
+
+pub struct TilemapPickingPlugin;
+
+impl Plugin for TilemapPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, tile_picker.after(culling::cull_tilemaps));
+
+        app.init_resource::<TilemapPicking>();
+
+        app.register_type::<PickedTile>()
+            .register_type::<TileHovered>()
+            .register_type::<TileClicked>()
+            .register_type::<TileDragged>();
+
+        app.add_event::<TileHovered>()
+            .add_event::<TileClicked>()
+            .add_event::<TileDragged>();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct PickedTile {
+    pub tilemap: Entity,
+    pub index: IVec2,
+    pub tile: Entity,
+}
+
+/// Sent when the cursor of a camera moves onto a tile.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub struct TileHovered {
+    pub camera: Entity,
+    pub tilemap: Entity,
+    pub index: IVec2,
+    pub tile: Entity,
+}
+
+/// Sent when a mouse button is pressed on a tile.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub struct TileClicked {
+    pub camera: Entity,
+    pub button: MouseButton,
+    pub tilemap: Entity,
+    pub index: IVec2,
+    pub tile: Entity,
+}
+
+/// Sent when the cursor moves onto another tile while holding
+/// a mouse button that was pressed on a tile.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub struct TileDragged {
+    pub camera: Entity,
+    pub button: MouseButton,
+    /// The tile where the button was pressed.
+    pub start: PickedTile,
+    pub tilemap: Entity,
+    pub index: IVec2,
+    pub tile: Entity,
+}
+
+/// The tiles that are currently under the cursor of each camera.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct TilemapPicking {
+    pub(crate) hovered: HashMap<Entity, PickedTile>,
+}
+
+impl TilemapPicking {
+    #[inline]
+    pub fn get_hovered(&self, camera: Entity) -> Option<PickedTile> {
+        self.hovered.get(&camera).copied()
+    }
+}
+
+pub fn tile_picker(
+    cameras_query: Query<(Entity, &Camera, &GlobalTransform)>,
+    windows_query: Query<&Window>,
+    primary_window_query: Query<Entity, With<PrimaryWindow>>,
+    tilemaps_query: Query<
+        (
+            Entity,
+            &TilemapType,
+            &TilePivot,
+            &TilemapSlotSize,
+            &GlobalTransform,
+            &TilemapStorage,
+            Option<&TilemapAabbs>,
+            Option<&TilemapTexture>,
+            Option<&TilemapLayerOpacities>,
+            Option<&InheritedVisibility>,
+        ),
+        Without<InvisibleTilemap>,
+    >,
+    tiles_query: Query<&Tile>,
+    mouse: Res<Input<MouseButton>>,
+    mut picking: ResMut<TilemapPicking>,
+    mut drag_starts: Local<HashMap<(Entity, MouseButton), PickedTile>>,
+    mut hovered_event: EventWriter<TileHovered>,
+    mut clicked_event: EventWriter<TileClicked>,
+    mut dragged_event: EventWriter<TileDragged>,
+) {
+    let primary_window = primary_window_query.get_single().ok();
+
+    // The tilemaps on the top come first.
+    let mut tilemaps = tilemaps_query
+        .iter()
+        .filter(|(.., visibility)| visibility.map(|v| v.get()).unwrap_or(true))
+        .collect::<Vec<_>>();
+    tilemaps.sort_by(|a, b| b.4.translation().z.total_cmp(&a.4.translation().z));
+
+    for (camera_entity, camera, camera_transform) in cameras_query.iter() {
+        if !camera.is_active {
+            continue;
+        }
+
+        let cursor = match camera.target.normalize(primary_window) {
+            Some(NormalizedRenderTarget::Window(window)) => windows_query
+                .get(window.entity())
+                .ok()
+                .and_then(|w| w.cursor_position()),
+            _ => None,
+        }
+        .and_then(|c| camera.viewport_to_world_2d(camera_transform, c));
+
+        let picked = cursor.and_then(|cursor| {
+            tilemaps.iter().find_map(
+                |(
+                    entity,
+                    ty,
+                    pivot,
+                    slot_size,
+                    transform,
+                    storage,
+                    aabbs,
+                    texture,
+                    opacities,
+                    _,
+                )| {
+                    if aabbs.is_some_and(|a| !a.world_aabb.contains(cursor)) {
+                        return None;
+                    }
+
+                    let index = coordinates::world_to_index(
+                        cursor,
+                        ty,
+                        &TilemapTransform::from_global(transform),
+                        pivot.0,
+                        slot_size.0,
+                    );
+                    let tile = storage.get(index)?;
+                    tiles_query
+                        .get(tile)
+                        .is_ok_and(|t| is_tile_visible(t, texture.is_some(), *opacities))
+                        .then_some(PickedTile {
+                            tilemap: *entity,
+                            index,
+                            tile,
+                        })
+                },
+            )
+        });
+
+        let prev = match picked {
+            Some(picked) => picking.hovered.insert(camera_entity, picked),
+            None => picking.hovered.remove(&camera_entity),
+        };
+        let Some(picked) = picked else {
+            continue;
+        };
+
+        if prev != Some(picked) {
+            hovered_event.send(TileHovered {
+                camera: camera_entity,
+                tilemap: picked.tilemap,
+                index: picked.index,
+                tile: picked.tile,
+            });
+
+            drag_starts
+                .iter()
+                .filter(|((camera, _), _)| *camera == camera_entity)
+                .for_each(|((_, button), start)| {
+                    dragged_event.send(TileDragged {
+                        camera: camera_entity,
+                        button: *button,
+                        start: *start,
+                        tilemap: picked.tilemap,
+                        index: picked.index,
+                        tile: picked.tile,
+                    });
+                });
+        }
+
+        mouse.get_just_pressed().for_each(|button| {
+            clicked_event.send(TileClicked {
+                camera: camera_entity,
+                button: *button,
+                tilemap: picked.tilemap,
+                index: picked.index,
+                tile: picked.tile,
+            });
+            drag_starts.insert((camera_entity, *button), picked);
+        });
+    }
+
+    mouse.get_just_released().for_each(|button| {
+        drag_starts.retain(|(_, b), _| b != button);
+    });
+}
+
+/// Tiles that are fully transparent can't be picked.
+fn is_tile_visible(tile: &Tile, textured: bool, opacities: Option<&TilemapLayerOpacities>) -> bool {
+    if tile.color.w <= 0. {
+        return false;
+    }
+
+    if !textured {
+        return true;
+    }
+
+    let opacity = |layer| opacities.map(|o| o.get(layer)).unwrap_or(1.);
+    match &tile.texture {
+        TileTexture::Static(layers) => layers.iter().enumerate().any(|(i, layer)| {
+            (layer.texture_index >= 0 || layer.animation.is_some()) && opacity(i) > 0.
+        }),
+        TileTexture::Animated(_) => opacity(0) > 0.,
+    }
+}
@@ -1,18 +1,32 @@
 use bevy::{
-    ecs::system::{ParallelCommands, Query},
+    ecs::{
+        event::{Event, EventWriter},
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Local, ParallelCommands, Query, Res},
+    },
     math::IVec2,
     prelude::{Component, Entity, Vec4},
     reflect::Reflect,
-    render::render_resource::ShaderType,
+    time::Time,
+    utils::EntityHashMap,
 };
 
-use super::{buffers::Tiles, map::TilemapStorage};
+use super::{
+    buffers::Tiles,
+    map::{TilemapAnimationPlayback, TilemapStorage},
+};
 
 #[derive(Debug, Default, Clone, Copy, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileLayer {
     pub(crate) texture_index: i32,
     pub(crate) flip: u32,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) rotation: TileRotation,
+    /// Overrides the texture index of this layer if set.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) animation: Option<TileAnimation>,
 }
 
 impl TileLayer {
@@ -20,6 +34,8 @@ impl TileLayer {
         Self {
             texture_index: -1,
             flip: 0,
+            rotation: TileRotation::None,
+            animation: None,
         }
     }
 
@@ -29,7 +45,19 @@ impl TileLayer {
     }
 
     pub fn with_flip(mut self, flip: TileFlip) -> Self {
-        self.flip |= flip as u32;
+        self.flip |= flip.bits();
+        self
+    }
+
+    /// Rotate this layer clockwise. Applied after the flipping.
+    pub fn with_rotation(mut self, rotation: TileRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Animate this layer. The animation is played independently of the other layers.
+    pub fn with_animation(mut self, animation: TileAnimation) -> Self {
+        self.animation = Some(animation);
         self
     }
 
@@ -55,31 +83,48 @@ pub struct LayerUpdater {
 
 #[derive(Default, Component, Clone, Reflect)]
 pub struct TileUpdater {
-    pub layer: Option<LayerUpdater>,
+    /// Applied in order, so the later ones win if they update the same layer.
+    pub layers: Vec<LayerUpdater>,
     pub color: Option<Vec4>,
 }
 
-#[repr(u32)]
-#[derive(Debug, Clone, Copy, Reflect)]
-pub enum TileFlip {
-    None = 0b00,
-    Horizontal = 0b01,
-    Vertical = 0b10,
-    Both = 0b11,
-}
-
-impl From<u32> for TileFlip {
-    fn from(value: u32) -> Self {
-        match value {
-            0b00 => Self::None,
-            0b01 => Self::Horizontal,
-            0b10 => Self::Vertical,
-            0b11 => Self::Both,
-            _ => panic!("Invalid flip value! {}", value),
+impl TileUpdater {
+    /// Apply `other` after the updates in this updater.
+    pub fn merge(&mut self, other: TileUpdater) {
+        self.layers.extend(other.layers);
+        if other.color.is_some() {
+            self.color = other.color;
         }
     }
 }
 
+/// How a tile layer is flipped. The flags can be combined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct TileFlip(u32);
+
+bitflags::bitflags! {
+    impl TileFlip: u32 {
+        const HORIZONTAL = 0b001;
+        const VERTICAL = 0b010;
+        const BOTH = Self::HORIZONTAL.bits() | Self::VERTICAL.bits();
+        /// Flip along the diagonal from the top left to the bottom right, as Tiled does.
+        /// Applied before the horizontal and vertical flipping.
+        const ANTI_DIAGONAL = 0b100;
+    }
+}
+
+/// The clockwise rotation of a tile layer.
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileRotation {
+    #[default]
+    None = 0,
+    Cw90 = 1,
+    Cw180 = 2,
+    Cw270 = 3,
+}
+
 #[derive(Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBuilder {
@@ -113,6 +158,7 @@ impl TileBuilder {
         self
     }
 
+    /// Animate the whole tile. Use `TileLayer::with_animation` to animate a single layer.
     pub fn with_animation(mut self, animation: TileAnimation) -> Self {
         self.texture = TileTexture::Animated(animation);
         self
@@ -136,12 +182,90 @@ impl TileBuilder {
     }
 }
 
-#[derive(ShaderType, Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileAnimation {
     pub start: u32,
     pub length: u32,
     pub fps: u32,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub mode: TileAnimationMode,
+    /// The animation time in seconds when this animation starts playing.
+    /// See `TilemapAnimationPlayback`.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub offset: f32,
+}
+
+impl TileAnimation {
+    pub fn with_mode(mut self, mode: TileAnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set when the animation starts playing. Use different offsets to make tiles play
+    /// out of sync, or the current animation time to play a `TileAnimationMode::Once`
+    /// animation from the beginning.
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The time in seconds to play through the sequence once.
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.length as f32 / self.fps as f32
+    }
+
+    /// The position in the sequence at the animation time `time`.
+    /// This is the same as what the shader computes.
+    pub fn frame_at(&self, time: f32) -> u32 {
+        if self.length == 0 {
+            return 0;
+        }
+
+        // Hold the first frame until the animation starts.
+        let frame = ((time - self.offset) * self.fps as f32).max(0.) as u32;
+        match self.mode {
+            TileAnimationMode::Loop => frame % self.length,
+            TileAnimationMode::PingPong => {
+                let period = (self.length * 2 - 2).max(1);
+                let i = frame % period;
+                if i >= self.length {
+                    period - i
+                } else {
+                    i
+                }
+            }
+            TileAnimationMode::Once => frame.min(self.length - 1),
+        }
+    }
+
+    /// If a `TileAnimationMode::Once` animation has played to the end at the animation time `time`.
+    /// Other animations never finish.
+    #[inline]
+    pub fn is_finished(&self, time: f32) -> bool {
+        self.mode == TileAnimationMode::Once && time - self.offset >= self.duration()
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileAnimationMode {
+    /// Restart from the first frame after the last one.
+    #[default]
+    Loop = 0,
+    /// Play forwards and then backwards.
+    PingPong = 1,
+    /// Play once and hold the last frame.
+    Once = 2,
+}
+
+/// An animation that is not registered in any `TilemapAnimations` yet.
+#[derive(Debug, Clone, Reflect)]
+pub struct RawTileAnimation {
+    pub sequence: Vec<u32>,
+    pub fps: u32,
 }
 
 #[derive(Debug, Clone, Reflect)]
@@ -151,6 +275,20 @@ pub enum TileTexture {
     Animated(TileAnimation),
 }
 
+impl TileTexture {
+    /// The animations of the layers, and the layer they belong to.
+    pub fn animations(&self) -> Vec<(usize, TileAnimation)> {
+        match self {
+            TileTexture::Static(layers) => layers
+                .iter()
+                .enumerate()
+                .filter_map(|(i, l)| l.animation.map(|a| (i, a)))
+                .collect(),
+            TileTexture::Animated(anim) => vec![(0, *anim)],
+        }
+    }
+}
+
 #[derive(Component, Clone, Debug, Reflect)]
 pub struct Tile {
     pub tilemap_id: Entity,
@@ -179,8 +317,8 @@ pub fn tile_updater(
     tiles_query
         .par_iter_mut()
         .for_each(|(entity, mut tile, updater)| {
-            if let Some(layer) = &updater.layer {
-                if let TileTexture::Static(ref mut tex) = tile.texture {
+            if let TileTexture::Static(ref mut tex) = tile.texture {
+                for layer in &updater.layers {
                     match layer.position {
                         TileLayerPosition::Top => {
                             tex.push(layer.layer);
@@ -205,3 +343,197 @@ pub fn tile_updater(
             });
         });
 }
+
+/// Sent when a `TileAnimationMode::Once` animation of a tile has played to the end.
+#[derive(Event, Debug, Clone, Reflect)]
+pub struct TileAnimationFinished {
+    pub tilemap: Entity,
+    pub tile: Entity,
+    pub index: IVec2,
+    /// The layer that the animation belongs to.
+    pub layer: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PendingAnimation {
+    tilemap: Entity,
+    index: IVec2,
+    layer: usize,
+    animation: TileAnimation,
+    /// Finished animations are kept so they won't be sent again when other parts of the tile change.
+    finished: bool,
+}
+
+impl PendingAnimation {
+    #[inline]
+    fn is_same(&self, layer: usize, animation: &TileAnimation) -> bool {
+        self.layer == layer
+            && self.animation.start == animation.start
+            && self.animation.offset == animation.offset
+    }
+}
+
+/// Mirrors the timing of the one shot animations on the CPU and sends `TileAnimationFinished`.
+pub fn animation_finish_notifier(
+    changed_tiles_query: Query<(Entity, &Tile), Changed<Tile>>,
+    playbacks_query: Query<&TilemapAnimationPlayback>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    mut pending: Local<EntityHashMap<Entity, Vec<PendingAnimation>>>,
+    mut finished: EventWriter<TileAnimationFinished>,
+    time: Res<Time>,
+) {
+    removed_tiles.read().for_each(|entity| {
+        pending.remove(&entity);
+    });
+
+    changed_tiles_query.for_each(|(entity, tile)| {
+        let prev = pending.remove(&entity).unwrap_or_default();
+        let animations = tile
+            .texture
+            .animations()
+            .into_iter()
+            .filter(|(_, anim)| anim.mode == TileAnimationMode::Once)
+            .map(|(layer, animation)| PendingAnimation {
+                tilemap: tile.tilemap_id,
+                index: tile.index,
+                layer,
+                animation,
+                finished: prev
+                    .iter()
+                    .any(|p| p.finished && p.is_same(layer, &animation)),
+            })
+            .collect::<Vec<_>>();
+
+        if !animations.is_empty() {
+            pending.insert(entity, animations);
+        }
+    });
+
+    pending.iter_mut().for_each(|(tile, animations)| {
+        animations.iter_mut().filter(|p| !p.finished).for_each(|p| {
+            let anim_time = playbacks_query
+                .get(p.tilemap)
+                .map_or(time.elapsed_seconds(), |playback| playback.time());
+            if !p.animation.is_finished(anim_time) {
+                return;
+            }
+
+            p.finished = true;
+            finished.send(TileAnimationFinished {
+                tilemap: p.tilemap,
+                tile: *tile,
+                index: p.index,
+                layer: p.layer,
+            });
+        });
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::ecs::{event::Events, schedule::Schedule, world::World};
+
+    use super::*;
+
+    fn animation(mode: TileAnimationMode) -> TileAnimation {
+        TileAnimation {
+            start: 1,
+            length: 4,
+            fps: 2,
+            mode,
+            offset: 1.,
+        }
+    }
+
+    #[test]
+    fn test_frame_at() {
+        let once = animation(TileAnimationMode::Once);
+        assert_eq!(once.frame_at(0.), 0);
+        assert_eq!(once.frame_at(2.), 2);
+        assert_eq!(once.frame_at(10.), 3);
+
+        let looping = animation(TileAnimationMode::Loop);
+        assert_eq!(looping.frame_at(3.), 0);
+        assert_eq!(looping.frame_at(3.5), 1);
+
+        let ping_pong = animation(TileAnimationMode::PingPong);
+        let frames = (0..8)
+            .map(|i| ping_pong.frame_at(1. + i as f32 * 0.5))
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![0, 1, 2, 3, 2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_is_finished() {
+        let once = animation(TileAnimationMode::Once);
+        assert!(!once.is_finished(2.9));
+        assert!(once.is_finished(3.));
+        assert!(!animation(TileAnimationMode::Loop).is_finished(100.));
+    }
+
+    #[test]
+    fn test_flip() {
+        let layer = TileLayer::new()
+            .with_flip(TileFlip::ANTI_DIAGONAL)
+            .with_flip(TileFlip::HORIZONTAL);
+        assert_eq!(layer.flip, 0b101);
+        assert_eq!(
+            TileFlip::from_bits_truncate(layer.flip),
+            TileFlip::ANTI_DIAGONAL | TileFlip::HORIZONTAL
+        );
+        assert_eq!(TileFlip::from_bits_truncate(0b1111), TileFlip::all());
+    }
+
+    #[test]
+    fn test_animation_finished_once() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<TileAnimationFinished>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(animation_finish_notifier);
+
+        let tilemap = world.spawn_empty().id();
+        let tile = world
+            .spawn(Tile {
+                tilemap_id: tilemap,
+                chunk_index: IVec2::ZERO,
+                in_chunk_index: 0,
+                index: IVec2::ZERO,
+                texture: TileTexture::Static(vec![
+                    TileLayer::new().with_animation(animation(TileAnimationMode::Once))
+                ]),
+                color: Vec4::ONE,
+            })
+            .id();
+
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Events<TileAnimationFinished>>().len(), 0);
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(3));
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Events<TileAnimationFinished>>().len(), 1);
+
+        // Changing the color doesn't restart the animation.
+        world.get_mut::<Tile>(tile).unwrap().color = Vec4::ZERO;
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Events<TileAnimationFinished>>().len(), 1);
+
+        // But replacing the animation does.
+        world.get_mut::<Tile>(tile).unwrap().texture = TileTexture::Static(vec![TileLayer::new()
+            .with_animation(TileAnimation {
+                offset: 3.,
+                ..animation(TileAnimationMode::Once)
+            })]);
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Events<TileAnimationFinished>>().len(), 1);
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(2));
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Events<TileAnimationFinished>>().len(), 2);
+    }
+}